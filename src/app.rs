@@ -1,11 +1,75 @@
 // app.rs
 use copypasta::ClipboardProvider;
+use std::borrow::Cow;
 use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::bookmark::{self, Bookmark};
+use crate::bus::Update;
+use crate::clipboard;
+use crate::config::{Config, Feedback};
+use crate::entry::{self, split_lines, HistoryEntry, HistoryLine, HistoryRecord, RunInfo};
+use crate::ignore::IgnoreList;
+use crate::import;
+use crate::normalize::{self, Normalizer};
+use crate::menu::{ContextMenu, MenuItem};
+use crate::merge::BookmarkMerge;
+use crate::presets::{Preset, Presets};
+use crate::process;
+use crate::profile::{Phase, StartupProfile};
+use crate::recall::QueryHistory;
+use crate::redact::Redactor;
+use crate::search::{MatchMode, Matcher, SearchDisplay, Usage};
+use crate::sources::{self, HistoryProvider, HistorySource};
+use crate::sqlite;
+use crate::storage::{BookmarkStore, DataFile};
+use crate::stats::{self, HistoryStats};
+use crate::views::{display_command, truncate_for_display, view_for};
+use crate::watch;
+use crate::zoxide;
 
-#[cfg(not(target_os = "windows"))]
-use std::env;
+/// Bookmarks listed under "Recently used"
+const RECENT_BOOKMARKS: usize = 5;
+
+/// Score added to bookmarked matches when `boost_bookmarks` is enabled
+const BOOKMARK_BOOST: i64 = 32;
+
+/// How long the screen stays inverted for `Feedback::Flash`
+const FLASH_DURATION: Duration = Duration::from_millis(150);
+
+/// Program filter chips shown above the list
+const MAX_CHIPS: usize = 8;
+
+/// Longest command prefix quoted in a confirmation prompt
+const MAX_PROMPT_CHARS: usize = 40;
+
+/// Kept next to the bookmarks
+const PRESETS_FILE: DataFile = DataFile {
+    name: "presets.json",
+    legacy: ".term_kit_presets",
+};
+const SEARCHES_FILE: DataFile = DataFile {
+    name: "searches.json",
+    legacy: ".term_kit_searches",
+};
+
+/// Hotkeys applying the first presets, shown in the preset picker
+const PRESET_KEYS: [&str; 9] = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+/// How Nushell's plaintext history writes a line break inside one command
+const NUSHELL_NEWLINE: &str = "<\\n>";
+
+/// First line of `read_nushell_sqlite`'s dump; typed commands can't contain NUL
+const NUSHELL_SQLITE_DUMP: &[u8] = b"\0nushell sqlite\n";
 
 const HELP_TEXT: &str = r#"
 Navigation:
@@ -13,17 +77,83 @@ Navigation:
   j/k            - Move selection up/down
   Enter          - Copy selected command
   /              - Start search (in input mode)
+  s              - Cycle sort: recency, frequency, alphabetical, length
+  o              - Reverse the order, e.g. oldest commands first
+  u              - Collapse/expand duplicate entries (Enter copies the newest form)
+  v              - List every form of the selected entry to copy an older one
+  D              - Delete the selected entry from the history file (bash, zsh,
+                   fish); the file is backed up first
+  t              - Show/hide when each entry last ran (timestamped histories)
+  Left/Right     - Filter by one of the most used programs
+  m              - Actions for the selected entry
+  e              - Show which $VARIABLES the selected entry uses and
+                   whether they are set (nothing is executed)
+  x              - Show/hide likely secrets (AWS keys, --password=..., bearer
+                   tokens), masked as **** by default; copying a masked
+                   command asks first, then copies it in full
+  p              - Pick a saved search preset
+  S              - Show another shell's history (bash, zsh, fish, ...)
+  r              - Reload the history (re-runs history_command)
+  1-9            - Apply search preset 1-9
   h              - Toggle help
   q              - Quit
 
 Search Mode:
-  Type to filter history
+  Type to fuzzy-filter history (best and most frequently/recently used first)
+  Space-separated words must all match, in any order; "quote" phrases
+  !word excludes entries containing word, e.g. git !push
+  cmd:name only matches entries running that program, e.g. cmd:git push
+  tag:name only matches commands bookmarked with that tag
+  note:text only matches bookmarks whose note contains text
+  Lowercase queries ignore case, any uppercase letter makes it exact
+  Tab - Copy the most frequently used match
+  Ctrl+R - Cycle fuzzy, glob (git * --force) and regex matching
+  Ctrl+F - Toggle filtering/highlighting matches; when highlighting,
+           Enter keeps the query and n/N jump to the next/previous match
+  Ctrl+S - Save the query as a named preset
+  Up/Down - Recall earlier searches (on an empty or recalled query)
+  Left/Right, Home/End - Move the cursor in the query
+  Ctrl+W / Ctrl+U - Delete the previous word / clear the query
+  Ctrl+V - Paste from the clipboard (pasting into the terminal works too)
   Press ESC to cancel search
 
 Bookmark Mode:
   b - Add current command to bookmarks
+  ] / [ - Jump to next/previous bookmarked entry in history
   B - Toggle bookmark/history mode
-  d - Delete selected bookmark
+  Tab / Shift+Tab - Switch to the most/least recently used other mode
+  d - Move the selected bookmark to the trash (delete it for good from there)
+  u - Restore the selected bookmark from the trash
+  t - Edit the tags of the selected bookmark
+  a - Write a note on the selected bookmark, shown under the list
+  e - Edit the command of the selected bookmark (e shows variables in history)
+  R - Give the selected bookmark a title, listed instead of the command
+      (Enter still copies the command; searches match the title too)
+  g - Move the selected bookmark to a group, listed in its own section
+  Space - Collapse/expand the selected group (Enter on its header too)
+  Copying a bookmark counts a use; the last few copied are listed first
+  under "Recently used"
+  J / K, Ctrl+Down/Up - Move the selected bookmark down/up (sorted by recency)
+  p - Pin/unpin the selected bookmark, listed first in any sort order
+      (presets open with p from history mode)
+  I - Import bookmarks from another bookmark file, a backup or a list of
+      commands; ones already bookmarked are skipped
+      (also: term-kit import <file>)
+
+Merge Mode (bookmarks changed on disk):
+  Space - Accept/reject the selected change
+  (~ both: edited here and on disk; accepting takes the disk version,
+   rejecting keeps this one)
+  w     - Save the merged bookmarks
+
+zoxide:
+  z - Add the directory in the selected command to zoxide
+  Z - Copy a "z <dir>" jump command
+
+Macros:
+  Q<a-z> - Start recording actions into a register
+  Q      - Stop recording
+  @<a-z> - Replay a recorded register
 "#;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,39 +162,518 @@ pub enum MoveDirection {
     Down,
 }
 
+/// Cursor movement inside the search input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorMotion {
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+/// Everything the user can ask the app to do, independent of the key that triggered it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ShowHelp,
+    /// Leave search, close help, or return to history mode, in that order
+    Cancel,
+    Move(MoveDirection),
+    CopySelection,
+    ToggleBookmark,
+    DeleteBookmark,
+    /// Select the next/previous history entry that is bookmarked
+    JumpBookmark(MoveDirection),
+    /// Add the directory referenced by the selected entry to zoxide
+    ZoxideAdd,
+    /// Copy a `z <dir>` jump command for the selected entry's directory
+    CopyZoxideJump,
+    NextMode,
+    /// Tab/Shift-Tab: switch to the most/least recently used other mode
+    RecentMode(MoveDirection),
+    /// Cycle the order of the history list
+    CycleSort,
+    /// Reverse the list, e.g. oldest entries first
+    ToggleOrder,
+    /// Select the next/previous program chip, pre-filtering by that program
+    CycleChip(MoveDirection),
+    /// Show repeated history entries once or every time
+    ToggleCollapse,
+    /// List every recorded form of the selected entry
+    ShowVariants,
+    /// Copy the n-th form listed by `ShowVariants`
+    CopyVariant(usize),
+    /// Ask before deleting the selected entry from the history file
+    DeleteEntry,
+    /// Delete what the confirmation asked about
+    ConfirmDeletion,
+    StartSearch,
+    SearchInput(char),
+    SearchBackspace,
+    /// Ctrl+V: insert the clipboard contents into the query
+    PasteClipboard,
+    SearchCursor(CursorMotion),
+    /// Delete the word before the cursor
+    SearchDeleteWord,
+    /// Clear the query but keep searching
+    SearchClear,
+    /// Up/Down while searching: recall past queries, or move once a query is typed
+    RecallQuery(MoveDirection),
+    /// Copy the most frequently used entry matching the query
+    AcceptTopMatch,
+    /// Cycle the search between fuzzy, glob and regex matching
+    ToggleMatchMode,
+    /// Switch between filtering the list and highlighting matches in it
+    ToggleSearchDisplay,
+    /// Leave the search bar keeping the query, to step through highlighted matches
+    CommitSearch,
+    /// Select the next/previous entry matching the query
+    JumpMatch(MoveDirection),
+    /// Start waiting for a register to record into, or stop the running recording
+    RecordMacro,
+    /// Start waiting for a register to replay
+    PlayMacro,
+    /// Register chosen after `RecordMacro` or `PlayMacro`
+    Register(char),
+    /// Show the actions available for the selected entry
+    OpenMenu,
+    /// Show which variables the selected command expands
+    ToggleExpansions,
+    /// Show or hide when each history entry last ran
+    ToggleTimestamps,
+    /// Start naming the current query to save it as a preset
+    SavePreset,
+    /// Change the tags of the selected bookmark
+    EditTags,
+    /// Write or change the note of the selected bookmark
+    EditNote,
+    /// Give the selected bookmark a title to list instead of its command
+    EditTitle,
+    /// Fix the command of the selected bookmark, keeping everything else
+    EditCommand,
+    /// Move the selected bookmark to another group
+    MoveToGroup,
+    /// Collapse or expand the group of the selected row
+    ToggleGroup,
+    /// Swap the selected bookmark with the one above/below it
+    MoveBookmark(MoveDirection),
+    /// Keep the selected bookmark at the top of the list, or stop doing so
+    TogglePin,
+    /// Take the selected bookmark back out of the trash
+    RestoreBookmark,
+    /// Swap the near-duplicate bookmark the warning was about for the new command
+    ReplaceBookmark,
+    /// Bookmark the new command next to its near-duplicate
+    AddBookmarkAnyway,
+    /// Ask for a file to import bookmarks from
+    ImportBookmarks,
+    /// Show masked secrets as typed, or mask them again
+    ToggleSecrets,
+    /// Copy the command the secret warning was about
+    CopySecret,
+    PromptInput(char),
+    PromptBackspace,
+    PromptCursor(CursorMotion),
+    /// Use the text typed into the open prompt
+    ConfirmPrompt,
+    /// Show the saved presets in a menu
+    OpenPresets,
+    /// Search with the n-th saved preset
+    ApplyPreset(usize),
+    /// Offer the shells whose history can be shown instead
+    OpenShells,
+    /// Reload from the n-th of `ShellType::known()`, or the detected shell for `None`
+    SwitchShell(Option<usize>),
+    /// Read the history source again
+    ReloadHistory,
+    /// Run the highlighted context menu action
+    MenuAccept,
+    /// Flip accept/reject on the selected merge change
+    ToggleMergeChoice,
+    /// Write the resolved bookmark merge to disk
+    ApplyMerge,
+}
+
+/// What the next register key is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegisterOp {
+    Record,
+    Replay,
+}
+
+/// What a `TextPrompt` is asking for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    PresetName,
+    BookmarkTags,
+    BookmarkNote,
+    BookmarkTitle,
+    BookmarkGroup,
+    BookmarkCommand,
+    ImportPath,
+}
+
+impl PromptKind {
+    /// Shown in front of the typed text
+    pub fn label(self) -> &'static str {
+        match self {
+            PromptKind::PresetName => "Save preset as: ",
+            PromptKind::BookmarkTags => "Tags: ",
+            PromptKind::BookmarkNote => "Note: ",
+            PromptKind::BookmarkTitle => "Title: ",
+            PromptKind::BookmarkGroup => "Move to group: ",
+            PromptKind::BookmarkCommand => "Command: ",
+            PromptKind::ImportPath => "Import bookmarks from: ",
+        }
+    }
+
+    pub fn hint(self) -> &'static str {
+        match self {
+            PromptKind::PresetName => "  (Enter to save, Esc to cancel)",
+            PromptKind::BookmarkTags => "  (space-separated, Enter to save, Esc to skip)",
+            PromptKind::BookmarkNote => "  (Enter to save, empty to remove, Esc to cancel)",
+            PromptKind::BookmarkTitle => {
+                "  (Enter to save, empty to list the command, Esc to cancel)"
+            }
+            PromptKind::BookmarkGroup => "  (Enter to move, empty for no group, Esc to cancel)",
+            PromptKind::BookmarkCommand => "  (Enter to save, Esc to cancel)",
+            PromptKind::ImportPath => "  (file path, Enter to import, Esc to cancel)",
+        }
+    }
+}
+
+/// A line of text typed in place of the search bar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextPrompt {
+    pub kind: PromptKind,
+    pub text: String,
+    /// Byte offset of the text cursor
+    pub cursor: usize,
+    /// The bookmark being edited
+    target: Option<String>,
+}
+
+impl TextPrompt {
+    fn new(kind: PromptKind, text: String, target: Option<String>) -> Self {
+        Self {
+            kind,
+            cursor: text.len(),
+            text,
+            target,
+        }
+    }
+
+    fn insert(&mut self, c: char) {
+        self.text.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    fn backspace(&mut self) {
+        if let Some((index, _)) = self.text[..self.cursor].char_indices().next_back() {
+            self.text.remove(index);
+            self.cursor = index;
+        }
+    }
+
+    fn move_cursor(&mut self, motion: CursorMotion) {
+        let before = &self.text[..self.cursor];
+        let after = &self.text[self.cursor..];
+        self.cursor = match motion {
+            CursorMotion::Left => before.char_indices().next_back().map_or(0, |(i, _)| i),
+            CursorMotion::Right => self.cursor + after.chars().next().map_or(0, char::len_utf8),
+            CursorMotion::Home => 0,
+            CursorMotion::End => self.text.len(),
+        };
+    }
+}
+
+/// A part of the bookmark list, listed under a header row
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Section {
+    /// The last few bookmarks copied, again
+    Recent,
+    /// Headed only below the recent ones
+    Ungrouped,
+    Group(String),
+    /// Deleted bookmarks, until they expire
+    Trash,
+}
+
+impl Section {
+    pub fn name(&self) -> &str {
+        match self {
+            Section::Recent => "Recently used",
+            Section::Ungrouped => "Ungrouped",
+            Section::Group(name) => name,
+            Section::Trash => "Trash",
+        }
+    }
+}
+
+/// Header row of a section in the bookmark list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupRow {
+    pub section: Section,
+    /// Bookmarks in the section, listed or not
+    pub count: usize,
+    pub collapsed: bool,
+}
+
+/// Top-level modes, each backed by a view in `crate::views`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Mode {
+    History,
+    Bookmarks,
+    /// Resolving bookmark changes made elsewhere; entered automatically, never cycled to
+    Merge,
+}
+
+impl Mode {
+    /// All modes in switching order
+    pub const ALL: [Mode; 2] = [Mode::History, Mode::Bookmarks];
+
+    /// Lowercase name used in status messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::History => "history",
+            Mode::Bookmarks => "bookmark",
+            Mode::Merge => "merge",
+        }
+    }
+
+    /// The mode after this one when cycling with `B`
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Order of the history list; `Recency` keeps match quality first while searching
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortMode {
+    #[default]
+    Recency,
+    Frequency,
+    Alphabetical,
+    Length,
+}
+
+impl SortMode {
+    /// All sort modes in cycling order
+    pub const ALL: [SortMode; 4] = [
+        SortMode::Recency,
+        SortMode::Frequency,
+        SortMode::Alphabetical,
+        SortMode::Length,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Recency => "recency",
+            SortMode::Frequency => "frequency",
+            SortMode::Alphabetical => "alphabetical",
+            SortMode::Length => "length",
+        }
+    }
+
+    /// The sort mode after this one when cycling with `s`
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|m| *m == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum ShellType {
     PowerShell,
+    Cmd,
     Zsh,
     Bash,
     Fish,
+    Nushell,
+    /// ksh93, mksh and other Korn shells
+    Ksh,
+    Tcsh,
+    Xonsh,
+    Elvish,
     Unknown(String),
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct App {
-    bookmark_path: PathBuf,
     history: Vec<String>,
+    older_history: Vec<String>,
+    /// Set while the background loader is still parsing the history
+    #[serde(skip)]
+    history_loading: bool,
+    /// Where background loads deliver their result, kept for reloading
+    #[serde(skip)]
+    loader: Option<Sender<Update>>,
+    /// Counts loads so a slow, superseded one can't replace a newer result
+    #[serde(skip)]
+    history_generation: u64,
+    /// The generation being watched for changes; older watchers stop when it moves on
+    #[serde(skip)]
+    watch_generation: Arc<AtomicU64>,
+    /// Set while reloading because the history changed on disk
+    #[serde(skip)]
+    live_refresh: bool,
     queried_history: Vec<String>,
+    /// Bookmarks matching the search query, by section
+    #[serde(skip)]
+    queried_bookmarks: Vec<String>,
+    /// Rows of `queried_bookmarks` that head a group, which hold its name
+    #[serde(skip)]
+    group_rows: HashMap<usize, GroupRow>,
+    /// Sections whose bookmarks are hidden; the trash starts out folded
+    #[serde(skip)]
+    collapsed_sections: HashSet<Section>,
     pub selected: usize,
     pub search_mode: bool,
     pub search_query: String,
+    #[serde(skip)]
+    search_cursor: usize,
+    #[serde(skip)]
+    pub match_mode: MatchMode,
+    #[serde(skip)]
+    pub search_display: SearchDisplay,
+    /// The compiled query, kept for highlighting matches while drawing
+    #[serde(skip)]
+    matcher: Option<Matcher>,
+    #[serde(skip)]
+    pub sort_mode: SortMode,
+    /// Flip the sort order, e.g. to read a session's commands in the order they ran
+    #[serde(skip)]
+    oldest_first: bool,
+    #[serde(skip)]
+    collapse_duplicates: bool,
+    /// Entries with the same normalized form are duplicates
+    #[serde(skip)]
+    normalizer: Normalizer,
+    /// Normalized forms of the entries that have one different from the entry
+    #[serde(skip)]
+    normalized: HashMap<String, String>,
+    /// Distinct forms of each collapsed group, newest first, keyed by `duplicate_key`
+    #[serde(skip)]
+    variants: HashMap<String, Vec<String>>,
+    /// Forms offered by the open variants popup
+    #[serde(skip)]
+    variant_choices: Vec<String>,
+    /// Commands the open delete confirmation would remove from the history file
+    #[serde(skip)]
+    pending_deletion: Vec<String>,
+    /// Command being bookmarked and the existing near-duplicate the warning is about
+    #[serde(skip)]
+    pending_bookmark: Option<(String, String)>,
+    /// Command with a masked secret waiting for its copy to be confirmed, and
+    /// whether copying it counts a bookmark use
+    #[serde(skip)]
+    pending_copy: Option<(String, bool)>,
+    /// Most common programs in the history, offered as quick filters
+    #[serde(skip)]
+    program_chips: Vec<String>,
+    #[serde(skip)]
+    active_chip: Option<usize>,
+    #[serde(skip)]
+    startup: StartupProfile,
+    #[serde(skip)]
+    query_history: QueryHistory,
+    #[serde(skip)]
+    presets: Presets,
+    /// Preset name or tags being typed
+    #[serde(skip)]
+    pub prompt: Option<TextPrompt>,
     pub skipped_items: usize,
     pub size: Cell<usize>,
     pub show_help: bool,
+    #[serde(skip)]
+    pub menu: Option<ContextMenu>,
     pub should_quit: bool,
     pub message: String,
-    pub bookmarks: Vec<String>,
-    pub bookmark_mode: bool,
+    pub bookmarks: Vec<Bookmark>,
+    /// Bookmarks as last read from or written to disk
+    bookmark_snapshot: Vec<Bookmark>,
+    /// Why the bookmark file must not be written, e.g. it was saved by a
+    /// newer version; changes are kept in memory only
+    #[serde(skip)]
+    bookmarks_read_only: Option<String>,
+    #[serde(skip)]
+    pub merge: Option<BookmarkMerge>,
+    pub mode: Mode,
+    /// Switchable modes, most recently used first, for Tab/Shift-Tab
+    #[serde(skip)]
+    recent_modes: Vec<Mode>,
+    #[serde(skip)]
+    pub show_expansions: bool,
+    /// Whether history rows show when they last ran
+    #[serde(skip)]
+    pub show_timestamps: bool,
+    /// Whether secrets are listed as typed instead of masked
+    #[serde(skip)]
+    reveal_secrets: bool,
+    /// Finds the secrets masked in the list
+    #[serde(skip)]
+    redactor: Redactor,
     current_shell: ShellType,
+    #[serde(skip)]
+    history_error: Option<String>,
+    #[serde(skip)]
+    pub pending_register: Option<RegisterOp>,
+    #[serde(skip)]
+    recording: Option<(char, Vec<Action>)>,
+    #[serde(skip)]
+    macros: HashMap<char, Vec<Action>>,
+    /// How often and how recently each command occurs in the whole parsed history
+    #[serde(skip)]
+    usage: HashMap<String, Usage>,
+    /// When each command last ran and for how long, where the shell recorded it
+    #[serde(skip)]
+    runs: HashMap<String, RunInfo>,
+    /// Most frequent entry matching the current query
+    #[serde(skip)]
+    top_match: Option<String>,
+    #[serde(skip)]
+    pub config: Config,
+    /// Bell requested since the last frame
+    #[serde(skip)]
+    bell: bool,
+    #[serde(skip)]
+    flash_until: Option<Instant>,
 }
 
 impl ShellType {
     /// Detect the current shell based on platform and environment
     pub fn detect() -> Self {
+        if let Ok(name) = env::var("TERM_KIT_SHELL") {
+            match Self::from_name(&name) {
+                Some(shell) => return shell,
+                None => eprintln!("Ignoring unknown TERM_KIT_SHELL '{}'", name),
+            }
+        }
+
+        // Nushell exports its version to child processes on every platform
+        if env::var_os("NU_VERSION").is_some() {
+            return ShellType::Nushell;
+        }
+        // xonsh runs under Python, so its process name isn't reliable
+        if env::var_os("XONSH_VERSION").is_some() {
+            return ShellType::Xonsh;
+        }
+
+        // $SHELL is only the login shell, e.g. bash when fish was started from it
+        if let Some(shell) = process::parent_shell() {
+            return shell;
+        }
+
         #[cfg(target_os = "windows")]
         {
-            ShellType::PowerShell
+            // Without a recognisable parent: cmd.exe defines PROMPT for its
+            // children, PowerShell does not
+            if env::var_os("PROMPT").is_some() {
+                ShellType::Cmd
+            } else {
+                ShellType::PowerShell
+            }
         }
 
         #[cfg(not(target_os = "windows"))]
@@ -80,323 +689,2729 @@ impl ShellType {
                 ShellType::Fish
             } else if shell_name.contains("bash") {
                 ShellType::Bash
+            } else if shell_name.rsplit('/').next() == Some("nu") {
+                ShellType::Nushell
+            } else if shell_name.contains("ksh") {
+                ShellType::Ksh
+            } else if shell_name.contains("csh") {
+                ShellType::Tcsh
+            } else if shell_name.contains("xonsh") {
+                ShellType::Xonsh
+            } else if shell_name.contains("elvish") {
+                ShellType::Elvish
             } else {
                 ShellType::Unknown(shell_path)
             }
         }
     }
 
+    /// Short lowercase name, the inverse of `from_name`
+    pub fn name(&self) -> &str {
+        match self {
+            ShellType::PowerShell => "pwsh",
+            ShellType::Cmd => "cmd",
+            ShellType::Zsh => "zsh",
+            ShellType::Bash => "bash",
+            ShellType::Fish => "fish",
+            ShellType::Nushell => "nu",
+            ShellType::Ksh => "ksh",
+            ShellType::Tcsh => "tcsh",
+            ShellType::Xonsh => "xonsh",
+            ShellType::Elvish => "elvish",
+            ShellType::Unknown(path) => path,
+        }
+    }
+
+    /// Parse a shell name as given to `--shell` or `TERM_KIT_SHELL`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "bash" => Some(ShellType::Bash),
+            "zsh" => Some(ShellType::Zsh),
+            "fish" => Some(ShellType::Fish),
+            "nu" | "nushell" => Some(ShellType::Nushell),
+            "pwsh" | "powershell" => Some(ShellType::PowerShell),
+            "cmd" => Some(ShellType::Cmd),
+            "ksh" | "ksh93" | "mksh" | "pdksh" => Some(ShellType::Ksh),
+            // csh is tcsh on most systems that still ship it
+            "tcsh" | "csh" => Some(ShellType::Tcsh),
+            "xonsh" => Some(ShellType::Xonsh),
+            "elvish" => Some(ShellType::Elvish),
+            _ => None,
+        }
+    }
+
+    /// Shells offered by the in-app switcher and merged by the `all` source
+    pub fn known() -> Vec<ShellType> {
+        let mut shells = vec![
+            ShellType::Bash,
+            ShellType::Zsh,
+            ShellType::Fish,
+            ShellType::Nushell,
+            ShellType::PowerShell,
+            ShellType::Ksh,
+            ShellType::Tcsh,
+            ShellType::Xonsh,
+            ShellType::Elvish,
+        ];
+        if cfg!(target_os = "windows") {
+            shells.push(ShellType::Cmd);
+        }
+        shells
+    }
+
     // Get history file path for the shell
     pub fn history_path(&self) -> PathBuf {
-        let base_dirs = directories::BaseDirs::new().expect("Failed to determine system directories");
+        let base_dirs =
+            directories::BaseDirs::new().expect("Failed to determine system directories");
         let mut path = base_dirs.home_dir().to_path_buf();
 
         match self {
             ShellType::PowerShell => {
-                #[cfg(target_os = "windows")]
-                path.push("AppData\\Roaming\\Microsoft\\Windows\\PowerShell\\PSReadLine\\ConsoleHost_history.txt");
-
-                #[cfg(not(target_os = "windows"))]
-                path.push(".local/share/powershell/PSReadLine/ConsoleHost_history.txt");
+                let candidates = Self::powershell_histories(&base_dirs);
+                path = candidates
+                    .iter()
+                    .find(|candidate| candidate.exists())
+                    .or(candidates.first())
+                    .cloned()
+                    .unwrap_or(path);
+            }
+            ShellType::Cmd => {
+                // clink keeps a persistent history file for cmd.exe
+                path = base_dirs
+                    .data_local_dir()
+                    .join("clink")
+                    .join("clink_history");
+            }
+            ShellType::Zsh => {
+                path = self
+                    .histfile(&path)
+                    .unwrap_or_else(|| path.join(".zsh_history"));
+            }
+            ShellType::Bash => {
+                path = self
+                    .histfile(&path)
+                    .unwrap_or_else(|| path.join(".bash_history"));
+            }
+            ShellType::Fish => {
+                path = Self::fish_data_dir(&base_dirs)
+                    .join(format!("{}_history", Self::fish_session()));
+            }
+            ShellType::Nushell => {
+                // The SQLite backend is used when `history.file_format = "sqlite"`
+                let dir = Self::nushell_config_dir(&base_dirs);
+                let sqlite = dir.join("history.sqlite3");
+                path = if sqlite.exists() {
+                    sqlite
+                } else {
+                    dir.join("history.txt")
+                };
+            }
+            ShellType::Ksh => {
+                path = self
+                    .histfile(&path)
+                    .unwrap_or_else(|| path.join(".sh_history"));
+            }
+            ShellType::Tcsh => {
+                path.push(".history");
+            }
+            ShellType::Xonsh => {
+                // The SQLite backend is used when `$XONSH_HISTORY_BACKEND = 'sqlite'`
+                let dir = Self::xonsh_data_dir(&base_dirs);
+                let sqlite = dir.join("xonsh-history.sqlite");
+                path = if sqlite.exists() {
+                    sqlite
+                } else {
+                    dir.join("history_json")
+                };
+            }
+            ShellType::Elvish => {
+                path = Self::elvish_db(&base_dirs);
+            }
+            ShellType::Unknown(_) => {
+                path = self
+                    .histfile(&path)
+                    .unwrap_or_else(|| path.join(".bash_history")); // Fallback
             }
-            ShellType::Zsh => path.push(".zsh_history"),
-            ShellType::Bash => path.push(".bash_history"),
-            ShellType::Fish => path.push(".local/share/fish/fish_history"),
-            ShellType::Unknown(_) => path.push(".bash_history"), // Fallback
         }
         path
     }
 
-    /// Parse shell-specific history format
-    pub fn parse_history(&self, content: Vec<u8>) -> Vec<String> {
-        match self {
-            ShellType::PowerShell => Self::parse_powershell(content),
-            ShellType::Zsh => Self::parse_zsh(content),
-            ShellType::Bash => Self::parse_bash(content),
-            ShellType::Fish => Self::parse_fish(content),
-            ShellType::Unknown(_) => Self::parse_bash(content), // Fallback to bash parsing
+    /// $HISTFILE from the environment, then from the rc files
+    fn histfile(&self, home: &Path) -> Option<PathBuf> {
+        let from_env = env::var("HISTFILE")
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                let expanded = PathBuf::from(Self::expand_vars(&value, home, home));
+                if expanded.is_relative() {
+                    home.join(expanded)
+                } else {
+                    expanded
+                }
+            });
+        from_env.or_else(|| self.histfile_from_rc(home))
+    }
+
+    /// Every place PSReadLine may keep its history, the default first
+    fn powershell_histories(base_dirs: &directories::BaseDirs) -> Vec<PathBuf> {
+        #[cfg(target_os = "windows")]
+        {
+            Self::windows_powershell_histories(base_dirs.data_dir(), base_dirs.data_local_dir())
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            vec![Self::xdg_data_home(base_dirs)
+                .join("powershell")
+                .join("PSReadLine")
+                .join("ConsoleHost_history.txt")]
         }
     }
 
-    // -- History Parsers -- //
+    /// PSReadLine history files of a Windows user with the given %APPDATA% and
+    /// %LOCALAPPDATA%. Windows PowerShell 5 and an installer-installed pwsh 7
+    /// share %APPDATA%, but pwsh from the Microsoft Store writes into its
+    /// package's virtualized copy of it. Linux needs them for WSL.
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    pub fn windows_powershell_histories(roaming: &Path, local: &Path) -> Vec<PathBuf> {
+        let history = Path::new("Microsoft")
+            .join("Windows")
+            .join("PowerShell")
+            .join("PSReadLine")
+            .join("ConsoleHost_history.txt");
 
-    fn parse_powershell(content: Vec<u8>) -> Vec<String> {
-        String::from_utf8(content)
-            .unwrap_or_else(|e| {
-                eprintln!("Failed to decode PowerShell history: {}", e);
-                String::new()
-            })
-            .lines()
-            .rev()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty())
-            .take(1000)
-            .collect()
+        let mut paths = vec![roaming.join(&history)];
+        for package in [
+            "Microsoft.PowerShell_8wekyb3d8bbwe",
+            "Microsoft.PowerShellPreview_8wekyb3d8bbwe",
+        ] {
+            paths.push(
+                local
+                    .join("Packages")
+                    .join(package)
+                    .join("LocalCache")
+                    .join("Roaming")
+                    .join(&history),
+            );
+        }
+        paths
     }
 
-    fn parse_zsh(content: Vec<u8>) -> Vec<String> {
-        String::from_utf8_lossy(&content)
-            .lines()
-            .filter_map(|line| line.split_once(';').map(|x| x.1))
-            .filter(|cmd| !cmd.is_empty())
-            .map(String::from)
-            .rev()
-            .take(1000)
-            .collect()
+    /// Concatenate the files that exist, least recently written first, so the
+    /// one in use ends up newest after parsing
+    pub fn read_files(paths: &[PathBuf]) -> io::Result<Vec<u8>> {
+        let mut files: Vec<_> = paths
+            .iter()
+            .filter_map(|path| {
+                let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok()?;
+                Some((modified, path))
+            })
+            .collect();
+        if files.is_empty() {
+            // Report the first file as missing
+            return fs::read(paths.first().ok_or(io::ErrorKind::NotFound)?);
+        }
+        files.sort();
+
+        let mut content = Vec::new();
+        for (_, path) in files {
+            content.extend(fs::read(path)?);
+            if content.last().is_some_and(|byte| *byte != b'\n') {
+                content.push(b'\n');
+            }
+        }
+        Ok(content)
     }
 
-    fn parse_bash(content: Vec<u8>) -> Vec<String> {
-        String::from_utf8(content)
-            .expect("Failed to decode Bash history")
-            .lines()
-            .rev()
-            .take(1000)
-            .map(String::from)
-            .collect()
+    /// Nushell honors $XDG_CONFIG_HOME on every platform before the OS default
+    fn nushell_config_dir(base_dirs: &directories::BaseDirs) -> PathBuf {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| base_dirs.config_dir().to_path_buf())
+            .join("nushell")
     }
 
-    fn parse_fish(content: Vec<u8>) -> Vec<String> {
-        String::from_utf8(content)
-            .expect("Failed to decode Fish history")
-            .lines()
-            .filter_map(|line| line.strip_prefix("- cmd: "))
-            .map(String::from)
-            .rev()
-            .take(1000)
-            .collect()
+    /// Best-effort lookup of a `HISTFILE=` assignment in the shell's rc files,
+    /// since it is usually set without being exported
+    fn histfile_from_rc(&self, home: &Path) -> Option<PathBuf> {
+        let (rc_dir, rc_files): (PathBuf, &[&str]) = match self {
+            ShellType::Zsh => (
+                env::var_os("ZDOTDIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| home.to_path_buf()),
+                &[".zshenv", ".zprofile", ".zshrc"],
+            ),
+            ShellType::Bash => (
+                home.to_path_buf(),
+                &[".profile", ".bash_profile", ".bashrc"],
+            ),
+            ShellType::Ksh => (home.to_path_buf(), &[".profile", ".kshrc", ".mkshrc"]),
+            _ => return None,
+        };
+
+        // Files are listed in sourcing order, so the last assignment wins
+        let value = rc_files
+            .iter()
+            .filter_map(|name| fs::read_to_string(rc_dir.join(name)).ok())
+            .flat_map(|content| {
+                content
+                    .lines()
+                    .filter_map(Self::parse_histfile_assignment)
+                    .collect::<Vec<_>>()
+            })
+            .last()?;
+
+        let expanded = PathBuf::from(Self::expand_vars(&value, home, &rc_dir));
+        Some(if expanded.is_relative() {
+            home.join(expanded)
+        } else {
+            expanded
+        })
     }
-}
 
-impl App {
-    pub fn new() -> Self {
-        let current_shell = ShellType::detect();
-        let history = Self::load_history(&current_shell);
+    /// Extract the value from lines like `export HISTFILE="$HOME/.hist" # comment`
+    fn parse_histfile_assignment(line: &str) -> Option<String> {
+        let line = line.trim();
+        let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+        let value = line.strip_prefix("HISTFILE=")?;
 
-        let mut app = Self {
-            bookmarks: Vec::new(),
-            bookmark_mode: false,
-            bookmark_path: Self::get_bookmark_path(),
-            queried_history: history.clone(),
-            history,
-            selected: 0,
-            search_mode: false,
-            search_query: String::new(),
-            skipped_items: 0,
-            size: Cell::new(0),
-            show_help: false,
-            should_quit: false,
-            message: String::new(),
-            current_shell,
+        let value = match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next()?,
+            _ => value
+                .split(|c: char| c.is_whitespace() || c == ';')
+                .next()?,
         };
 
-        app.load_bookmarks();
-        app
+        (!value.is_empty()).then(|| value.to_string())
     }
 
-    // -- History -- //
-    fn load_history(shell: &ShellType) -> Vec<String> {
-        let history_path = shell.history_path();
+    /// Expand `~`, `$VAR` and `${VAR}` the way the rc file would see them
+    fn expand_vars(value: &str, home: &Path, zdotdir: &Path) -> String {
+        let lookup = |name: &str| match name {
+            "HOME" => Some(home.display().to_string()),
+            "ZDOTDIR" => {
+                Some(env::var("ZDOTDIR").unwrap_or_else(|_| zdotdir.display().to_string()))
+            }
+            _ => env::var(name).ok(),
+        };
 
-        fs::read(&history_path)
-            .map(|content| shell.parse_history(content))
-            .unwrap_or_else(|_| vec!["No history found".into()])
-    }
+        let value = match value.strip_prefix('~') {
+            Some(rest) => format!("{}{}", home.display(), rest),
+            None => value.to_string(),
+        };
 
-    pub fn search_query(&self) -> &str {
-        &self.search_query
+        let mut expanded = String::new();
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                expanded.push(c);
+                continue;
+            }
+
+            let braced = chars.next_if_eq(&'{').is_some();
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                name.push(c);
+            }
+            let mut fallback = None;
+            if braced {
+                // Support the `${VAR:-default}` form used for ZDOTDIR
+                let rest: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                fallback = rest
+                    .strip_prefix(":-")
+                    .or_else(|| rest.strip_prefix('-'))
+                    .map(|default| Self::expand_vars(default, home, zdotdir));
+            }
+
+            match lookup(&name).filter(|value| !value.is_empty()) {
+                Some(value) => expanded.push_str(&value),
+                None => expanded.push_str(&fallback.unwrap_or_default()),
+            }
+        }
+        expanded
     }
 
-    pub fn push_query(&mut self, c: char) {
-        if c.is_ascii_graphic() || c == ' ' {
-            self.search_query.push(c);
-            self.update_queried_history();
+    /// Read the raw history content for the shell
+    pub fn read_history(&self) -> io::Result<Vec<u8>> {
+        let path = self.history_path();
+
+        match self {
+            ShellType::Cmd if !path.exists() => Self::read_doskey_history(),
+            ShellType::Fish => Self::read_fish_sessions(&path),
+            ShellType::PowerShell => {
+                let base_dirs =
+                    directories::BaseDirs::new().expect("Failed to determine system directories");
+                Self::read_files(&Self::powershell_histories(&base_dirs))
+            }
+            ShellType::Nushell if path.extension().is_some_and(|ext| ext == "sqlite3") => {
+                Self::read_nushell_sqlite(&path)
+            }
+            ShellType::Xonsh => Self::read_xonsh_history(&path),
+            ShellType::Elvish => Self::read_elvish_history(),
+            _ => fs::read(path),
         }
     }
 
-    pub fn pop_query(&mut self) {
-        self.search_query.pop();
-        self.update_queried_history();
+    /// Fish keeps its data under $XDG_DATA_HOME/fish, defaulting to ~/.local/share/fish
+    fn fish_data_dir(base_dirs: &directories::BaseDirs) -> PathBuf {
+        Self::xdg_data_home(base_dirs).join("fish")
     }
 
-    pub fn clear_query(&mut self) {
-        self.search_query.clear();
-        self.queried_history = self.history.clone();
-        self.selected = 0;
-        self.skipped_items = 0;
+    /// $XDG_DATA_HOME, defaulting to ~/.local/share even on macOS, as fish and pwsh do
+    fn xdg_data_home(base_dirs: &directories::BaseDirs) -> PathBuf {
+        env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| base_dirs.home_dir().join(".local/share"))
     }
 
-    fn update_queried_history(&mut self) {
-        self.queried_history = self
-            .history
-            .iter()
-            .filter(|cmd| cmd.contains(&self.search_query))
-            .cloned()
-            .collect();
-        self.selected = self
-            .selected
-            .min(self.queried_history.len().saturating_sub(1));
-        self.skipped_items = self.skipped_items.min(self.selected);
+    /// Session name from $fish_history, where "default" is an alias of "fish"
+    fn fish_session() -> String {
+        match env::var("fish_history") {
+            Ok(session) if !session.is_empty() && session != "default" => session,
+            _ => "fish".into(),
+        }
     }
 
-    pub fn move_selection(&mut self, direction: MoveDirection) {
-        let max_index = self.current_list().len().saturating_sub(1);
+    /// Every `<session>_history` file next to `active`, sorted, with `active`
+    /// itself last
+    pub fn fish_session_files(active: &Path) -> Vec<PathBuf> {
+        let mut sessions: Vec<PathBuf> = active
+            .parent()
+            .map(|dir| {
+                fs::read_dir(dir)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|entry| entry.ok().map(|e| e.path()))
+                    .filter(|path| path.as_path() != active)
+                    .filter(|path| {
+                        path.file_name()
+                            .and_then(|name| name.to_str())
+                            .is_some_and(|name| name.ends_with("_history"))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        sessions.sort();
+        sessions.push(active.to_path_buf());
+        sessions
+    }
 
-        match direction {
-            MoveDirection::Up if self.selected > 0 => self.selected -= 1,
-            MoveDirection::Down if self.selected < max_index => self.selected += 1,
-            _ => (),
-        }
+    /// Merge every `<session>_history` file, keeping the active session last so
+    /// its entries end up newest after parsing
+    fn read_fish_sessions(active: &Path) -> io::Result<Vec<u8>> {
+        let mut content = Vec::new();
 
-        let size = self.size.get();
-        if size == 0 {
-            return;
+        for session in Self::fish_session_files(active)
+            .iter()
+            .filter(|session| session.as_path() != active)
+        {
+            if let Ok(data) = fs::read(session) {
+                content.extend(data);
+                content.push(b'\n');
+            }
         }
 
-        if self.selected < self.skipped_items {
-            self.skipped_items = self.selected;
-        } else if self.selected >= self.skipped_items + size {
-            self.skipped_items = self.selected.saturating_sub(size - 1);
+        match fs::read(active) {
+            Ok(data) => content.extend(data),
+            Err(e) if content.is_empty() => return Err(e),
+            Err(_) => {}
         }
+        Ok(content)
     }
 
-    // -- Selection -- //
-    pub fn copy_selected(&mut self) {
-        let Some(selected_cmd) = self.current_list().get(self.selected) else {
-            self.message = "No command to copy".into();
-            return;
-        };
+    /// Dump the console's cmd.exe history when clink is not installed
+    fn read_doskey_history() -> io::Result<Vec<u8>> {
+        use std::process::{Command, Stdio};
 
-        // Platform-specific clipboard handling
-        #[cfg(target_os = "linux")]
-        self.handle_linux_clipboard(selected_cmd);
+        let output = Command::new("doskey")
+            .arg("/history")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
 
-        #[cfg(target_os = "macos")]
-        self.handle_macos_clipboard(selected_cmd);
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(io::Error::other("doskey /history failed"))
+        }
+    }
 
-        #[cfg(target_os = "windows")]
-        self.handle_windows_clipboard(selected_cmd);
+    /// Dump Nushell's SQLite history, oldest first, as JSON lines of
+    /// `sqlite::HistoryRow` after the `NUSHELL_SQLITE_DUMP` marker. Nushell
+    /// stores times in milliseconds.
+    fn read_nushell_sqlite(path: &Path) -> io::Result<Vec<u8>> {
+        let rows = sqlite::query_hex_column(
+            path,
+            "SELECT hex(json_object('command', command_line, 'timestamp', start_timestamp / 1000, \
+             'duration', duration_ms / 1000, 'exit', exit_status, 'cwd', cwd)) FROM history ORDER BY id",
+            "Nushell SQLite history",
+        )?;
 
-        // Universal fallback
-        let _ = copypasta::ClipboardContext::new()
-            .and_then(|mut ctx| ctx.set_contents(selected_cmd.to_owned()));
+        let mut content = NUSHELL_SQLITE_DUMP.to_vec();
+        for row in rows {
+            content.extend(row.into_bytes());
+            content.push(b'\n');
+        }
+        Ok(content)
+    }
 
-        self.message = "Copied to clipboard!".to_string();
+    /// $XONSH_DATA_DIR, defaulting to $XDG_DATA_HOME/xonsh
+    fn xonsh_data_dir(base_dirs: &directories::BaseDirs) -> PathBuf {
+        env::var_os("XONSH_DATA_DIR")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| Self::xdg_data_home(base_dirs).join("xonsh"))
     }
 
-    #[cfg(target_os = "linux")]
-    fn handle_linux_clipboard(&self, cmd: &str) {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
-        use std::env;
-
-        let wayland = env::var("WAYLAND_DISPLAY").is_ok();
-        let x11 = env::var("DISPLAY").is_ok();
-
-        if wayland {
-            let _ = Command::new("wl-copy")
-                .stdin(Stdio::piped())
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()
-                .and_then(|mut child| {
-                    if let Some(stdin) = child.stdin.as_mut() {
-                        stdin.write_all(cmd.as_bytes())?;
-                    }
-                    child.wait().map(|_| ())
-                })
-                .map_err(|e| eprintln!("Wayland clipboard error: {e}"));
-        } else if x11 {
-            let _ = Command::new("xclip")
-                .args(["-selection", "clipboard"])
-                .stdin(Stdio::piped())
-                .stdout(Stdio::null())
-                .stderr(Stdio::null())
-                .spawn()
-                .and_then(|mut child| {
-                    if let Some(stdin) = child.stdin.as_mut() {
-                        stdin.write_all(cmd.as_bytes())?;
-                    }
-                    child.wait().map(|_| ())
-                })
-                .map_err(|e| eprintln!("X11 clipboard error: {e}"));
+    /// Elvish's bolt database: ~/.elvish/db from before 0.16, otherwise in the
+    /// XDG state directory, or %LocalAppData% on Windows
+    fn elvish_db(base_dirs: &directories::BaseDirs) -> PathBuf {
+        let legacy = base_dirs.home_dir().join(".elvish").join("db");
+        if legacy.exists() {
+            return legacy;
         }
+        if cfg!(target_os = "windows") {
+            return base_dirs.data_local_dir().join("elvish").join("db.bolt");
+        }
+        env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| base_dirs.home_dir().join(".local/state"))
+            .join("elvish")
+            .join("db.bolt")
     }
 
-    #[cfg(target_os = "macos")]
-    fn handle_macos_clipboard(&self, cmd: &str) {
-        use std::io::Write;
-        use std::process::{Command, Stdio};
+    /// Dump xonsh's history as JSON lines of `{"inp": ..., "ts": [start, end]}`,
+    /// from the SQLite backend or from every session file of the JSON backend
+    fn read_xonsh_history(path: &Path) -> io::Result<Vec<u8>> {
+        let records = if path.extension().is_some_and(|ext| ext == "sqlite") {
+            sqlite::query_hex_column(
+                path,
+                "SELECT hex(json_object('inp', inp, 'ts', json_array(tsb, tse))) FROM xonsh_history ORDER BY tsb",
+                "xonsh SQLite history",
+            )?
+        } else {
+            let mut sessions: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect();
+            sessions.sort();
 
-        let _ = Command::new("pbcopy")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut child| {
-                if let Some(stdin) = child.stdin.as_mut() {
-                    stdin.write_all(cmd.as_bytes())?;
+            let mut records = Vec::new();
+            for session in sessions {
+                let Ok(data) = fs::read(&session) else {
+                    continue;
+                };
+                // Sessions that are still running may be half written
+                let Ok(session) = serde_json::from_slice::<serde_json::Value>(&data) else {
+                    continue;
+                };
+                if let Some(cmds) = session
+                    .pointer("/data/cmds")
+                    .and_then(|cmds| cmds.as_array())
+                {
+                    records.extend(cmds.iter().map(|cmd| cmd.to_string()));
                 }
-                child.wait().map(|_| ())
-            })
-            .map_err(|e| eprintln!("macOS clipboard error: {e}"));
+            }
+            records
+        };
+
+        let mut content = records.join("\n").into_bytes();
+        content.push(b'\n');
+        Ok(content)
     }
 
-    #[cfg(target_os = "windows")]
-    fn handle_windows_clipboard(&self, cmd: &str) {
+    /// Elvish keeps its history in a bolt database behind its daemon, so ask
+    /// elvish itself for JSON lines of `{"seq": ..., "text": ...}`
+    fn read_elvish_history() -> io::Result<Vec<u8>> {
         use std::process::{Command, Stdio};
 
-        let escaped = cmd.replace("'", "''");
-        let _ = Command::new("powershell")
-            .args([
-                "-NoProfile",
-                "-Command",
-                &format!("Set-Clipboard -Value '{}'", escaped),
-            ])
+        let output = Command::new("elvish")
+            .args(["-norc", "-c", "use store; store:cmds 0 -1 | to-json"])
             .stdin(Stdio::null())
-            .stdout(Stdio::null())
             .stderr(Stdio::null())
-            .spawn()
-            .and_then(|mut child| child.wait().map(|_| ()))
-            .map_err(|e| eprintln!("Windows clipboard error: {e}"));
-    }
+            .output()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => {
+                    io::Error::new(e.kind(), "reading elvish history needs the elvish command")
+                }
+                _ => e,
+            })?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(io::Error::other(
+                "elvish could not list its command history",
+            ))
+        }
+    }
+
+    /// Parse shell-specific history format
+    pub fn parse_history(&self, content: Vec<u8>) -> Vec<HistoryEntry> {
+        match self {
+            ShellType::PowerShell => Self::parse_powershell(content),
+            ShellType::Cmd => Self::parse_cmd(content),
+            ShellType::Zsh => Self::parse_zsh(content),
+            ShellType::Bash => Self::parse_bash(content),
+            ShellType::Fish => Self::parse_fish(content),
+            ShellType::Nushell => Self::parse_nushell(content),
+            ShellType::Ksh => Self::parse_ksh(content),
+            ShellType::Tcsh => Self::parse_tcsh(content),
+            ShellType::Xonsh => Self::parse_xonsh(content),
+            ShellType::Elvish => Self::parse_elvish(content),
+            ShellType::Unknown(_) => Self::parse_bash(content), // Fallback to bash parsing
+        }
+    }
+
+    // -- History Parsers -- //
+
+    fn parse_powershell(content: Vec<u8>) -> Vec<HistoryEntry> {
+        String::from_utf8_lossy(&content)
+            .lines()
+            .rev()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(HistoryEntry::new)
+            .collect()
+    }
+
+    fn parse_cmd(content: Vec<u8>) -> Vec<HistoryEntry> {
+        String::from_utf8_lossy(&content)
+            .lines()
+            .map(str::trim)
+            // clink stores per-entry metadata on lines starting with '|'
+            .filter(|line| !line.is_empty() && !line.starts_with('|'))
+            .map(HistoryEntry::new)
+            .rev()
+            .collect()
+    }
+
+    fn parse_zsh(content: Vec<u8>) -> Vec<HistoryEntry> {
+        Self::zsh_records(&split_lines(&content))
+            .into_iter()
+            .map(|record| record.entry)
+            .filter(|entry| !entry.command.is_empty())
+            .rev()
+            .collect()
+    }
+
+    /// zsh ends each line of a multi-line command but the last with a `\`
+    pub fn zsh_records(lines: &[HistoryLine]) -> Vec<HistoryRecord> {
+        let mut records = Vec::new();
+        let mut start = 0;
+        let mut command = String::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            let (text, continues) = match line.text.strip_suffix('\\') {
+                Some(head) => (head, true),
+                None => (&*line.text, false),
+            };
+            if i > start {
+                command.push('\n');
+            }
+            command.push_str(text);
+            if !continues || i + 1 == lines.len() {
+                records.push(HistoryRecord {
+                    lines: start..i + 1,
+                    entry: Self::parse_zsh_line(&std::mem::take(&mut command)),
+                });
+                start = i + 1;
+            }
+        }
+        records
+    }
+
+    /// `: 1700000000:12;git push` with EXTENDED_HISTORY, otherwise the bare command
+    fn parse_zsh_line(line: &str) -> HistoryEntry {
+        let extended = line.strip_prefix(": ").and_then(|rest| {
+            let (meta, command) = rest.split_once(';')?;
+            let (timestamp, duration) = meta.split_once(':')?;
+            Some(HistoryEntry {
+                timestamp: Some(timestamp.trim().parse().ok()?),
+                duration: duration.trim().parse().ok(),
+                ..HistoryEntry::new(command)
+            })
+        });
+        extended.unwrap_or_else(|| HistoryEntry::new(line))
+    }
+
+    fn parse_bash(content: Vec<u8>) -> Vec<HistoryEntry> {
+        // Stray binary bytes (e.g. from pasting a binary) must not lose the rest
+        let mut entries: Vec<HistoryEntry> = Self::bash_records(&split_lines(&content))
+            .into_iter()
+            .map(|record| record.entry)
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// With HISTTIMEFORMAT set, bash writes a `#1700000000` line before each command,
+    /// so everything up to the next one belongs to it. Without, multi-line
+    /// commands (saved as such with `shopt -s lithist`) are put back together
+    /// from trailing backslashes and heredocs.
+    pub fn bash_records(lines: &[HistoryLine]) -> Vec<HistoryRecord> {
+        let mut records: Vec<HistoryRecord> = Vec::new();
+        let mut timestamp = None;
+        // Line of the timestamp that the next command starts at
+        let mut stamp_line = None;
+        // Set while the last entry is still open: after a timestamp, a
+        // trailing backslash or inside a heredoc
+        let mut continued = false;
+        let mut timed = false;
+        // Heredocs still waiting for their delimiter line, first opened first
+        let mut heredocs: Vec<(String, bool)> = Vec::new();
+
+        for (i, line) in lines.iter().enumerate() {
+            let line_text = &*line.text;
+            if let Some(seconds) = line_text
+                .strip_prefix('#')
+                .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+            {
+                timestamp = seconds.parse().ok();
+                stamp_line = Some(i);
+                continued = false;
+                timed = false;
+                heredocs.clear();
+                continue;
+            }
+
+            match records.last_mut().filter(|_| continued) {
+                Some(record) => {
+                    record.entry.command.push('\n');
+                    record.entry.command.push_str(line_text);
+                    record.lines.end = i + 1;
+                }
+                None => {
+                    timed = timestamp.is_some();
+                    records.push(HistoryRecord {
+                        lines: stamp_line.take().unwrap_or(i)..i + 1,
+                        entry: HistoryEntry {
+                            timestamp: timestamp.take(),
+                            ..HistoryEntry::new(line_text)
+                        },
+                    });
+                }
+            }
+
+            match heredocs.first() {
+                Some((delimiter, strip_tabs)) => {
+                    let end = if *strip_tabs {
+                        line_text.trim_start_matches('\t')
+                    } else {
+                        line_text
+                    };
+                    if end == delimiter {
+                        heredocs.remove(0);
+                    }
+                }
+                None => heredocs = Self::heredoc_delimiters(line_text),
+            }
+            continued = timed || !heredocs.is_empty() || line_text.ends_with('\\');
+        }
+        records
+    }
+
+    /// Words closing the heredocs opened on `line`, e.g. `EOF` for `cat <<'EOF'`,
+    /// each with whether `<<-` lets it be indented with tabs
+    fn heredoc_delimiters(line: &str) -> Vec<(String, bool)> {
+        let mut delimiters = Vec::new();
+        let mut rest = line;
+        while let Some(at) = rest.find("<<") {
+            rest = &rest[at + 2..];
+            // `<<<` is a here-string, which ends with the line
+            if rest.starts_with('<') {
+                rest = rest.trim_start_matches('<');
+                continue;
+            }
+            let strip_tabs = rest.starts_with('-');
+            let word = rest.trim_start_matches('-').trim_start();
+            let delimiter = match word.chars().next() {
+                Some(quote @ ('\'' | '"')) => word[1..]
+                    .split(quote)
+                    .next()
+                    .filter(|_| word[1..].contains(quote)),
+                // Unquoted words must not look like the `1 << 2` of arithmetic
+                Some(c) if c.is_alphabetic() || c == '_' || c == '\\' => {
+                    let word = word.trim_start_matches('\\');
+                    let len = word
+                        .find(|c: char| !c.is_alphanumeric() && c != '_')
+                        .unwrap_or(word.len());
+                    Some(&word[..len])
+                }
+                _ => None,
+            };
+            if let Some(delimiter) = delimiter.filter(|d| !d.is_empty()) {
+                delimiters.push((delimiter.to_string(), strip_tabs));
+            }
+        }
+        delimiters
+    }
+
+    fn parse_fish(content: Vec<u8>) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = Self::fish_records(&split_lines(&content))
+            .into_iter()
+            .map(|record| record.entry)
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// Fish writes YAML-like records:
+    ///
+    /// ```text
+    /// - cmd: cp notes.txt /tmp\necho done
+    ///   when: 1700000000
+    ///   paths:
+    ///     - notes.txt
+    /// ```
+    pub fn fish_records(lines: &[HistoryLine]) -> Vec<HistoryRecord> {
+        let mut records: Vec<HistoryRecord> = Vec::new();
+        let mut in_paths = false;
+
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(cmd) = line.text.strip_prefix("- cmd: ") {
+                records.push(HistoryRecord {
+                    lines: i..i + 1,
+                    entry: HistoryEntry::new(Self::unescape_fish(cmd)),
+                });
+                in_paths = false;
+                continue;
+            }
+            let Some(record) = records.last_mut() else {
+                continue;
+            };
+            record.lines.end = i + 1;
+            let entry = &mut record.entry;
+            let field = line.text.trim_start();
+            if let Some(when) = field.strip_prefix("when: ") {
+                entry.timestamp = when.trim().parse().ok();
+                in_paths = false;
+            } else if field == "paths:" {
+                in_paths = true;
+            } else if let Some(path) = field.strip_prefix("- ").filter(|_| in_paths) {
+                entry.paths.push(Self::unescape_fish(path));
+            } else {
+                in_paths = false;
+            }
+        }
+        records
+    }
+
+    /// Undo fish's history escaping, which only covers `\\` and newlines (`\n`)
+    fn unescape_fish(text: &str) -> String {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('\\') => unescaped.push('\\'),
+                Some(other) => {
+                    unescaped.push('\\');
+                    unescaped.push(other);
+                }
+                None => unescaped.push('\\'),
+            }
+        }
+        unescaped
+    }
+
+    fn parse_nushell(content: Vec<u8>) -> Vec<HistoryEntry> {
+        if let Some(rows) = content.strip_prefix(NUSHELL_SQLITE_DUMP) {
+            return String::from_utf8_lossy(rows)
+                .lines()
+                .filter_map(|row| serde_json::from_str::<sqlite::HistoryRow>(row).ok())
+                .map(HistoryEntry::from)
+                .filter(|entry| !entry.command.trim().is_empty())
+                .rev()
+                .collect();
+        }
+        String::from_utf8_lossy(&content)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| HistoryEntry::new(line.replace(NUSHELL_NEWLINE, "\n")))
+            .rev()
+            .collect()
+    }
+
+    /// ksh93 writes plain lines after a two-byte magic number; mksh writes
+    /// records of 0xff, a 4-byte line number and the NUL-terminated command
+    fn parse_ksh(content: Vec<u8>) -> Vec<HistoryEntry> {
+        let commands: Vec<String> = match content.as_slice() {
+            [0xab, 0xcd, records @ ..] => {
+                // The line number may contain NUL bytes, so walk record by record
+                let mut commands = Vec::new();
+                let mut rest = records;
+                while let [0xff, _, _, _, _, record @ ..] = rest {
+                    let end = record
+                        .iter()
+                        .position(|byte| *byte == 0)
+                        .unwrap_or(record.len());
+                    commands.push(String::from_utf8_lossy(&record[..end]).into_owned());
+                    rest = record.get(end + 1..).unwrap_or_default();
+                }
+                commands
+            }
+            [0x81, 0x01, lines @ ..] | lines => String::from_utf8_lossy(lines)
+                .lines()
+                .map(|line| line.trim_end_matches('\0').to_string())
+                .collect(),
+        };
+
+        commands
+            .into_iter()
+            .filter(|command| !command.trim().is_empty())
+            .map(HistoryEntry::new)
+            .rev()
+            .collect()
+    }
+
+    /// With `savehist ... merge`, tcsh writes a `#+1700000000` line before each command
+    fn parse_tcsh(content: Vec<u8>) -> Vec<HistoryEntry> {
+        let mut entries = Vec::new();
+        let mut timestamp = None;
+
+        for line in String::from_utf8_lossy(&content).lines() {
+            if let Some(seconds) = line.strip_prefix("#+") {
+                timestamp = seconds.trim().parse().ok();
+                continue;
+            }
+            if !line.trim().is_empty() {
+                entries.push(HistoryEntry {
+                    timestamp: timestamp.take(),
+                    ..HistoryEntry::new(line)
+                });
+            }
+        }
+        entries.reverse();
+        entries
+    }
+
+    /// JSON lines from `read_xonsh_history`; `inp` keeps the trailing newline
+    /// xonsh records
+    fn parse_xonsh(content: Vec<u8>) -> Vec<HistoryEntry> {
+        let mut entries: Vec<HistoryEntry> = String::from_utf8_lossy(&content)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter_map(|record| {
+                let command = record.get("inp")?.as_str()?.trim_end().to_string();
+                let ts = record.get("ts").and_then(|ts| ts.as_array());
+                let start = ts.and_then(|ts| ts.first()).and_then(|t| t.as_f64());
+                let end = ts.and_then(|ts| ts.get(1)).and_then(|t| t.as_f64());
+                Some(HistoryEntry {
+                    timestamp: start.map(|start| start as u64),
+                    duration: start
+                        .zip(end)
+                        .map(|(start, end)| (end - start).max(0.0) as u64),
+                    ..HistoryEntry::new(command)
+                })
+            })
+            .filter(|entry| !entry.command.is_empty())
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// JSON lines from `read_elvish_history`, oldest first
+    fn parse_elvish(content: Vec<u8>) -> Vec<HistoryEntry> {
+        String::from_utf8_lossy(&content)
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .filter_map(|record| Some(HistoryEntry::new(record.get("text")?.as_str()?)))
+            .filter(|entry| !entry.command.trim().is_empty())
+            .rev()
+            .collect()
+    }
+}
+
+impl App {
+    pub fn new(config: Config) -> Self {
+        let mut startup = StartupProfile::default();
+        let mut phases = Vec::new();
+        let current_shell = StartupProfile::time(&mut phases, "detect shell", ShellType::detect);
+        startup.record(phases);
+        // Moved here once, rather than whenever the files are opened, so a
+        // move that fails isn't retried on every save
+        let mut migration_errors: Vec<String> = [
+            config.bookmark_format.migrate(),
+            PRESETS_FILE.migrate(),
+            SEARCHES_FILE.migrate(),
+        ]
+        .into_iter()
+        .filter_map(|moved| moved.err().map(|e| e.to_string()))
+        .collect();
+        migration_errors.dedup();
+
+        let mut app = Self {
+            bookmarks: Vec::new(),
+            bookmark_snapshot: Vec::new(),
+            bookmarks_read_only: None,
+            merge: None,
+            mode: Mode::History,
+            recent_modes: Mode::ALL.to_vec(),
+            show_expansions: false,
+            show_timestamps: config.timestamps.show,
+            reveal_secrets: !config.mask_secrets,
+            redactor: Redactor::new(&config.redaction),
+            queried_history: Vec::new(),
+            queried_bookmarks: Vec::new(),
+            group_rows: HashMap::new(),
+            collapsed_sections: HashSet::from([Section::Trash]),
+            history: Vec::new(),
+            older_history: Vec::new(),
+            history_loading: false,
+            loader: None,
+            history_generation: 0,
+            watch_generation: Arc::new(AtomicU64::new(0)),
+            live_refresh: false,
+            selected: 0,
+            search_mode: false,
+            search_query: String::new(),
+            search_cursor: 0,
+            match_mode: MatchMode::default(),
+            search_display: SearchDisplay::default(),
+            matcher: None,
+            sort_mode: SortMode::default(),
+            oldest_first: false,
+            collapse_duplicates: config.collapse_duplicates,
+            normalizer: Normalizer::new(&config.normalize),
+            normalized: HashMap::new(),
+            variants: HashMap::new(),
+            variant_choices: Vec::new(),
+            pending_deletion: Vec::new(),
+            pending_bookmark: None,
+            pending_copy: None,
+            program_chips: Vec::new(),
+            active_chip: None,
+            skipped_items: 0,
+            size: Cell::new(0),
+            show_help: false,
+            menu: None,
+            should_quit: false,
+            message: String::new(),
+            current_shell,
+            history_error: None,
+            pending_register: None,
+            recording: None,
+            macros: HashMap::new(),
+            usage: HashMap::new(),
+            runs: HashMap::new(),
+            top_match: None,
+            config,
+            bell: false,
+            flash_until: None,
+            startup,
+            query_history: QueryHistory::load(Self::get_query_history_path()),
+            presets: Presets::load(Self::get_presets_path()),
+            prompt: None,
+        };
+
+        if !app.redactor.invalid.is_empty() {
+            app.report_error(format!(
+                "Invalid redaction pattern {}",
+                app.redactor.invalid.join(", ")
+            ));
+        }
+        if !migration_errors.is_empty() {
+            app.report_error(migration_errors.join("; "));
+        }
+        app.load_bookmarks();
+        app
+    }
+
+    /// Apply an action produced by the keymap
+    pub fn dispatch(&mut self, action: Action) {
+        // Macro control keys themselves are never part of a recording
+        let is_macro_control = self.pending_register.is_some()
+            || matches!(
+                action,
+                Action::RecordMacro | Action::PlayMacro | Action::Register(_)
+            );
+        if let Some((_, actions)) = self.recording.as_mut() {
+            if !is_macro_control {
+                actions.push(action);
+            }
+        }
+
+        self.perform(action);
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::ShowHelp => self.show_help = true,
+            Action::Cancel => {
+                if self.pending_register.take().is_some() {
+                    // Only abandon the register prompt
+                } else if self.prompt.take().is_some() {
+                    // Only abandon the prompt; a new bookmark stays untagged
+                } else if self.menu.take().is_some() {
+                    // Only close the menu
+                } else if self.search_mode {
+                    self.search_mode = false;
+                    self.clear_query();
+                } else if self.show_help {
+                    self.show_help = false;
+                } else if !self.search_query.is_empty() {
+                    // A query kept for highlighting outlives the search bar
+                    self.clear_query();
+                } else if self.active_chip.take().is_some() {
+                    self.update_query_results();
+                } else if self.mode != Mode::History {
+                    self.set_mode(Mode::History);
+                }
+            }
+            Action::Move(direction) => match self.menu.as_mut() {
+                Some(menu) => menu.move_selection(direction),
+                None => self.move_selection(direction),
+            },
+            Action::OpenMenu => self.open_menu(),
+            Action::ToggleExpansions => self.show_expansions = !self.show_expansions,
+            Action::ToggleTimestamps => {
+                self.show_timestamps = !self.show_timestamps;
+                self.message = if !self.show_timestamps {
+                    "Hiding times".to_string()
+                } else if self.runs.values().any(|run| run.timestamp.is_some()) {
+                    "Showing when commands last ran".to_string()
+                } else {
+                    "This history has no timestamps to show".to_string()
+                };
+            }
+            Action::SavePreset => {
+                if self.search_query.is_empty() {
+                    self.report_error("Type a query to save as a preset");
+                } else {
+                    self.open_prompt(PromptKind::PresetName, String::new(), None);
+                }
+            }
+            Action::EditTags => self.edit_tags(),
+            Action::EditNote => self.edit_note(),
+            Action::EditTitle => self.edit_title(),
+            Action::EditCommand => self.edit_command(),
+            Action::MoveToGroup => self.edit_group(),
+            Action::ToggleGroup => self.toggle_group(),
+            Action::MoveBookmark(direction) => self.move_bookmark(direction),
+            Action::TogglePin => self.toggle_pin(),
+            Action::RestoreBookmark => self.restore_bookmark(),
+            Action::ReplaceBookmark => self.replace_pending_bookmark(),
+            Action::ImportBookmarks => {
+                self.open_prompt(PromptKind::ImportPath, String::new(), None)
+            }
+            Action::ToggleSecrets => {
+                self.reveal_secrets = !self.reveal_secrets;
+                self.message = if self.reveal_secrets {
+                    "Showing secrets".to_string()
+                } else {
+                    "Masking secrets".to_string()
+                };
+            }
+            Action::CopySecret => {
+                if let Some((cmd, bookmark_use)) = self.pending_copy.take() {
+                    if self.copy_command(&cmd) && bookmark_use {
+                        self.record_bookmark_use(&cmd);
+                    }
+                }
+            }
+            Action::AddBookmarkAnyway => {
+                if let Some((cmd, _)) = self.pending_bookmark.take() {
+                    self.add_bookmark(cmd);
+                }
+            }
+            Action::PromptInput(c) => {
+                if let Some(prompt) = self.prompt.as_mut() {
+                    prompt.insert(c);
+                }
+            }
+            Action::PromptBackspace => {
+                if let Some(prompt) = self.prompt.as_mut() {
+                    prompt.backspace();
+                }
+            }
+            Action::PromptCursor(motion) => {
+                if let Some(prompt) = self.prompt.as_mut() {
+                    prompt.move_cursor(motion);
+                }
+            }
+            Action::ConfirmPrompt => match self.prompt.take() {
+                Some(TextPrompt {
+                    kind: PromptKind::PresetName,
+                    text,
+                    ..
+                }) => self.save_preset(&text),
+                Some(TextPrompt {
+                    kind: PromptKind::BookmarkTags,
+                    text,
+                    target,
+                    ..
+                }) => self.set_tags(target.as_deref().unwrap_or_default(), &text),
+                Some(TextPrompt {
+                    kind: PromptKind::BookmarkNote,
+                    text,
+                    target,
+                    ..
+                }) => self.set_note(target.as_deref().unwrap_or_default(), &text),
+                Some(TextPrompt {
+                    kind: PromptKind::BookmarkTitle,
+                    text,
+                    target,
+                    ..
+                }) => self.set_title(target.as_deref().unwrap_or_default(), &text),
+                Some(TextPrompt {
+                    kind: PromptKind::BookmarkGroup,
+                    text,
+                    target,
+                    ..
+                }) => self.set_group(target.as_deref().unwrap_or_default(), &text),
+                Some(TextPrompt {
+                    kind: PromptKind::BookmarkCommand,
+                    text,
+                    target,
+                    ..
+                }) => self.set_command(target.as_deref().unwrap_or_default(), &text),
+                Some(TextPrompt {
+                    kind: PromptKind::ImportPath,
+                    text,
+                    ..
+                }) => self.import_bookmarks(&text),
+                None => {}
+            },
+            Action::OpenPresets => self.open_presets(),
+            Action::ApplyPreset(index) => self.apply_preset(index),
+            Action::OpenShells => self.open_shells(),
+            Action::SwitchShell(index) => self.switch_shell(index),
+            Action::ReloadHistory => {
+                self.message = "Reloading history".to_string();
+                self.reload_history();
+            }
+            Action::MenuAccept => {
+                // The chosen action runs directly; the menu keys are what gets recorded
+                if let Some(action) = self.menu.take().and_then(|menu| menu.selected_action()) {
+                    self.perform(action);
+                }
+            }
+            Action::CopySelection => self.copy_selected(),
+            Action::ToggleBookmark => self.toggle_bookmark(),
+            Action::DeleteBookmark => self.delete_bookmark(),
+            Action::JumpBookmark(direction) => self.jump_to_bookmark(direction),
+            Action::ZoxideAdd => self.zoxide_add_selected(),
+            Action::CopyZoxideJump => self.copy_zoxide_jump(),
+            Action::NextMode => {
+                self.set_mode(self.mode.next());
+                self.message = format!("Switched to {} mode", self.mode.name());
+            }
+            Action::RecentMode(direction) => {
+                // Most recent first; the current mode is at the front
+                let target = match direction {
+                    MoveDirection::Down => self.recent_modes.get(1),
+                    MoveDirection::Up => self.recent_modes.last(),
+                };
+                if let Some(mode) = target.copied().filter(|mode| *mode != self.mode) {
+                    self.set_mode(mode);
+                    self.message = format!("Switched to {} mode", self.mode.name());
+                }
+            }
+            Action::CycleChip(direction) => self.cycle_chip(direction),
+            Action::CycleSort => {
+                self.sort_mode = self.sort_mode.next();
+                self.message = format!("Sorted by {}", self.sort_label());
+                self.update_query_results();
+            }
+            Action::ToggleOrder => self.toggle_order(),
+            Action::ShowVariants => self.show_variants(),
+            Action::DeleteEntry => self.confirm_delete_selected(),
+            Action::ConfirmDeletion => self.delete_pending(),
+            Action::CopyVariant(index) => match self.variant_choices.get(index).cloned() {
+                Some(variant) => self.copy_unless_secret(variant, false),
+                None => self.report_error("No such variant"),
+            },
+            Action::ToggleCollapse => {
+                self.collapse_duplicates = !self.collapse_duplicates;
+                self.message = if self.collapse_duplicates {
+                    "Collapsing duplicate entries".to_string()
+                } else {
+                    "Showing every entry".to_string()
+                };
+                self.update_query_results();
+            }
+            Action::StartSearch => {
+                self.search_mode = true;
+                self.clear_query();
+            }
+            Action::SearchInput(c) => self.push_query(c),
+            Action::SearchBackspace => self.pop_query(),
+            Action::PasteClipboard => self.paste_clipboard(),
+            Action::SearchCursor(motion) => self.move_search_cursor(motion),
+            Action::SearchDeleteWord => self.delete_query_word(),
+            Action::SearchClear => self.clear_query(),
+            Action::RecallQuery(direction) => self.recall_query(direction),
+            Action::ToggleMatchMode => {
+                self.match_mode = self.match_mode.next();
+                self.message = format!("Search mode: {}", self.match_mode.label());
+                self.update_query_results();
+            }
+            Action::ToggleSearchDisplay => {
+                self.search_display = self.search_display.toggled();
+                self.message = format!("Search display: {}", self.search_display.label());
+                self.update_query_results();
+            }
+            Action::CommitSearch => {
+                self.search_mode = false;
+                let on_match = self
+                    .current_list()
+                    .get(self.selected)
+                    .is_some_and(|cmd| self.is_highlighted(cmd));
+                if !on_match {
+                    self.jump_to_match(MoveDirection::Down);
+                }
+                self.remember_search();
+            }
+            Action::JumpMatch(direction) => self.jump_to_match(direction),
+            Action::AcceptTopMatch => match self.top_match.clone() {
+                Some(cmd) => {
+                    self.copy_unless_secret(cmd, false);
+                    self.remember_search();
+                }
+                None => self.report_error("No match to accept"),
+            },
+            Action::RecordMacro => match self.recording.take() {
+                Some((register, actions)) => {
+                    self.message = format!("Recorded {} actions into @{}", actions.len(), register);
+                    self.macros.insert(register, actions);
+                }
+                None => self.pending_register = Some(RegisterOp::Record),
+            },
+            Action::PlayMacro => self.pending_register = Some(RegisterOp::Replay),
+            Action::ToggleMergeChoice => {
+                if let Some(merge) = self.merge.as_mut() {
+                    merge.toggle(self.selected);
+                }
+            }
+            Action::ApplyMerge => self.apply_bookmark_merge(),
+            Action::Register(register) => match self.pending_register.take() {
+                Some(RegisterOp::Record) => {
+                    self.recording = Some((register, Vec::new()));
+                    self.message = format!("Recording @{}", register);
+                }
+                Some(RegisterOp::Replay) => self.replay_macro(register),
+                None => {}
+            },
+        }
+    }
+
+    // -- Macros -- //
+    fn replay_macro(&mut self, register: char) {
+        let Some(actions) = self.macros.get(&register).cloned() else {
+            self.report_error(format!("Register @{} is empty", register));
+            return;
+        };
+
+        for action in actions {
+            self.dispatch(action);
+        }
+    }
+
+    fn open_menu(&mut self) {
+        let Some(cmd) = self.selected_command() else {
+            self.report_error("Nothing selected");
+            return;
+        };
+
+        let items = match self.mode {
+            Mode::History => vec![
+                MenuItem::new("Copy", "Enter", Action::CopySelection),
+                if self.is_bookmarked(cmd) {
+                    MenuItem::new("Remove bookmark", "b", Action::ToggleBookmark)
+                } else {
+                    MenuItem::new("Bookmark", "b", Action::ToggleBookmark)
+                },
+                MenuItem::new("Show variants", "v", Action::ShowVariants),
+                MenuItem::new("Delete from history file", "D", Action::DeleteEntry),
+                MenuItem::new("Add directory to zoxide", "z", Action::ZoxideAdd),
+                MenuItem::new("Copy z jump", "Z", Action::CopyZoxideJump),
+            ],
+            Mode::Bookmarks if self.bookmark(cmd).is_some_and(Bookmark::is_trashed) => vec![
+                MenuItem::new("Copy", "Enter", Action::CopySelection),
+                MenuItem::new("Restore", "u", Action::RestoreBookmark),
+                MenuItem::new("Delete for good", "d", Action::DeleteBookmark),
+            ],
+            Mode::Bookmarks => vec![
+                MenuItem::new("Copy", "Enter", Action::CopySelection),
+                MenuItem::new("Edit tags", "t", Action::EditTags),
+                MenuItem::new("Edit note", "a", Action::EditNote),
+                MenuItem::new("Rename", "R", Action::EditTitle),
+                MenuItem::new("Edit command", "e", Action::EditCommand),
+                MenuItem::new("Move to group", "g", Action::MoveToGroup),
+                if self.bookmark(cmd).is_some_and(|b| b.pinned) {
+                    MenuItem::new("Unpin", "p", Action::TogglePin)
+                } else {
+                    MenuItem::new("Pin to the top", "p", Action::TogglePin)
+                },
+                MenuItem::new("Move to the trash", "d", Action::DeleteBookmark),
+                MenuItem::new("Add directory to zoxide", "z", Action::ZoxideAdd),
+                MenuItem::new("Copy z jump", "Z", Action::CopyZoxideJump),
+            ],
+            Mode::Merge => vec![
+                MenuItem::new("Copy", "Enter", Action::CopySelection),
+                MenuItem::new("Accept/reject change", "Space", Action::ToggleMergeChoice),
+                MenuItem::new("Save merged bookmarks", "w", Action::ApplyMerge),
+            ],
+        };
+        self.menu = Some(ContextMenu::new("Actions", items));
+    }
+
+    // -- Deleting history -- //
+    /// Ask before deleting every run of the selected entry (of its whole group
+    /// when collapsed) from the history file
+    fn confirm_delete_selected(&mut self) {
+        let Some(cmd) = self.current_list().get(self.selected).cloned() else {
+            self.report_error("Nothing selected");
+            return;
+        };
+
+        let targets = if self.collapse_duplicates {
+            self.variants_of(&cmd).to_vec()
+        } else {
+            vec![cmd.clone()]
+        };
+        let runs = targets
+            .iter()
+            .filter_map(|target| self.usage.get(target))
+            .map(|usage| usage.count)
+            .sum::<usize>()
+            .max(1);
+        let shown = prompt_excerpt(&self.shown_command(&cmd));
+
+        self.pending_deletion = targets;
+        self.menu = Some(ContextMenu::new(
+            "Delete from history file?",
+            vec![
+                MenuItem::new("Keep it", "Esc", Action::Cancel),
+                MenuItem::new(
+                    format!(
+                        "Delete {} {} of {}",
+                        runs,
+                        if runs == 1 { "run" } else { "runs" },
+                        truncate_for_display(&shown)
+                    ),
+                    "",
+                    Action::ConfirmDeletion,
+                ),
+            ],
+        ));
+    }
+
+    /// Remove the commands the confirmation was about from the history file,
+    /// leaving a backup next to it
+    fn delete_pending(&mut self) {
+        let targets: HashSet<String> = std::mem::take(&mut self.pending_deletion)
+            .into_iter()
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        match self
+            .history_provider()
+            .remove_entries(&|cmd| targets.contains(cmd))
+        {
+            Ok(removal) => {
+                self.message = if removal.backups.is_empty() {
+                    "Nothing to delete, the history file has changed".to_string()
+                } else {
+                    format!(
+                        "Deleted {} entr{}, backup in {}",
+                        removal.removed,
+                        if removal.removed == 1 { "y" } else { "ies" },
+                        removal.backup_list()
+                    )
+                };
+                // Reloaded like a live refresh, staying near the deleted row
+                self.live_refresh = true;
+                self.reload_history();
+            }
+            Err(e) => self.report_error(format!("Could not delete: {}", e)),
+        }
+    }
+
+    // -- Presets -- //
+    fn open_prompt(&mut self, kind: PromptKind, text: String, target: Option<String>) {
+        self.prompt = Some(TextPrompt::new(kind, text, target));
+    }
+
+    fn save_preset(&mut self, name: &str) {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            self.report_error("Preset name can't be empty");
+            return;
+        }
+
+        let saved = self.presets.save_preset(Preset {
+            name: name.clone(),
+            query: self.search_query.clone(),
+            match_mode: self.match_mode,
+        });
+        match saved {
+            Ok(()) => self.message = format!("Saved preset \"{}\"", name),
+            Err(e) => self.report_error(format!(
+                "Preset \"{}\" not saved to {}: {}",
+                name,
+                self.presets.path().display(),
+                e
+            )),
+        }
+    }
+
+    fn open_presets(&mut self) {
+        if self.presets.items.is_empty() {
+            self.report_error("No presets yet: press Ctrl+S while searching to save one");
+            return;
+        }
+
+        let items = self
+            .presets
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, preset)| {
+                let key = PRESET_KEYS.get(i).copied().unwrap_or("");
+                MenuItem::new(
+                    format!("{}  {}", preset.name, preset.query),
+                    key,
+                    Action::ApplyPreset(i),
+                )
+            })
+            .collect();
+        self.menu = Some(ContextMenu::new("Presets", items));
+    }
+
+    fn apply_preset(&mut self, index: usize) {
+        let Some(preset) = self.presets.get(index).cloned() else {
+            self.report_error(format!("No preset {}", index + 1));
+            return;
+        };
+
+        self.search_mode = true;
+        self.set_query(preset.query);
+        self.match_mode = preset.match_mode;
+        self.selected = 0;
+        self.skipped_items = 0;
+        self.update_query_results();
+        self.message = format!("Preset \"{}\"", preset.name);
+    }
+
+    /// Register currently being recorded into, if any
+    pub fn recording_register(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    // -- History -- //
+    /// Read and parse the history on a background thread, delivering it through the bus
+    pub fn start_history_load(&mut self, bus: Sender<Update>) {
+        self.history_loading = true;
+        self.history_generation += 1;
+        self.loader = Some(bus.clone());
+        let generation = self.history_generation;
+        let provider = self.history_provider();
+        let ignore = self.ignore_list();
+
+        self.watch_generation.store(generation, Ordering::Relaxed);
+        if self.config.live_reload {
+            watch::spawn(
+                provider.watched_files(),
+                generation,
+                Arc::clone(&self.watch_generation),
+                bus.clone(),
+            );
+        }
+
+        thread::spawn(move || {
+            let mut timings = Vec::new();
+            let (entries, error) = Self::load_history(provider.as_ref(), &ignore, &mut timings);
+            let _ = bus.send(Update::HistoryLoaded {
+                generation,
+                entries,
+                error,
+                timings,
+            });
+        });
+    }
+
+    /// Load the history on the calling thread, for the line-based fallback picker
+    pub fn load_history_now(&mut self) {
+        let mut timings = Vec::new();
+        let ignore = self.ignore_list();
+        let (entries, error) =
+            Self::load_history(self.history_provider().as_ref(), &ignore, &mut timings);
+        self.startup.record(timings);
+        self.set_history(entries, error);
+    }
+
+    /// Read the history again, in the background when the event loop is running
+    fn reload_history(&mut self) {
+        match self.loader.clone() {
+            Some(bus) => self.start_history_load(bus),
+            None => self.load_history_now(),
+        }
+    }
+
+    fn open_shells(&mut self) {
+        let detected = ShellType::detect();
+        let mark = |shell: &ShellType| {
+            if *shell == self.current_shell && self.config.history_source == HistorySource::Shell {
+                "  (shown)"
+            } else {
+                ""
+            }
+        };
+
+        let mut items = vec![MenuItem::new(
+            format!("Detected: {}{}", detected.name(), mark(&detected)),
+            "",
+            Action::SwitchShell(None),
+        )];
+        items.extend(ShellType::known().iter().enumerate().map(|(i, shell)| {
+            MenuItem::new(
+                format!("{}{}", shell.name(), mark(shell)),
+                "",
+                Action::SwitchShell(Some(i)),
+            )
+        }));
+        self.menu = Some(ContextMenu::new("Shell", items));
+    }
+
+    /// Show `ShellType::known()[index]`'s history, or the detected shell's
+    fn switch_shell(&mut self, index: Option<usize>) {
+        let shell = match index {
+            Some(index) => match ShellType::known().get(index) {
+                Some(shell) => shell.clone(),
+                None => return,
+            },
+            None => ShellType::detect(),
+        };
+
+        self.message = format!("Showing {} history", shell.name());
+        self.config.history_source = HistorySource::Shell;
+        self.set_shell(shell);
+        self.selected = 0;
+        self.skipped_items = 0;
+        self.reload_history();
+    }
+
+    /// Apply work finished in the background
+    pub fn apply_update(&mut self, update: Update) {
+        match update {
+            Update::HistoryLoaded {
+                generation,
+                entries,
+                error,
+                timings,
+            } => {
+                if generation != self.history_generation {
+                    return;
+                }
+                self.startup.record(timings);
+                self.set_history(entries, error);
+            }
+            Update::HistoryChanged { generation } => {
+                if generation == self.history_generation && !self.history_loading {
+                    self.live_refresh = true;
+                    self.reload_history();
+                }
+            }
+        }
+    }
+
+    /// Called after every draw to note when the UI and the history first appeared
+    pub fn frame_drawn(&mut self) {
+        self.startup.mark("first frame");
+        if !self.history_loading {
+            self.startup.mark("history on screen");
+        }
+    }
+
+    pub fn startup_profile(&self) -> &StartupProfile {
+        &self.startup
+    }
+
+    fn set_history(&mut self, entries: Vec<HistoryEntry>, error: Option<String>) {
+        // A live refresh keeps what the user was looking at
+        let refresh = std::mem::take(&mut self.live_refresh);
+        let previous_total = self.history.len() + self.older_history.len();
+        let previous_loaded = self.history.len();
+        let previous_selection = self.selected_command().map(str::to_string);
+        let previous_chip = self.active_chip().map(str::to_string);
+
+        // Oldest first, so each command ends up with its newest run
+        self.runs = HashMap::new();
+        for entry in entries.iter().rev() {
+            let run = RunInfo {
+                timestamp: entry.timestamp,
+                duration: entry.duration,
+                exit_status: entry.exit_status,
+                cwd: entry.cwd.clone(),
+                paths: entry.paths.clone(),
+                origin: entry.origin.clone(),
+            };
+            if !run.is_empty() {
+                self.runs.insert(entry.command.clone(), run);
+            }
+        }
+
+        let mut entries: Vec<String> = entries.into_iter().map(|entry| entry.command).collect();
+        self.usage = Usage::collect(&entries);
+        self.variants = HashMap::new();
+        self.normalized = HashMap::new();
+        for cmd in &entries {
+            let key = self.normalizer.normalize(cmd);
+            if key != cmd.as_str() {
+                self.normalized.insert(cmd.clone(), key.to_string());
+            }
+            let variants = self.variants.entry(key.into_owned()).or_default();
+            if !variants.contains(cmd) {
+                variants.push(cmd.clone());
+            }
+        }
+        self.program_chips = HistoryStats::compute(&entries)
+            .top_programs
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .map(|(program, _)| program)
+            .take(MAX_CHIPS)
+            .collect();
+        self.active_chip = None;
+        if self.config.dedup_history {
+            // Newest first, so the most recent run is the one kept
+            let mut seen = HashSet::new();
+            entries.retain(|cmd| seen.insert(self.duplicate_key(cmd).to_string()));
+        }
+        let total = entries.len();
+        let chunk = self.config.history_limit.entries();
+        let loaded = if refresh {
+            previous_loaded.max(chunk)
+        } else {
+            chunk
+        };
+        self.older_history = entries.split_off(loaded.min(entries.len()));
+        self.history = entries;
+        self.history_error = error;
+        self.history_loading = false;
+
+        if !refresh {
+            self.update_query_results();
+            return;
+        }
+        self.active_chip =
+            previous_chip.and_then(|chip| self.program_chips.iter().position(|p| *p == chip));
+        self.update_query_results();
+        if let Some(position) = previous_selection
+            .and_then(|cmd| self.current_list().iter().position(|entry| *entry == cmd))
+        {
+            self.selected = position;
+            self.scroll_to_selection();
+        }
+        if total > previous_total {
+            let added = total - previous_total;
+            self.message = format!("{} new command{}", added, if added == 1 { "" } else { "s" });
+        }
+    }
+
+    pub fn is_history_loading(&self) -> bool {
+        self.history_loading
+    }
+
+    /// Compiled ignore rules, reporting patterns that don't compile
+    fn ignore_list(&mut self) -> IgnoreList {
+        let ignore = IgnoreList::new(&self.config.ignore);
+        if !ignore.invalid.is_empty() {
+            self.report_error(format!(
+                "Invalid ignore pattern {}",
+                ignore.invalid.join(", ")
+            ));
+        }
+        ignore
+    }
+
+    /// Parsed history without ignored commands, or the reason it could not be read
+    fn load_history(
+        provider: &dyn HistoryProvider,
+        ignore: &IgnoreList,
+        timings: &mut Vec<Phase>,
+    ) -> (Vec<HistoryEntry>, Option<String>) {
+        match provider.load(timings) {
+            Ok(mut entries) => {
+                ignore.apply(&mut entries);
+                // Files appended by several sessions can be out of order
+                if entries.iter().all(|entry| entry.timestamp.is_some()) {
+                    entries.sort_by_key(|entry| Reverse(entry.timestamp));
+                }
+                (entries, None)
+            }
+            Err(e) => (Vec::new(), Some(e.to_string())),
+        }
+    }
+
+    /// Whether there is no history at all, as opposed to no search matches
+    pub fn history_is_empty(&self) -> bool {
+        self.history.is_empty() && self.older_history.is_empty()
+    }
+
+    /// Use `shell` instead of the detected one; takes effect on the next load
+    pub fn set_shell(&mut self, shell: ShellType) {
+        self.current_shell = shell;
+    }
+
+    /// Timestamp and duration of the newest run of `cmd`, if recorded
+    pub fn run_info(&self, cmd: &str) -> Option<&RunInfo> {
+        self.runs.get(cmd)
+    }
+
+    /// Where the history list is read from
+    pub fn history_provider(&self) -> Box<dyn HistoryProvider> {
+        self.config
+            .history_source
+            .provider(&self.current_shell, &self.config)
+    }
+
+    /// Why the history file could not be read, if it could not
+    pub fn history_error(&self) -> Option<&str> {
+        self.history_error.as_deref()
+    }
+
+    /// Whether older entries are still waiting to be loaded
+    pub fn has_older_history(&self) -> bool {
+        !self.older_history.is_empty()
+    }
+
+    /// Move the next chunk of older entries into the visible history
+    fn load_older_history(&mut self) {
+        if self.older_history.is_empty() {
+            return;
+        }
+
+        let rest = self.older_history.split_off(
+            self.config
+                .history_limit
+                .entries()
+                .min(self.older_history.len()),
+        );
+        let chunk = std::mem::replace(&mut self.older_history, rest);
+        self.history.extend(chunk);
+        self.update_query_results();
+    }
+
+    pub fn search_query(&self) -> &str {
+        &self.search_query
+    }
+
+    /// Byte offset of the cursor in the search query
+    pub fn search_cursor(&self) -> usize {
+        self.search_cursor
+    }
+
+    /// Replace the whole query, leaving the cursor at its end
+    fn set_query(&mut self, query: String) {
+        self.search_cursor = query.len();
+        self.search_query = query;
+    }
+
+    /// Insert pasted text at the cursor, starting a search if none is active.
+    /// Line breaks become spaces so a copied multi-line command still matches.
+    pub fn paste(&mut self, text: &str) {
+        if self.prompt.is_some() || self.menu.is_some() || self.show_help {
+            return;
+        }
+        if !self.search_mode {
+            self.search_mode = true;
+            self.clear_query();
+        }
+
+        self.query_history.reset();
+        let text: String = text
+            .trim_end_matches(['\r', '\n'])
+            .replace("\r\n", "\n")
+            .chars()
+            .map(|c| if c == '\n' || c == '\r' { ' ' } else { c })
+            .filter(|c| !c.is_control())
+            .collect();
+        self.search_query.insert_str(self.search_cursor, &text);
+        self.search_cursor += text.len();
+        self.update_query_results();
+    }
+
+    fn paste_clipboard(&mut self) {
+        let contents = copypasta::ClipboardContext::new().and_then(|mut ctx| ctx.get_contents());
+        match contents {
+            Ok(text) => self.paste(&text),
+            Err(e) => self.report_error(format!("Can't read the clipboard: {}", e)),
+        }
+    }
+
+    pub fn push_query(&mut self, c: char) {
+        self.query_history.reset();
+        if c.is_ascii_graphic() || c == ' ' {
+            self.search_query.insert(self.search_cursor, c);
+            self.search_cursor += c.len_utf8();
+            self.update_query_results();
+        }
+    }
+
+    /// Delete the character before the cursor
+    pub fn pop_query(&mut self) {
+        self.query_history.reset();
+        if let Some((index, _)) = self.search_query[..self.search_cursor]
+            .char_indices()
+            .next_back()
+        {
+            self.search_query.remove(index);
+            self.search_cursor = index;
+        }
+        self.update_query_results();
+    }
+
+    fn move_search_cursor(&mut self, motion: CursorMotion) {
+        let before = &self.search_query[..self.search_cursor];
+        let after = &self.search_query[self.search_cursor..];
+        self.search_cursor = match motion {
+            CursorMotion::Left => before.char_indices().next_back().map_or(0, |(i, _)| i),
+            CursorMotion::Right => {
+                self.search_cursor + after.chars().next().map_or(0, char::len_utf8)
+            }
+            CursorMotion::Home => 0,
+            CursorMotion::End => self.search_query.len(),
+        };
+    }
+
+    /// Ctrl+W: delete back to the start of the word before the cursor
+    fn delete_query_word(&mut self) {
+        self.query_history.reset();
+        let before = self.search_query[..self.search_cursor].trim_end();
+        let start = before.rfind(' ').map_or(0, |i| i + 1);
+        self.search_query
+            .replace_range(start..self.search_cursor, "");
+        self.search_cursor = start;
+        self.update_query_results();
+    }
+
+    pub fn clear_query(&mut self) {
+        self.query_history.reset();
+        self.search_query.clear();
+        self.search_cursor = 0;
+        self.selected = 0;
+        self.skipped_items = 0;
+        self.update_query_results();
+    }
+
+    /// Walk through past queries while the search bar is empty or showing a recalled
+    /// one; with a freshly typed query the arrows keep moving the selection
+    fn recall_query(&mut self, direction: MoveDirection) {
+        if !self.search_query.is_empty() && !self.query_history.is_recalling() {
+            self.move_selection(direction);
+            return;
+        }
+
+        let recalled = match direction {
+            MoveDirection::Up => self.query_history.older(),
+            MoveDirection::Down => self.query_history.newer(),
+        };
+        let recalled = recalled.unwrap_or_default().to_string();
+        self.set_query(recalled);
+        self.selected = 0;
+        self.skipped_items = 0;
+        self.update_query_results();
+    }
+
+    fn update_query_results(&mut self) {
+        // The oldest entries only come first once they are all loaded
+        if self.oldest_first {
+            self.history.append(&mut self.older_history);
+        }
+
+        let case_sensitive = self.config.search_case.is_sensitive(&self.search_query);
+        let matcher = match Matcher::new(self.match_mode, &self.search_query, case_sensitive) {
+            Ok(matcher) => matcher,
+            Err(e) => {
+                // Keep the last valid results while the pattern is being typed
+                self.report_error(format!("{}, showing previous results", e));
+                return;
+            }
+        };
+
+        self.queried_history = self.rank(&self.history, &matcher, self.collapse_duplicates);
+        let ranked = self.rank(self.bookmarks.iter().map(|b| &b.command), &matcher, false);
+        self.group_bookmarks(ranked);
+
+        // Ties go to the newest entry, which comes first in the list unless reversed
+        self.top_match = if self.search_query.is_empty() {
+            None
+        } else {
+            let matches = self
+                .current_list()
+                .iter()
+                .filter(|cmd| self.score(&matcher, cmd).is_some());
+            let count = |cmd: &&String| self.usage.get(*cmd).map_or(0, |u| u.count);
+            if self.oldest_first {
+                matches.max_by_key(count).cloned()
+            } else {
+                matches.min_by_key(|cmd| Reverse(count(cmd))).cloned()
+            }
+        };
+        self.matcher = Some(matcher);
+        self.selected = self
+            .selected
+            .min(self.current_list().len().saturating_sub(1));
+        self.skipped_items = self.skipped_items.min(self.selected);
+    }
+
+    /// Entries of `source` matching the query, in the active sort order
+    fn rank<'a>(
+        &self,
+        source: impl IntoIterator<Item = &'a String>,
+        matcher: &Matcher,
+        collapse: bool,
+    ) -> Vec<String> {
+        let program = self.active_chip();
+        let mut scored: Vec<(i64, &String)> = source
+            .into_iter()
+            .filter(|cmd| program.is_none() || stats::program_name(cmd) == program)
+            .filter_map(|cmd| match self.search_display {
+                SearchDisplay::Filter => self.score(matcher, cmd).map(|score| (score, cmd)),
+                SearchDisplay::Highlight => Some((0, cmd)),
+            })
+            .collect();
+        if !self.search_query.is_empty() && self.search_display == SearchDisplay::Filter {
+            for (score, cmd) in scored.iter_mut() {
+                *score += self.usage.get(*cmd).map_or(0, Usage::frecency_bonus);
+                if self.config.boost_bookmarks && self.is_bookmarked(cmd) {
+                    *score += BOOKMARK_BOOST;
+                }
+            }
+        }
+        // Stable sorts keep newer entries first among equal keys
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        match self.sort_mode {
+            SortMode::Recency => {}
+            SortMode::Frequency => {
+                scored.sort_by_key(|(_, cmd)| Reverse(self.usage.get(*cmd).map_or(0, |u| u.count)))
+            }
+            SortMode::Alphabetical => scored.sort_by_key(|(_, cmd)| *cmd),
+            SortMode::Length => scored.sort_by_key(|(_, cmd)| cmd.chars().count()),
+        }
+        let mut ranked: Vec<String> = if collapse {
+            // One row per group, showing its newest form
+            let mut seen = HashSet::new();
+            scored
+                .into_iter()
+                .filter_map(|(_, cmd)| {
+                    let key = self.duplicate_key(cmd);
+                    let canonical = self
+                        .variants
+                        .get(key)
+                        .and_then(|v| v.first())
+                        .unwrap_or(cmd);
+                    seen.insert(key).then(|| canonical.clone())
+                })
+                .collect()
+        } else {
+            scored.into_iter().map(|(_, cmd)| cmd.clone()).collect()
+        };
+        if self.oldest_first {
+            ranked.reverse();
+        }
+        ranked
+    }
+
+    /// The sort mode as shown in the header, e.g. "recency, oldest first"
+    pub fn sort_label(&self) -> String {
+        match (self.oldest_first, self.sort_mode) {
+            (false, mode) => mode.label().to_string(),
+            (true, SortMode::Recency) => "recency, oldest first".to_string(),
+            (true, mode) => format!("{}, reversed", mode.label()),
+        }
+    }
+
+    /// Reverse the list, keeping the same entry selected
+    fn toggle_order(&mut self) {
+        let selected = self.selected;
+        let command = self.selected_command().map(str::to_string);
+        self.oldest_first = !self.oldest_first;
+        self.update_query_results();
+
+        // Mirrored position first, so the same one of several identical rows stays selected
+        let list = self.current_list();
+        let mirrored = list.len().saturating_sub(selected + 1);
+        self.selected = match command {
+            Some(command) if list.get(mirrored) != Some(&command) => {
+                list.iter().position(|cmd| *cmd == command).unwrap_or(0)
+            }
+            Some(_) => mirrored,
+            None => 0,
+        };
+        self.scroll_to_selection();
+        self.message = format!("Sorted by {}", self.sort_label());
+    }
+
+    fn cycle_chip(&mut self, direction: MoveDirection) {
+        if self.program_chips.is_empty() {
+            self.report_error("No common programs to filter by");
+            return;
+        }
+
+        // `None` ("all") sits before the first chip
+        let count = self.program_chips.len() + 1;
+        let position = self.active_chip.map_or(0, |i| i + 1);
+        let position = match direction {
+            MoveDirection::Down => (position + 1) % count,
+            MoveDirection::Up => (position + count - 1) % count,
+        };
+        self.active_chip = position.checked_sub(1);
+        self.selected = 0;
+        self.skipped_items = 0;
+        self.update_query_results();
+    }
+
+    pub fn program_chips(&self) -> &[String] {
+        &self.program_chips
+    }
+
+    /// Program the list is pre-filtered to, if a chip is selected
+    pub fn active_chip(&self) -> Option<&str> {
+        self.active_chip
+            .and_then(|i| self.program_chips.get(i))
+            .map(String::as_str)
+    }
+
+    /// How often a collapsed entry occurs in the whole history, when worth showing
+    pub fn duplicate_count(&self, cmd: &str) -> Option<usize> {
+        if !self.collapse_duplicates {
+            return None;
+        }
+        let count: usize = self
+            .variants_of(cmd)
+            .iter()
+            .filter_map(|variant| self.usage.get(variant))
+            .map(|usage| usage.count)
+            .sum();
+        (count > 1).then_some(count)
+    }
+
+    /// Entries with the same key are duplicates, e.g. differing only in spacing
+    fn duplicate_key<'a>(&'a self, cmd: &'a str) -> &'a str {
+        self.normalized.get(cmd).map_or(cmd, String::as_str)
+    }
+
+    /// Match `cmd` as written or in normalized form, whichever scores better,
+    /// so near-identical commands rank alike; bookmarks also match by title
+    fn score(&self, matcher: &Matcher, cmd: &str) -> Option<i64> {
+        let bookmark = self.bookmark(cmd);
+        let normalized = self
+            .normalized
+            .get(cmd)
+            .and_then(|normalized| matcher.score(normalized, bookmark));
+        let title = bookmark
+            .and_then(|b| b.title.as_deref())
+            .and_then(|title| matcher.score(title, bookmark));
+        matcher.score(cmd, bookmark).max(normalized).max(title)
+    }
+
+    /// Distinct forms of a command that collapse into the same row, newest first
+    fn variants_of(&self, cmd: &str) -> &[String] {
+        self.variants
+            .get(self.duplicate_key(cmd))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// List every recorded form of the selected entry to copy a specific one
+    fn show_variants(&mut self) {
+        let Some(cmd) = self.current_list().get(self.selected) else {
+            self.report_error("Nothing selected");
+            return;
+        };
+
+        let variants = self.variants_of(cmd).to_vec();
+        if variants.is_empty() {
+            self.report_error("No other variants of this command");
+            return;
+        }
+
+        let items = variants
+            .iter()
+            .enumerate()
+            .map(|(i, variant)| {
+                let count = self.usage.get(variant).map_or(0, |u| u.count);
+                MenuItem::new(
+                    format!("{} (×{})", display_command(self, variant), count),
+                    "",
+                    Action::CopyVariant(i),
+                )
+            })
+            .collect();
+        self.variant_choices = variants;
+        self.menu = Some(ContextMenu::new("Variants", items));
+    }
+
+    pub fn move_selection(&mut self, direction: MoveDirection) {
+        // Fetch older entries once the selection approaches the end of the loaded range
+        if direction == MoveDirection::Down
+            && self.mode == Mode::History
+            && self.selected + self.size.get().max(1) >= self.queried_history.len()
+        {
+            self.load_older_history();
+        }
+
+        let max_index = self.current_list().len().saturating_sub(1);
+
+        match direction {
+            MoveDirection::Up if self.selected > 0 => self.selected -= 1,
+            MoveDirection::Down if self.selected < max_index => self.selected += 1,
+            _ => (),
+        }
+
+        self.scroll_to_selection();
+    }
+
+    /// Adjust the scroll offset so the selected row is on screen
+    fn scroll_to_selection(&mut self) {
+        let size = self.size.get();
+        if size == 0 {
+            return;
+        }
+
+        if self.selected < self.skipped_items {
+            self.skipped_items = self.selected;
+        } else if self.selected >= self.skipped_items + size {
+            self.skipped_items = self.selected.saturating_sub(size - 1);
+        }
+    }
+
+    /// Whether `cmd` should be marked as a match of a highlight-only search
+    pub fn is_highlighted(&self, cmd: &str) -> bool {
+        self.search_display == SearchDisplay::Highlight
+            && !self.search_query.is_empty()
+            && self
+                .matcher
+                .as_ref()
+                .is_some_and(|matcher| self.score(matcher, cmd).is_some())
+    }
+
+    /// Move to the next/previous entry matching the query, wrapping around
+    fn jump_to_match(&mut self, direction: MoveDirection) {
+        let Some(matcher) = self
+            .matcher
+            .as_ref()
+            .filter(|_| !self.search_query.is_empty())
+        else {
+            self.report_error("No search to jump through");
+            return;
+        };
+
+        let list = self.current_list();
+        let len = list.len();
+        let found = (1..=len)
+            .map(|step| match direction {
+                MoveDirection::Down => (self.selected + step) % len,
+                MoveDirection::Up => (self.selected + len - step % len) % len,
+            })
+            .find(|&i| self.score(matcher, &list[i]).is_some());
+
+        match found {
+            Some(index) => {
+                self.selected = index;
+                self.scroll_to_selection();
+            }
+            None => self.report_error("No matches"),
+        }
+    }
+
+    /// Move to the next/previous bookmarked entry in the history list
+    pub fn jump_to_bookmark(&mut self, direction: MoveDirection) {
+        let list = &self.queried_history;
+        let found = match direction {
+            MoveDirection::Down => {
+                (self.selected + 1..list.len()).find(|&i| self.is_bookmarked(&list[i]))
+            }
+            MoveDirection::Up => (0..self.selected)
+                .rev()
+                .find(|&i| self.is_bookmarked(&list[i])),
+        };
+
+        match found {
+            Some(index) => {
+                self.selected = index;
+                self.scroll_to_selection();
+            }
+            None => {
+                self.message = match direction {
+                    MoveDirection::Down => "No bookmarked entry below".into(),
+                    MoveDirection::Up => "No bookmarked entry above".into(),
+                };
+            }
+        }
+    }
+
+    // -- Feedback -- //
+    /// Show an error in the status bar and trigger the configured error feedback
+    fn report_error(&mut self, message: impl Into<String>) {
+        self.message = message.into();
+        self.feedback(self.config.error_feedback);
+    }
+
+    fn feedback(&mut self, feedback: Feedback) {
+        match feedback {
+            Feedback::None => {}
+            Feedback::Bell => self.bell = true,
+            Feedback::Flash => self.flash_until = Some(Instant::now() + FLASH_DURATION),
+        }
+    }
+
+    /// Whether a bell was requested since the last call
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
+    /// Whether the screen should currently be drawn inverted
+    pub fn is_flashing(&self) -> bool {
+        self.flash_until.is_some_and(|until| Instant::now() < until)
+    }
+
+    // -- zoxide -- //
+    fn selected_directory(&mut self) -> Option<PathBuf> {
+        // Paths the shell recorded beat guessing from the command text
+        let dir = self.selected_command().and_then(|cmd| {
+            let recorded = self.runs.get(cmd).and_then(|run| {
+                run.paths
+                    .iter()
+                    .rev()
+                    .map(PathBuf::from)
+                    .find(|path| path.is_absolute() && path.is_dir())
+            });
+            recorded.or_else(|| zoxide::directory_in(cmd))
+        });
+        if dir.is_none() {
+            self.report_error("No existing directory in this command");
+        }
+        dir
+    }
+
+    fn zoxide_add_selected(&mut self) {
+        let Some(dir) = self.selected_directory() else {
+            return;
+        };
+
+        match zoxide::add(&dir) {
+            Ok(()) => self.message = format!("Added {} to zoxide", dir.display()),
+            Err(e) => self.report_error(format!("zoxide add failed: {}", e)),
+        }
+    }
+
+    fn copy_zoxide_jump(&mut self) {
+        if let Some(dir) = self.selected_directory() {
+            self.copy_command(&format!("z {}", dir.display()));
+        }
+    }
+
+    // -- Selection -- //
+    /// The selected entry, or `None` on a group header and on the bookmark
+    /// order row of a merge
+    pub fn selected_command(&self) -> Option<&str> {
+        if self.group_at(self.selected).is_some() {
+            return None;
+        }
+        self.current_list()
+            .get(self.selected)
+            .map(String::as_str)
+            .filter(|cmd| !cmd.is_empty())
+    }
+
+    pub fn copy_selected(&mut self) {
+        // Enter on a group header folds it instead
+        if self.group_at(self.selected).is_some() {
+            self.toggle_group();
+            return;
+        }
+        let Some(selected_cmd) = self.selected_command().map(str::to_string) else {
+            self.report_error("No command to copy");
+            return;
+        };
+        self.copy_unless_secret(selected_cmd, self.mode == Mode::Bookmarks);
+        self.remember_search();
+    }
+
+    /// Add the search to the recent ones, after what it led to so a failure
+    /// to save them isn't hidden by that action's message
+    fn remember_search(&mut self) {
+        if let Err(e) = self.query_history.push(&self.search_query) {
+            self.report_error(format!(
+                "Recent searches not saved to {}: {}",
+                self.query_history.path().display(),
+                e
+            ));
+        }
+    }
+
+    /// Copy `cmd`, or ask first while a secret in it is masked; a confirmed
+    /// copy still gets the full command
+    fn copy_unless_secret(&mut self, cmd: String, bookmark_use: bool) {
+        if self.reveal_secrets || self.redactor.mask(&cmd).is_none() {
+            if self.copy_command(&cmd) && bookmark_use {
+                self.record_bookmark_use(&cmd);
+            }
+            return;
+        }
+
+        let shown = prompt_excerpt(&self.shown_command(&cmd));
+        self.message = "This command contains a secret".to_string();
+        self.pending_copy = Some((cmd, bookmark_use));
+        self.menu = Some(ContextMenu::new(
+            "Copy a secret?",
+            vec![
+                MenuItem::new("Don't copy", "Esc", Action::Cancel),
+                MenuItem::new(
+                    format!("Copy {} unmasked", truncate_for_display(&shown)),
+                    "",
+                    Action::CopySecret,
+                ),
+            ],
+        ));
+    }
+
+    /// `cmd` as listed: with likely secrets masked unless they were revealed
+    pub fn shown_command<'a>(&self, cmd: &'a str) -> Cow<'a, str> {
+        match self.redactor.mask(cmd).filter(|_| !self.reveal_secrets) {
+            Some(masked) => Cow::Owned(masked),
+            None => Cow::Borrowed(cmd),
+        }
+    }
+
+    /// Suggestion accepted with Tab while searching
+    pub fn top_match(&self) -> Option<&str> {
+        self.top_match.as_deref()
+    }
+
+    /// Put `selected_cmd` on the clipboard; false if that failed
+    fn copy_command(&mut self, selected_cmd: &str) -> bool {
+        // Platform-specific clipboard handling
+        if let Err(e) = clipboard::copy(selected_cmd) {
+            self.report_error(format!("Can't copy: {}", e));
+            return false;
+        }
+
+        // Universal fallback
+        let _ = copypasta::ClipboardContext::new()
+            .and_then(|mut ctx| ctx.set_contents(selected_cmd.to_owned()));
+
+        self.message = "Copied to clipboard!".to_string();
+        self.feedback(self.config.copy_feedback);
+        true
+    }
 
     // -- Bookmarks -- //
     pub fn current_list(&self) -> &Vec<String> {
-        if self.bookmark_mode {
-            &self.bookmarks
-        } else {
-            &self.queried_history
+        match self.mode {
+            Mode::History => &self.queried_history,
+            Mode::Bookmarks => &self.queried_bookmarks,
+            Mode::Merge => match &self.merge {
+                Some(merge) => &merge.commands,
+                None => &self.queried_bookmarks,
+            },
         }
     }
 
-    fn get_bookmark_path() -> PathBuf {
-        directories::BaseDirs::new()
-            .expect("Failed to determine user home directory")
-            .home_dir()
-            .join(".term_kit_bookmarks")
+    /// Where bookmarks are kept, in the configured format
+    pub fn bookmark_store(&self) -> Box<dyn BookmarkStore> {
+        self.config.bookmark_format.store()
+    }
+
+    /// Next to the bookmarks, see `storage::DataFile`
+    pub fn get_presets_path() -> PathBuf {
+        PRESETS_FILE.path()
+    }
+
+    pub fn get_query_history_path() -> PathBuf {
+        SEARCHES_FILE.path()
     }
 
     fn load_bookmarks(&mut self) {
-        if let Ok(content) = fs::read_to_string(&self.bookmark_path) {
-            self.bookmarks = serde_json::from_str(&content).unwrap_or_default();
+        let store = self.bookmark_store();
+        self.bookmarks = match store.load() {
+            Ok((version, bookmarks)) => {
+                self.check_bookmark_version(version);
+                bookmarks
+            }
+            Err(e) => {
+                self.bookmarks_read_only = Some(format!(
+                    "Can't read {} ({}); bookmarks won't be saved until it is fixed",
+                    store.location().display(),
+                    e
+                ));
+                Vec::new()
+            }
+        };
+        self.bookmark_snapshot = self.bookmarks.clone();
+        if let Some(reason) = self.bookmarks_read_only.clone() {
+            self.report_error(reason);
         }
+
+        // Empty the trash of bookmarks deleted long enough ago
+        let cutoff = entry::now().saturating_sub(self.config.bookmark_trash_days * 86_400);
+        let before = self.bookmarks.len();
+        self.bookmarks
+            .retain(|b| b.trashed_at.is_none_or(|at| at >= cutoff));
+        if self.bookmarks.len() != before {
+            self.save_bookmarks();
+        }
+        self.update_query_results();
     }
 
-    fn save_bookmarks(&self) {
-        match serde_json::to_string_pretty(&self.bookmarks) {
-            Ok(data) => {
-                if let Err(e) = fs::write(&self.bookmark_path, data) {
-                    eprintln!("Failed to save bookmarks: {}", e);
-                }
+    /// Stop saving bookmarks to a file written by a newer version, whose
+    /// layout may mean more than this one knows about
+    fn check_bookmark_version(&mut self, version: u32) {
+        if version > bookmark::FILE_VERSION {
+            self.bookmarks_read_only = Some(format!(
+                "Bookmarks were saved by a newer term-kit (format {}); changes won't be saved",
+                version
+            ));
+        }
+    }
+
+    /// Write bookmarks, unless the file changed on disk since we last read it,
+    /// in which case the user resolves the differences in merge mode first;
+    /// true if they were written
+    fn save_bookmarks(&mut self) -> bool {
+        let store = self.bookmark_store();
+        let on_disk = match store.load() {
+            Ok((version, on_disk)) => {
+                self.check_bookmark_version(version);
+                on_disk
+            }
+            Err(e) => {
+                self.report_error(format!(
+                    "Bookmarks not saved, can't read {}: {}",
+                    store.location().display(),
+                    e
+                ));
+                return false;
+            }
+        };
+        if on_disk != self.bookmark_snapshot {
+            return self.start_bookmark_merge(on_disk);
+        }
+        self.write_bookmarks()
+    }
+
+    fn write_bookmarks(&mut self) -> bool {
+        if let Some(reason) = self.bookmarks_read_only.clone() {
+            self.report_error(reason);
+            return false;
+        }
+        let store = self.bookmark_store();
+        // Only called right after the file was found to hold the snapshot
+        if let Err(e) = store.save_over(&self.bookmarks, &self.bookmark_snapshot) {
+            self.report_error(format!(
+                "Bookmarks not saved to {}: {}",
+                store.location().display(),
+                e
+            ));
+            return false;
+        }
+        self.bookmark_snapshot = self.bookmarks.clone();
+        true
+    }
+
+    /// Merge `remote` into our bookmarks, saving right away when nothing
+    /// conflicts; true if they were written
+    fn start_bookmark_merge(&mut self, remote: Vec<Bookmark>) -> bool {
+        let merge = BookmarkMerge::new(
+            self.bookmark_snapshot.clone(),
+            &self.bookmarks,
+            remote.clone(),
+        );
+
+        if merge.changes.is_empty() {
+            self.bookmark_snapshot = remote;
+            self.bookmarks = merge.resolve();
+            return self.write_bookmarks();
+        }
+
+        self.message = format!(
+            "Bookmarks changed on disk: {} conflicting changes to review",
+            merge.changes.len()
+        );
+        self.merge = Some(merge);
+        self.set_mode(Mode::Merge);
+        false
+    }
+
+    fn apply_bookmark_merge(&mut self) {
+        let Some(merge) = self.merge.take() else {
+            return;
+        };
+
+        self.bookmarks = merge.resolve();
+        self.bookmark_snapshot = merge.remote;
+        // Another writer may have raced us again while we were resolving
+        let saved = self.save_bookmarks();
+        if self.merge.is_none() {
+            if saved {
+                self.message = "Bookmark merge saved!".to_string();
+            }
+            self.set_mode(Mode::Bookmarks);
+        }
+    }
+
+    /// Whether `cmd` is bookmarked and not in the trash
+    pub fn is_bookmarked(&self, cmd: &str) -> bool {
+        self.bookmark(cmd).is_some_and(|b| !b.is_trashed())
+    }
+
+    pub fn bookmark(&self, cmd: &str) -> Option<&Bookmark> {
+        self.bookmarks.iter().find(|b| b.command == cmd)
+    }
+
+    fn selected_bookmark(&self) -> Option<&Bookmark> {
+        self.selected_command().and_then(|cmd| self.bookmark(cmd))
+    }
+
+    /// The group header at `index` of the bookmark list
+    pub fn group_at(&self, index: usize) -> Option<&GroupRow> {
+        match self.mode {
+            Mode::Bookmarks => self.group_rows.get(&index),
+            _ => None,
+        }
+    }
+
+    /// Lay out ranked bookmarks in sections: pinned ones first, then the most
+    /// recently used, ungrouped ones, each group under a header row in the
+    /// order the groups were first used, and the trash last
+    fn group_bookmarks(&mut self, ranked: Vec<String>) {
+        let (trashed, ranked): (Vec<String>, Vec<String>) = ranked
+            .into_iter()
+            .partition(|cmd| self.bookmark(cmd).is_some_and(Bookmark::is_trashed));
+        let mut groups: Vec<&str> = Vec::new();
+        for group in self
+            .bookmarks
+            .iter()
+            .filter(|b| !b.is_trashed())
+            .filter_map(|b| b.group.as_deref())
+        {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+        let group_of = |cmd: &str| self.bookmark(cmd).and_then(|b| b.group.as_deref());
+
+        let (mut rows, unpinned): (Vec<String>, Vec<String>) = ranked
+            .into_iter()
+            .partition(|cmd| self.bookmark(cmd).is_some_and(|b| b.pinned));
+
+        // Listed again in their own section; a search ranks them anyway
+        let mut recent: Vec<(u64, &String)> = unpinned
+            .iter()
+            .filter_map(|cmd| Some((self.bookmark(cmd)?.last_used?, cmd)))
+            .collect();
+        recent.sort_by_key(|(last_used, _)| Reverse(*last_used));
+        recent.truncate(RECENT_BOOKMARKS);
+        let recent: Vec<String> = recent.into_iter().map(|(_, cmd)| cmd.clone()).collect();
+        let recent = if self.search_query.is_empty() {
+            recent
+        } else {
+            Vec::new()
+        };
+
+        let mut sections = vec![(Section::Recent, recent)];
+        sections.push((
+            Section::Ungrouped,
+            unpinned
+                .iter()
+                .filter(|cmd| group_of(cmd).is_none())
+                .cloned()
+                .collect(),
+        ));
+        for group in groups {
+            let members = unpinned
+                .iter()
+                .filter(|cmd| group_of(cmd) == Some(group))
+                .cloned()
+                .collect();
+            sections.push((Section::Group(group.to_string()), members));
+        }
+        sections.push((Section::Trash, trashed));
+
+        let mut headers = HashMap::new();
+        for (section, members) in sections {
+            if members.is_empty() {
+                continue;
+            }
+            // Ungrouped bookmarks only need a header to end the recent ones
+            if section == Section::Ungrouped && headers.is_empty() {
+                rows.extend(members);
+                continue;
             }
-            Err(e) => eprintln!("Failed to serialize bookmarks: {}", e),
+            // Groups show their matches while searching; the trash stays as it was
+            let collapsed = self.collapsed_sections.contains(&section)
+                && (self.search_query.is_empty() || section == Section::Trash);
+            rows.push(section.name().to_string());
+            headers.insert(
+                rows.len() - 1,
+                GroupRow {
+                    section,
+                    count: members.len(),
+                    collapsed,
+                },
+            );
+            if !collapsed {
+                rows.extend(members);
+            }
+        }
+        self.queried_bookmarks = rows;
+        self.group_rows = headers;
+    }
+
+    /// Fold or unfold the section of the selected row, keeping its header selected
+    fn toggle_group(&mut self) {
+        // The closest header above, unless the row comes after its section
+        let section = (0..=self.selected)
+            .rev()
+            .find_map(|i| Some((i, self.group_at(i)?)))
+            .filter(|(i, row)| self.selected <= i + if row.collapsed { 0 } else { row.count })
+            .map(|(_, row)| row.section.clone());
+        let Some(section) = section else {
+            self.report_error("Not in a group: press g to move it to one");
+            return;
+        };
+
+        if !self.collapsed_sections.remove(&section) {
+            self.collapsed_sections.insert(section.clone());
         }
+        self.update_query_results();
+        if let Some((&row, _)) = self
+            .group_rows
+            .iter()
+            .find(|(_, row)| row.section == section)
+        {
+            self.selected = row;
+        }
+        self.scroll_to_selection();
     }
 
-    pub fn toggle_bookmark_mode(&mut self) {
-        self.bookmark_mode = !self.bookmark_mode;
+    pub fn set_mode(&mut self, mode: Mode) {
+        // Leaving merge mode without saving keeps the on-disk copy untouched
+        if mode != Mode::Merge {
+            self.merge = None;
+        }
+        if let Some(pos) = self.recent_modes.iter().position(|m| *m == mode) {
+            let mode = self.recent_modes.remove(pos);
+            self.recent_modes.insert(0, mode);
+        }
+        self.mode = mode;
         self.selected = 0;
         self.skipped_items = 0;
+        self.update_query_results();
     }
 
     pub fn toggle_bookmark(&mut self) {
@@ -404,28 +3419,430 @@ impl App {
             return;
         };
 
-        if let Some(pos) = self.bookmarks.iter().position(|b| b == &cmd) {
-            self.bookmarks.remove(pos);
+        if let Some(bookmark) = self
+            .bookmarks
+            .iter_mut()
+            .find(|b| b.command == cmd && b.is_trashed())
+        {
+            bookmark.trashed_at = None;
+            self.message = "Bookmark restored from the trash!".to_string();
+        } else if self.is_bookmarked(&cmd) {
+            self.trash_bookmark(&cmd);
             self.message = "Bookmark removed!".to_string();
+        } else if let Some(similar) = self.similar_bookmark(&cmd) {
+            self.confirm_similar_bookmark(cmd, similar);
+            return;
+        } else {
+            self.add_bookmark(cmd);
+            return;
+        }
+        self.save_bookmarks();
+        self.update_query_results();
+    }
+
+    /// Merge the bookmarks of the file at `text` into ours
+    fn import_bookmarks(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        let path = sources::expand_home(Path::new(text));
+        match import::read(&path) {
+            Ok(incoming) => {
+                let summary = import::merge(&mut self.bookmarks, incoming, &self.normalizer);
+                self.message = format!("Imported bookmarks: {}", summary);
+                self.save_bookmarks();
+                self.update_query_results();
+            }
+            Err(e) => self.report_error(format!("Import failed: {:#}", e)),
+        }
+    }
+
+    /// Bookmark `cmd` and prompt for its tags
+    fn add_bookmark(&mut self, cmd: String) {
+        self.bookmarks.push(Bookmark::new(cmd.clone()));
+        self.message = "Bookmark added!".to_string();
+        self.open_prompt(PromptKind::BookmarkTags, String::new(), Some(cmd));
+        self.save_bookmarks();
+        if self.merge.is_some() {
+            // Tagging waits until the merge is resolved
+            self.prompt = None;
+        }
+        self.update_query_results();
+    }
+
+    /// An active bookmark that only differs from `cmd` in spacing or option
+    /// order, e.g. `ls /tmp -al` for `ls -la  /tmp`
+    fn similar_bookmark(&self, cmd: &str) -> Option<String> {
+        let key = normalize::option_order_key(&self.normalizer.normalize(cmd));
+        self.bookmarks
+            .iter()
+            .filter(|b| !b.is_trashed() && b.command != cmd)
+            .find(|b| normalize::option_order_key(&self.normalizer.normalize(&b.command)) == key)
+            .map(|b| b.command.clone())
+    }
+
+    /// Warn that `cmd` is already bookmarked in another form, offering to
+    /// replace that bookmark instead of adding a second copy
+    fn confirm_similar_bookmark(&mut self, cmd: String, similar: String) {
+        let shown = prompt_excerpt(&self.shown_command(&similar));
+        self.message = "A similar command is already bookmarked".to_string();
+        self.pending_bookmark = Some((cmd, similar));
+        self.menu = Some(ContextMenu::new(
+            "Already bookmarked",
+            vec![
+                MenuItem::new(
+                    format!("Keep {}", truncate_for_display(&shown)),
+                    "Esc",
+                    Action::Cancel,
+                ),
+                MenuItem::new("Replace it with this command", "", Action::ReplaceBookmark),
+                MenuItem::new("Add both", "", Action::AddBookmarkAnyway),
+            ],
+        ));
+    }
+
+    /// Swap the command of the near-duplicate for the new form, keeping its
+    /// tags, note and group
+    fn replace_pending_bookmark(&mut self) {
+        let Some((cmd, similar)) = self.pending_bookmark.take() else {
+            return;
+        };
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == similar) else {
+            return;
+        };
+        bookmark.command = cmd;
+        self.message = "Bookmark replaced!".to_string();
+        self.save_bookmarks();
+        self.update_query_results();
+    }
+
+    /// Prompt for new tags for the selected bookmark, starting from its current ones
+    fn edit_tags(&mut self) {
+        let Some(bookmark) = self.selected_bookmark() else {
+            self.report_error("Only bookmarks can be tagged");
+            return;
+        };
+        let mut text = bookmark.tags.join(" ");
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let target = Some(bookmark.command.clone());
+        self.open_prompt(PromptKind::BookmarkTags, text, target);
+    }
+
+    fn set_tags(&mut self, cmd: &str, text: &str) {
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) else {
+            return;
+        };
+        bookmark.tags = Bookmark::parse_tags(text);
+        self.message = match bookmark.tag_label() {
+            Some(label) => format!("Tagged {}", label),
+            None => "Tags cleared".to_string(),
+        };
+        self.save_bookmarks();
+        self.update_query_results();
+    }
+
+    /// Prompt for the note of the selected bookmark, starting from the current one
+    fn edit_note(&mut self) {
+        let Some(bookmark) = self.selected_bookmark() else {
+            self.report_error("Only bookmarks can have notes");
+            return;
+        };
+        let text = bookmark.note.clone().unwrap_or_default();
+        let target = Some(bookmark.command.clone());
+        self.open_prompt(PromptKind::BookmarkNote, text, target);
+    }
+
+    fn set_note(&mut self, cmd: &str, text: &str) {
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) else {
+            return;
+        };
+        let text = text.trim();
+        bookmark.note = (!text.is_empty()).then(|| text.to_string());
+        self.message = if bookmark.note.is_some() {
+            "Note saved"
+        } else {
+            "Note removed"
+        }
+        .to_string();
+        self.save_bookmarks();
+        self.update_query_results();
+    }
+
+    /// Prompt for a corrected command for the selected bookmark
+    fn edit_command(&mut self) {
+        let Some(cmd) = self.selected_bookmark().map(|b| b.command.clone()) else {
+            self.report_error("Only bookmarks can be edited");
+            return;
+        };
+        if cmd.contains('\n') {
+            self.report_error("Multi-line commands can't be edited here");
+            return;
+        }
+        self.open_prompt(PromptKind::BookmarkCommand, cmd.clone(), Some(cmd));
+    }
+
+    fn set_command(&mut self, cmd: &str, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            self.report_error("Command can't be empty");
+            return;
+        }
+        if text == cmd {
+            return;
+        }
+        if self.bookmark(text).is_some() {
+            self.report_error("That command is already bookmarked");
+            return;
+        }
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) else {
+            return;
+        };
+
+        bookmark.command = text.to_string();
+        self.message = "Bookmark updated".to_string();
+        self.save_bookmarks();
+        self.update_query_results();
+        if let Some(pos) = self.current_list().iter().position(|c| c == text) {
+            self.selected = pos;
+            self.scroll_to_selection();
+        }
+    }
+
+    /// Prompt for the title of the selected bookmark, starting from the current one
+    fn edit_title(&mut self) {
+        let Some(bookmark) = self.selected_bookmark() else {
+            self.report_error("Only bookmarks can have titles");
+            return;
+        };
+        let text = bookmark.title.clone().unwrap_or_default();
+        let target = Some(bookmark.command.clone());
+        self.open_prompt(PromptKind::BookmarkTitle, text, target);
+    }
+
+    fn set_title(&mut self, cmd: &str, text: &str) {
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) else {
+            return;
+        };
+        let text = text.trim();
+        bookmark.title = (!text.is_empty()).then(|| text.to_string());
+        self.message = match &bookmark.title {
+            Some(title) => format!("Listing as \"{}\"", title),
+            None => "Title removed".to_string(),
+        };
+        self.save_bookmarks();
+        self.update_query_results();
+    }
+
+    /// Swap the selected bookmark with its neighbor in the list and save the new order
+    fn move_bookmark(&mut self, direction: MoveDirection) {
+        let Some(cmd) = self.selected_command().map(str::to_string) else {
+            self.report_error("Select a bookmark to move");
+            return;
+        };
+        // Other orders and ranked matches would put it straight back
+        if self.sort_mode != SortMode::Recency || !self.search_query.is_empty() {
+            self.report_error(
+                "Bookmarks can only be moved when sorted by recency, without a search",
+            );
+            return;
+        }
+
+        let neighbor = match direction {
+            MoveDirection::Up => self.selected.checked_sub(1),
+            MoveDirection::Down => Some(self.selected + 1),
+        };
+        // Pinned bookmarks only trade places among themselves
+        let pinned = |command: &str| self.bookmark(command).is_some_and(|b| b.pinned);
+        let Some(neighbor) = neighbor
+            .filter(|&i| self.group_at(i).is_none())
+            .and_then(|i| self.current_list().get(i).cloned())
+            .filter(|neighbor| pinned(neighbor) == pinned(&cmd))
+        else {
+            self.report_error(match direction {
+                MoveDirection::Up => "Already at the top",
+                MoveDirection::Down => "Already at the bottom",
+            });
+            return;
+        };
+
+        let position = |command: &str| self.bookmarks.iter().position(|b| b.command == command);
+        let (Some(from), Some(to)) = (position(&cmd), position(&neighbor)) else {
+            return;
+        };
+        self.bookmarks.swap(from, to);
+        self.save_bookmarks();
+        self.update_query_results();
+        if let Some(pos) = self.current_list().iter().position(|c| *c == cmd) {
+            self.selected = pos;
+            self.scroll_to_selection();
+        }
+    }
+
+    fn toggle_pin(&mut self) {
+        let Some(cmd) = self.selected_command().map(str::to_string) else {
+            self.report_error("Select a bookmark to pin");
+            return;
+        };
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) else {
+            return;
+        };
+        bookmark.pinned = !bookmark.pinned;
+        self.message = if bookmark.pinned {
+            "Pinned to the top"
         } else {
-            self.bookmarks.push(cmd);
-            self.message = "Bookmark added!".to_string();
+            "Unpinned"
+        }
+        .to_string();
+        self.save_bookmarks();
+        self.update_query_results();
+        if let Some(pos) = self.current_list().iter().position(|c| *c == cmd) {
+            self.selected = pos;
+            self.scroll_to_selection();
+        }
+    }
+
+    /// Prompt for the group of the selected bookmark, starting from the current one
+    fn edit_group(&mut self) {
+        let Some(bookmark) = self.selected_bookmark() else {
+            self.report_error("Only bookmarks can be grouped");
+            return;
+        };
+        let text = bookmark.group.clone().unwrap_or_default();
+        let target = Some(bookmark.command.clone());
+        self.open_prompt(PromptKind::BookmarkGroup, text, target);
+    }
+
+    fn set_group(&mut self, cmd: &str, text: &str) {
+        let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) else {
+            return;
+        };
+        let text = text.trim();
+        bookmark.group = (!text.is_empty()).then(|| text.to_string());
+        self.message = match &bookmark.group {
+            Some(group) => format!("Moved to {}", group),
+            None => "Removed from its group".to_string(),
+        };
+        self.save_bookmarks();
+        self.update_query_results();
+        // Follow the bookmark to its new section
+        if let Some(pos) = self.current_list().iter().position(|c| c == cmd) {
+            self.selected = pos;
+            self.scroll_to_selection();
         }
+    }
+
+    fn record_bookmark_use(&mut self, cmd: &str) {
+        let Some(bookmark) = self
+            .bookmarks
+            .iter_mut()
+            .find(|b| b.command == cmd && !b.is_trashed())
+        else {
+            return;
+        };
+        bookmark.uses += 1;
+        bookmark.last_used = Some(entry::now());
         self.save_bookmarks();
+        self.update_query_results();
 
-        if self.bookmark_mode {
-            self.queried_history = self.bookmarks.clone();
-            self.selected = self.selected.min(self.queried_history.len().saturating_sub(1));
+        // The recent section moved; stay on the copied bookmark
+        if self
+            .current_list()
+            .get(self.selected)
+            .is_none_or(|c| c != cmd)
+        {
+            if let Some(pos) = self.current_list().iter().position(|c| c == cmd) {
+                self.selected = pos;
+                self.scroll_to_selection();
+            }
         }
     }
 
+    /// Move the selected bookmark to the trash, or delete it for good when
+    /// it already is there
     pub fn delete_bookmark(&mut self) {
-        if !self.bookmarks.is_empty() {
-            self.bookmarks.remove(self.selected);
-            self.selected = self.selected.min(self.bookmarks.len().saturating_sub(1));
-            self.save_bookmarks();
+        let Some(cmd) = self.selected_command().map(str::to_string) else {
+            return;
+        };
+
+        if self.bookmark(&cmd).is_some_and(Bookmark::is_trashed)
+            || self.config.bookmark_trash_days == 0
+        {
+            self.bookmarks.retain(|b| b.command != cmd);
             self.message = "Bookmark deleted!".to_string();
+        } else {
+            self.trash_bookmark(&cmd);
+            self.message = "Bookmark moved to the trash (u to restore)".to_string();
+        }
+        self.save_bookmarks();
+        self.update_query_results();
+    }
+
+    /// Keep `cmd` in the trash, or drop it when the trash is turned off
+    fn trash_bookmark(&mut self, cmd: &str) {
+        if self.config.bookmark_trash_days == 0 {
+            self.bookmarks.retain(|b| b.command != cmd);
+        } else if let Some(bookmark) = self.bookmarks.iter_mut().find(|b| b.command == cmd) {
+            bookmark.trashed_at = Some(entry::now());
+        }
+    }
+
+    fn restore_bookmark(&mut self) {
+        let Some(cmd) = self.selected_command().map(str::to_string) else {
+            self.report_error("Select a bookmark in the trash to restore");
+            return;
+        };
+        let Some(bookmark) = self
+            .bookmarks
+            .iter_mut()
+            .find(|b| b.command == cmd && b.is_trashed())
+        else {
+            self.report_error("Only bookmarks in the trash can be restored");
+            return;
+        };
+
+        bookmark.trashed_at = None;
+        self.message = "Bookmark restored!".to_string();
+        self.save_bookmarks();
+        self.update_query_results();
+        if let Some(pos) = self.current_list().iter().position(|c| *c == cmd) {
+            self.selected = pos;
+            self.scroll_to_selection();
+        }
+    }
+
+    /// Counts for the position indicator: (selected position, matching entries, loaded entries)
+    pub fn entry_counts(&self) -> (usize, usize, usize) {
+        let matches = match self.mode {
+            // Neither section headers, the trash nor repeated recent ones count
+            Mode::Bookmarks => self
+                .current_list()
+                .iter()
+                .enumerate()
+                .filter(|(i, cmd)| self.group_at(*i).is_none() && self.is_bookmarked(cmd))
+                .map(|(_, cmd)| cmd)
+                .collect::<HashSet<_>>()
+                .len(),
+            _ => self.current_list().len(),
+        };
+        let loaded = match self.mode {
+            Mode::History => self.history.len(),
+            Mode::Bookmarks => self.bookmarks.iter().filter(|b| !b.is_trashed()).count(),
+            Mode::Merge => self.current_list().len(),
+        };
+        let position = if matches == 0 { 0 } else { self.selected + 1 };
+        (position, matches, loaded)
+    }
+
+    /// Terminal title describing the current mode and filter
+    pub fn terminal_title(&self) -> String {
+        let mut title = format!("term-kit — {}", view_for(self.mode).label());
+        if !self.search_query.is_empty() {
+            title.push_str(&format!(" (filter: {})", self.search_query));
         }
+        title
     }
 
     pub fn get_help_text(&self) -> &'static str {
@@ -436,3 +3853,174 @@ impl App {
         self.size.set(size);
     }
 }
+
+/// `cmd` cut to MAX_PROMPT_CHARS for quoting in a confirmation prompt
+fn prompt_excerpt(cmd: &str) -> String {
+    match cmd.char_indices().nth(MAX_PROMPT_CHARS) {
+        Some((cut, _)) => format!("{}…", &cmd[..cut]),
+        None => cmd.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+
+    fn records(
+        parse: fn(&[HistoryLine]) -> Vec<HistoryRecord>,
+        content: &str,
+    ) -> Vec<(Range<usize>, HistoryEntry)> {
+        parse(&split_lines(content.as_bytes()))
+            .into_iter()
+            .map(|record| (record.lines, record.entry))
+            .collect()
+    }
+
+    fn commands(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.command.as_str()).collect()
+    }
+
+    #[test]
+    fn heredoc_delimiters() {
+        let delimiters = ShellType::heredoc_delimiters;
+        assert_eq!(delimiters("cat <<EOF"), [("EOF".to_string(), false)]);
+        assert_eq!(delimiters("cat <<-EOF > out"), [("EOF".to_string(), true)]);
+        assert_eq!(
+            delimiters("cat <<'END' | grep x"),
+            [("END".to_string(), false)]
+        );
+        assert_eq!(delimiters("cat <<- \"END\""), [("END".to_string(), true)]);
+        assert_eq!(delimiters("cat <<\\EOF"), [("EOF".to_string(), false)]);
+        assert_eq!(
+            delimiters("paste <<A <<-B"),
+            [("A".to_string(), false), ("B".to_string(), true)]
+        );
+        assert!(delimiters("grep x <<< \"$text\"").is_empty());
+        assert!(delimiters("echo $((1 << 2))").is_empty());
+        assert!(delimiters("cat <<'unclosed").is_empty());
+    }
+
+    #[test]
+    fn bash_heredocs() {
+        let parsed = records(
+            ShellType::bash_records,
+            "cat <<EOF\nhello\nEOF\ncat <<-'END'\n\tindented\n\tEND\nls\n",
+        );
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].0, 0..3);
+        assert_eq!(parsed[0].1.command, "cat <<EOF\nhello\nEOF");
+        assert_eq!(parsed[1].0, 3..6);
+        assert_eq!(parsed[1].1.command, "cat <<-'END'\n\tindented\n\tEND");
+        assert_eq!(parsed[2].0, 6..7);
+        assert_eq!(parsed[2].1.command, "ls");
+    }
+
+    #[test]
+    fn bash_tab_stripping_only_with_dash() {
+        // Without `<<-` an indented delimiter is part of the body
+        let parsed = records(ShellType::bash_records, "cat <<EOF\n\tEOF\nEOF\nls\n");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].1.command, "cat <<EOF\n\tEOF\nEOF");
+    }
+
+    #[test]
+    fn bash_backslash_continuation() {
+        let parsed = records(ShellType::bash_records, "docker run \\\n  --rm image\nls\n");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].0, 0..2);
+        assert_eq!(parsed[0].1.command, "docker run \\\n  --rm image");
+        assert_eq!(parsed[1].1.command, "ls");
+    }
+
+    #[test]
+    fn bash_timestamps_between_records() {
+        let parsed = records(
+            ShellType::bash_records,
+            "#1700000000\nfor f in *; do\n  echo $f\ndone\n#1700000005\nls\n",
+        );
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].0, 0..4);
+        assert_eq!(parsed[0].1.command, "for f in *; do\n  echo $f\ndone");
+        assert_eq!(parsed[0].1.timestamp, Some(1700000000));
+        assert_eq!(parsed[1].0, 4..6);
+        assert_eq!(parsed[1].1.command, "ls");
+        assert_eq!(parsed[1].1.timestamp, Some(1700000005));
+    }
+
+    #[test]
+    fn bash_comment_is_not_a_timestamp() {
+        let parsed = records(ShellType::bash_records, "#todo\nls\n");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].1.command, "#todo");
+        assert_eq!(parsed[0].1.timestamp, None);
+    }
+
+    #[test]
+    fn zsh_extended_and_continued() {
+        let parsed = records(
+            ShellType::zsh_records,
+            ": 1700000000:5;echo one \\\\\ntwo\n: 1700000009:0;ls\nplain\n",
+        );
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].0, 0..2);
+        assert_eq!(parsed[0].1.command, "echo one \\\ntwo");
+        assert_eq!(parsed[0].1.timestamp, Some(1700000000));
+        assert_eq!(parsed[0].1.duration, Some(5));
+        assert_eq!(parsed[1].0, 2..3);
+        assert_eq!(parsed[1].1.command, "ls");
+        assert_eq!(parsed[2].1.command, "plain");
+        assert_eq!(parsed[2].1.timestamp, None);
+    }
+
+    #[test]
+    fn zsh_trailing_backslash_at_end_of_file() {
+        let parsed = records(ShellType::zsh_records, ": 1700000000:0;echo \\");
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].0, 0..1);
+        assert_eq!(parsed[0].1.command, "echo ");
+    }
+
+    #[test]
+    fn ksh93_lines() {
+        let mut content = vec![0x81, 0x01];
+        content.extend(b"ls\n\ncd /tmp\n");
+        assert_eq!(commands(&ShellType::parse_ksh(content)), ["cd /tmp", "ls"]);
+    }
+
+    #[test]
+    fn mksh_records() {
+        // Line numbers with NUL bytes in them must not end the record early
+        let mut content = vec![0xab, 0xcd];
+        content.extend([0xff, 0, 0, 0, 1]);
+        content.extend(b"ls\0");
+        content.extend([0xff, 0, 0, 1, 0]);
+        content.extend(b"cd /tmp\0");
+        assert_eq!(commands(&ShellType::parse_ksh(content)), ["cd /tmp", "ls"]);
+    }
+
+    #[test]
+    fn tcsh_timestamps() {
+        let entries =
+            ShellType::parse_tcsh(b"#+1700000000\nls\npwd\n#+1700000003\nmake\n".to_vec());
+        assert_eq!(commands(&entries), ["make", "pwd", "ls"]);
+        assert_eq!(entries[0].timestamp, Some(1700000003));
+        assert_eq!(entries[1].timestamp, None);
+        assert_eq!(entries[2].timestamp, Some(1700000000));
+    }
+
+    #[test]
+    fn xonsh_records() {
+        let content = concat!(
+            "{\"inp\": \"ls\\n\", \"ts\": [1700000000.2, 1700000002.9]}\n",
+            "not json\n",
+            "{\"inp\": \"\\n\", \"ts\": [1700000003.0, 1700000003.0]}\n",
+            "{\"inp\": \"make\\n\"}\n",
+        );
+        let entries = ShellType::parse_xonsh(content.as_bytes().to_vec());
+        assert_eq!(commands(&entries), ["make", "ls"]);
+        assert_eq!(entries[0].timestamp, None);
+        assert_eq!(entries[1].timestamp, Some(1700000000));
+        assert_eq!(entries[1].duration, Some(2));
+    }
+}
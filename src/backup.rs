@@ -0,0 +1,183 @@
+// backup.rs
+//! `term-kit backup`: every file term-kit keeps, bundled into one JSON file for
+//! moving to another machine or recovering after a reinstall.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::app::App;
+use crate::bookmark;
+use crate::cli::BackupArgs;
+use crate::config::Config;
+
+/// Bumped when the bundle layout changes incompatibly
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    version: u32,
+    /// Seconds since the Unix epoch
+    created: u64,
+    /// File contents keyed by the names in `files()`, and the bookmarks as a
+    /// JSON bookmark file whatever format they are kept in
+    files: BTreeMap<String, String>,
+}
+
+const BOOKMARKS: &str = "bookmarks";
+
+/// Every file that goes into a bundle besides the bookmarks. The config file
+/// also holds the ignore lists.
+fn files() -> Vec<(&'static str, PathBuf)> {
+    let mut files = vec![
+        ("presets", App::get_presets_path()),
+        ("searches", App::get_query_history_path()),
+    ];
+    if let Some(path) = Config::path() {
+        files.insert(0, ("config", path));
+    }
+    files
+}
+
+pub fn run(args: &BackupArgs) -> Result<()> {
+    match args {
+        BackupArgs::Create { path } => create(path),
+        BackupArgs::Restore { path, force } => restore(path, *force),
+    }
+}
+
+fn create(path: &Path) -> Result<()> {
+    let mut bundle = Bundle {
+        version: FORMAT_VERSION,
+        created: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs()),
+        files: BTreeMap::new(),
+    };
+    for (name, source) in files() {
+        match fs::read_to_string(&source) {
+            Ok(content) => {
+                bundle.files.insert(name.to_string(), content);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", source.display()))
+            }
+        }
+    }
+    let store = Config::load().bookmark_format.store();
+    if store.location().exists() {
+        let (_, bookmarks) = store
+            .load()
+            .with_context(|| format!("Failed to read {}", store.location().display()))?;
+        bundle
+            .files
+            .insert(BOOKMARKS.to_string(), bookmark::write_file(&bookmarks)?);
+    }
+
+    fs::write(path, serde_json::to_string_pretty(&bundle)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!(
+        "Backed up {} to {}",
+        names(bundle.files.keys().map(String::as_str)),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Write the bundled files back. Files that exist and differ are only
+/// replaced with `force`, so a stale bundle can't silently undo recent changes.
+fn restore(path: &Path, force: bool) -> Result<()> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let bundle: Bundle = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a term-kit backup", path.display()))?;
+    if bundle.version > FORMAT_VERSION {
+        bail!(
+            "{} was made by a newer term-kit (format {}); please upgrade first",
+            path.display(),
+            bundle.version
+        );
+    }
+
+    let targets = files();
+    for name in bundle.files.keys() {
+        if name != BOOKMARKS && !targets.iter().any(|(known, _)| known == name) {
+            eprintln!("Skipping unknown entry '{}' in the backup", name);
+        }
+    }
+
+    let restored: Vec<_> = targets
+        .iter()
+        .filter_map(|(name, target)| Some((*name, target, bundle.files.get(*name)?)))
+        .collect();
+    let mut conflicts: Vec<_> = restored
+        .iter()
+        .filter(|(_, target, content)| {
+            fs::read_to_string(target).is_ok_and(|current| current != **content)
+        })
+        .map(|(_, target, _)| target.display().to_string())
+        .collect();
+
+    let store = Config::load().bookmark_format.store();
+    let bookmarks = match bundle.files.get(BOOKMARKS) {
+        Some(content) => {
+            let (_, bookmarks) = bookmark::parse_file(content)
+                .with_context(|| format!("the bookmarks in {} are damaged", path.display()))?;
+            Some(bookmarks)
+        }
+        None => None,
+    };
+    if let Some(bookmarks) = &bookmarks {
+        if store.location().exists()
+            && store
+                .load()
+                .map_or(true, |(_, current)| current != *bookmarks)
+        {
+            conflicts.push(store.location().display().to_string());
+        }
+    }
+    if !conflicts.is_empty() && !force {
+        bail!(
+            "these files differ from the backup:\n  {}\nrun `term-kit backup restore --force {}` to overwrite them",
+            conflicts.join("\n  "),
+            path.display()
+        );
+    }
+
+    for (_, target, content) in &restored {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(target, content)
+            .with_context(|| format!("Failed to write {}", target.display()))?;
+    }
+    if let Some(bookmarks) = &bookmarks {
+        store
+            .save(bookmarks)
+            .with_context(|| format!("Failed to write {}", store.location().display()))?;
+    }
+    println!(
+        "Restored {} from {}",
+        names(
+            restored
+                .iter()
+                .map(|(name, _, _)| *name)
+                .chain(bookmarks.is_some().then_some(BOOKMARKS))
+        ),
+        path.display()
+    );
+    Ok(())
+}
+
+fn names<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let names: Vec<_> = names.collect();
+    if names.is_empty() {
+        "nothing".to_string()
+    } else {
+        names.join(", ")
+    }
+}
@@ -0,0 +1,191 @@
+// bookmark.rs
+//! A saved command and what the user attached to it, and the file they are
+//! kept in.
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+/// Layout of the bookmark file, bumped when it changes incompatibly; version
+/// 1 was a bare list of bookmarks
+pub const FILE_VERSION: u32 = 2;
+
+#[derive(Deserialize)]
+struct BookmarkFile {
+    version: u32,
+    bookmarks: Vec<Bookmark>,
+}
+
+#[derive(Serialize)]
+struct BookmarkFileRef<'a> {
+    version: u32,
+    bookmarks: &'a [Bookmark],
+}
+
+/// Bookmarks in a bookmark file of any version, with the version it was
+/// written in
+pub fn parse_file(content: &str) -> serde_json::Result<(u32, Vec<Bookmark>)> {
+    parse_value(serde_json::from_str(content)?)
+}
+
+/// `parse_file` for a document already read into a value
+pub fn parse_value(value: serde_json::Value) -> serde_json::Result<(u32, Vec<Bookmark>)> {
+    if value.is_array() {
+        return Ok((1, serde_json::from_value(value)?));
+    }
+    let file: BookmarkFile = serde_json::from_value(value)?;
+    Ok((file.version, file.bookmarks))
+}
+
+/// A bookmark file of the current version
+pub fn write_file(bookmarks: &[Bookmark]) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(&BookmarkFileRef {
+        version: FILE_VERSION,
+        bookmarks,
+    })
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredBookmark", into = "StoredBookmark")]
+pub struct Bookmark {
+    pub command: String,
+    /// Short name listed instead of the command, e.g. "nuke docker"
+    pub title: Option<String>,
+    /// Lowercase labels without the `#`, e.g. "deploy"
+    pub tags: Vec<String>,
+    /// Free text shown under the list, e.g. "run before deploy, needs VPN"
+    pub note: Option<String>,
+    /// Section of the bookmark list, e.g. "homelab"; ungrouped ones come first
+    pub group: Option<String>,
+    /// Listed above every section, whatever the sort order
+    pub pinned: bool,
+    /// When it was deleted; trashed bookmarks are only listed in the trash
+    pub trashed_at: Option<u64>,
+    /// Times it was copied from bookmark mode
+    pub uses: u32,
+    pub last_used: Option<u64>,
+    /// Fields written by newer versions, kept so saving doesn't drop them
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Bookmark files used to be a list of bare commands. Those still load, and
+/// bookmarks without extras keep being written that way, so the file only
+/// changes shape for the entries that need it.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StoredBookmark {
+    Command(String),
+    Full {
+        command: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        pinned: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        trashed_at: Option<u64>,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        uses: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_used: Option<u64>,
+        #[serde(flatten)]
+        extra: BTreeMap<String, serde_json::Value>,
+    },
+}
+
+fn is_zero(count: &u32) -> bool {
+    *count == 0
+}
+
+impl From<StoredBookmark> for Bookmark {
+    fn from(stored: StoredBookmark) -> Self {
+        match stored {
+            StoredBookmark::Command(command) => Bookmark::new(command),
+            StoredBookmark::Full {
+                command,
+                title,
+                tags,
+                note,
+                group,
+                pinned,
+                trashed_at,
+                uses,
+                last_used,
+                extra,
+            } => Bookmark {
+                command,
+                title,
+                tags,
+                note,
+                group,
+                pinned,
+                trashed_at,
+                uses,
+                last_used,
+                extra,
+            },
+        }
+    }
+}
+
+impl From<Bookmark> for StoredBookmark {
+    fn from(bookmark: Bookmark) -> Self {
+        if bookmark == Bookmark::new(bookmark.command.as_str()) {
+            StoredBookmark::Command(bookmark.command)
+        } else {
+            StoredBookmark::Full {
+                command: bookmark.command,
+                title: bookmark.title,
+                tags: bookmark.tags,
+                note: bookmark.note,
+                group: bookmark.group,
+                pinned: bookmark.pinned,
+                trashed_at: bookmark.trashed_at,
+                uses: bookmark.uses,
+                last_used: bookmark.last_used,
+                extra: bookmark.extra,
+            }
+        }
+    }
+}
+
+impl Bookmark {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_trashed(&self) -> bool {
+        self.trashed_at.is_some()
+    }
+
+    /// Tags as typed in the tag prompt: separated by spaces or commas, with or
+    /// without a leading `#`
+    pub fn parse_tags(text: &str) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in text.split(|c: char| c.is_whitespace() || c == ',') {
+            let tag = tag.trim_start_matches('#').to_lowercase();
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
+    /// Tags as shown next to the command, e.g. "#deploy #k8s"
+    pub fn tag_label(&self) -> Option<String> {
+        (!self.tags.is_empty()).then(|| {
+            self.tags
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+    }
+}
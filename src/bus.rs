@@ -0,0 +1,61 @@
+// bus.rs
+//! Channel for work finished off the UI thread (history loading, ...).
+//!
+//! Producers send as often as they like; the event loop drains the bus once per
+//! frame and updates that supersede each other are merged first, so bursts of
+//! background work cause a single re-filter and redraw instead of a storm.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use crate::entry::HistoryEntry;
+use crate::profile::Phase;
+
+#[derive(Debug)]
+pub enum Update {
+    /// Complete parse of the active history source; a newer one replaces older ones
+    HistoryLoaded {
+        /// Which load this is, see `App::start_history_load`
+        generation: u64,
+        entries: Vec<HistoryEntry>,
+        error: Option<String>,
+        /// How long reading and parsing took, for `--profile-startup`
+        timings: Vec<Phase>,
+    },
+    /// A file behind the history of load `generation` changed on disk
+    HistoryChanged { generation: u64 },
+}
+
+pub struct UpdateBus {
+    sender: Sender<Update>,
+    receiver: Receiver<Update>,
+}
+
+impl UpdateBus {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver }
+    }
+
+    /// Handle for a background producer
+    pub fn sender(&self) -> Sender<Update> {
+        self.sender.clone()
+    }
+
+    /// Everything sent since the last call, coalesced
+    pub fn drain(&self) -> Vec<Update> {
+        let mut updates: Vec<Update> = Vec::new();
+
+        for update in self.receiver.try_iter() {
+            match update {
+                Update::HistoryLoaded { .. } => {
+                    updates.retain(|u| !matches!(u, Update::HistoryLoaded { .. }));
+                    updates.push(update);
+                }
+                Update::HistoryChanged { .. } => {
+                    updates.retain(|u| !matches!(u, Update::HistoryChanged { .. }));
+                    updates.push(update);
+                }
+            }
+        }
+        updates
+    }
+}
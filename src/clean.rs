@@ -0,0 +1,83 @@
+// clean.rs
+//! `term-kit clean`: delete every history entry matching a pattern, e.g. a
+//! leaked token or thousands of `ls` lines, after previewing what goes.
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use crate::app::ShellType;
+use crate::cli::CleanArgs;
+use crate::config::Config;
+
+pub fn run(args: &CleanArgs) -> Result<()> {
+    let config = Config::load();
+    let shell = args.shell.clone().unwrap_or_else(ShellType::detect);
+    let provider = config.history_source.provider(&shell, &config);
+    let location = provider.location();
+
+    let pattern = match &args.pattern {
+        Some(pattern) => pattern.clone(),
+        None => prompt("Regex matching the entries to delete: ")?,
+    };
+    if pattern.is_empty() {
+        bail!("no pattern given, nothing deleted");
+    }
+    let pattern = Regex::new(&pattern).map_err(|e| anyhow!("invalid pattern: {}", e))?;
+
+    let entries = provider.load(&mut Vec::new())?;
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for entry in entries
+        .iter()
+        .filter(|entry| pattern.is_match(&entry.command))
+    {
+        *counts.entry(entry.command.as_str()).or_default() += 1;
+    }
+    if counts.is_empty() {
+        println!("No entries in {} match.", location.display());
+        return Ok(());
+    }
+
+    // Most repeated first, so the bulk of what goes is on top
+    let mut matches: Vec<(&str, usize)> = counts.into_iter().collect();
+    matches.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let total: usize = matches.iter().map(|(_, count)| count).sum();
+    println!("{} entries in {} match:", total, location.display());
+    for (cmd, count) in &matches {
+        println!("{:>7}  {}", format!("{}×", count), cmd.replace('\n', " ⏎ "));
+    }
+
+    if !args.yes {
+        let answer = prompt(&format!(
+            "Delete these {} entries? The file is backed up first. [y/N] ",
+            total
+        ))?;
+        if !matches!(answer.to_lowercase().as_str(), "y" | "yes") {
+            println!("Nothing deleted.");
+            return Ok(());
+        }
+    }
+
+    let removal = provider.remove_entries(&|cmd| pattern.is_match(cmd))?;
+    if removal.backups.is_empty() {
+        println!("Nothing deleted, the matching entries are gone already.");
+    } else {
+        println!(
+            "Deleted {} entries, backup in {}",
+            removal.removed,
+            removal.backup_list()
+        );
+        // Purging a leaked secret isn't done while the backup still has it
+        println!("The backup still holds them; delete it once the history looks right.");
+    }
+    Ok(())
+}
+
+/// Ask on stderr so a redirected preview stays clean, and read one line
+fn prompt(question: &str) -> Result<String> {
+    eprint!("{}", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
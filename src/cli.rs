@@ -0,0 +1,366 @@
+// cli.rs
+use anyhow::{anyhow, bail, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+use crate::app::ShellType;
+use crate::sources::{HistoryFormat, HistorySource};
+
+const USAGE: &str = "\
+Usage: term-kit [COMMAND]
+
+Commands:
+  (none) [--profile-startup] [--source shell|atuin|mcfly|all|file|command|remote]
+         [--shell <name>]
+         [--history-file <file> [--history-format plain|bash|zsh|fish]]
+         [--history-command <command>] [--remote <user@host>]
+                             Browse shell history in the TUI; with
+                             --profile-startup, print load timings on exit;
+                             --source overrides `history_source` in the config;
+                             --history-file browses any file, e.g. one synced
+                             from another machine, and --history-command the
+                             lines a command prints (r reloads it);
+                             --remote fetches the login shell's history from
+                             an SSH host (key-based login needed);
+                             --shell (or TERM_KIT_SHELL) picks bash, zsh, fish,
+                             nu, pwsh, cmd, ksh, tcsh, xonsh or elvish instead
+                             of detecting it
+  report [--since <age>] [--format markdown|text]
+                             Print a summary of recent shell usage
+  clean [--yes] [--shell <name>] [<regex>]
+                             Delete every history entry matching <regex>
+                             (asked for when left out) from the history
+                             file, after listing them and asking; the file
+                             is backed up first. --shell (or TERM_KIT_SHELL)
+                             cleans another shell's history, as for browsing
+  backup create <file>       Bundle config, bookmarks, presets and recent
+                             searches into one file
+  backup restore [--force] <file>
+                             Put a bundle's files back; --force overwrites
+                             files that differ
+  export aliases [--shell <name>] [<file>]
+                             Write the bookmarks as aliases (functions for
+                             multi-line ones) for bash, zsh, ksh, fish or
+                             pwsh, named after their titles; the file
+                             defaults to term-kit-aliases.sh (.fish, .ps1)
+  export markdown [--by group|tag] [<file>]
+                             Write the bookmarks as a Markdown cheatsheet,
+                             one section per group (or tag), with secrets
+                             redacted as in reports; the file defaults to
+                             term-kit-bookmarks.md
+  import <file>              Add the bookmarks of another bookmark file, a
+                             backup or a list of commands (one per line),
+                             skipping the ones already bookmarked
+";
+
+/// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Browse(BrowseArgs),
+    Report(ReportArgs),
+    Clean(CleanArgs),
+    Backup(BackupArgs),
+    Import(ImportArgs),
+    Export(ExportArgs),
+    Help,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BrowseArgs {
+    /// Print how long each startup step took after leaving the TUI
+    pub profile_startup: bool,
+    /// Read history from here instead of the configured source
+    pub source: Option<HistorySource>,
+    /// Load this shell's history instead of detecting the shell
+    pub shell: Option<ShellType>,
+    /// Browse this file instead of any configured source
+    pub history_file: Option<PathBuf>,
+    pub history_format: Option<HistoryFormat>,
+    /// Browse what this command prints
+    pub history_command: Option<String>,
+    /// Browse this SSH host's history
+    pub remote: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportArgs {
+    pub since: Option<Duration>,
+    pub format: ReportFormat,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanArgs {
+    /// Regex the deleted entries match; asked for when missing
+    pub pattern: Option<String>,
+    /// Delete without asking
+    pub yes: bool,
+    /// Clean this shell's history instead of the detected shell's
+    pub shell: Option<ShellType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackupArgs {
+    Create { path: PathBuf },
+    Restore { path: PathBuf, force: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportArgs {
+    /// Shell aliases, for the detected shell unless given
+    Aliases {
+        shell: Option<ShellType>,
+        path: Option<PathBuf>,
+    },
+    /// A cheatsheet for pasting into a wiki
+    Markdown {
+        sections: MarkdownSections,
+        path: Option<PathBuf>,
+    },
+}
+
+/// What the sections of a Markdown export are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkdownSections {
+    Group,
+    /// A bookmark with several tags is listed under each
+    Tag,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportArgs {
+    pub path: PathBuf,
+}
+
+pub fn usage() -> &'static str {
+    USAGE
+}
+
+/// Parse command-line arguments, excluding the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command> {
+    let mut args = args.into_iter();
+
+    match args.next().as_deref() {
+        None => Ok(Command::Browse(BrowseArgs::default())),
+        Some("-h" | "--help" | "help") => Ok(Command::Help),
+        Some(flag) if flag.starts_with("--") => {
+            parse_browse_args(std::iter::once(flag.to_string()).chain(args)).map(Command::Browse)
+        }
+        Some("report") => parse_report_args(args).map(Command::Report),
+        Some("clean") => parse_clean_args(args).map(Command::Clean),
+        Some("backup") => parse_backup_args(args).map(Command::Backup),
+        Some("import") => parse_import_args(args).map(Command::Import),
+        Some("export") => parse_export_args(args).map(Command::Export),
+        Some(other) => bail!("unknown command '{}'\n\n{}", other, USAGE),
+    }
+}
+
+fn parse_browse_args(mut args: impl Iterator<Item = String>) -> Result<BrowseArgs> {
+    let mut browse = BrowseArgs::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--profile-startup" => browse.profile_startup = true,
+            "--source" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--source needs a value"))?;
+                let source = HistorySource::from_name(&name).ok_or_else(|| {
+                    anyhow!(
+                        "unknown history source '{}' \
+                         (use shell, atuin, mcfly, all, file, command or remote)",
+                        name
+                    )
+                })?;
+                browse.source = Some(source);
+            }
+            "--shell" => browse.shell = Some(parse_shell(args.next())?),
+            "--history-file" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--history-file needs a path"))?;
+                browse.history_file = Some(PathBuf::from(path));
+            }
+            "--history-command" => {
+                let command = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--history-command needs a command"))?;
+                browse.history_command = Some(command);
+            }
+            "--remote" => {
+                let host = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--remote needs a host, e.g. user@server"))?;
+                browse.remote = Some(host);
+            }
+            "--history-format" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--history-format needs a value"))?;
+                let format = HistoryFormat::from_name(&name).ok_or_else(|| {
+                    anyhow!(
+                        "unknown history format '{}' (use plain, bash, zsh or fish)",
+                        name
+                    )
+                })?;
+                browse.history_format = Some(format);
+            }
+            other => bail!("unknown option '{}'\n\n{}", other, USAGE),
+        }
+    }
+    if browse.history_format.is_some() && browse.history_file.is_none() {
+        bail!("--history-format only applies together with --history-file");
+    }
+    Ok(browse)
+}
+
+fn parse_report_args(mut args: impl Iterator<Item = String>) -> Result<ReportArgs> {
+    let mut report = ReportArgs {
+        since: None,
+        format: ReportFormat::Markdown,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+        match arg.as_str() {
+            "--since" => report.since = Some(parse_age(&value()?)?),
+            "--format" => {
+                report.format = match value()?.as_str() {
+                    "markdown" | "md" => ReportFormat::Markdown,
+                    "text" | "plain" => ReportFormat::Text,
+                    other => bail!("unknown report format '{}'", other),
+                }
+            }
+            other => bail!("unknown report option '{}'", other),
+        }
+    }
+    Ok(report)
+}
+
+fn parse_clean_args(mut args: impl Iterator<Item = String>) -> Result<CleanArgs> {
+    let mut clean = CleanArgs::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--yes" | "-y" => clean.yes = true,
+            "--shell" => clean.shell = Some(parse_shell(args.next())?),
+            other if other.starts_with("--") => bail!("unknown clean option '{}'", other),
+            _ if clean.pattern.is_some() => {
+                bail!("clean takes a single pattern; combine them with |")
+            }
+            _ => clean.pattern = Some(arg),
+        }
+    }
+    Ok(clean)
+}
+
+fn parse_backup_args(mut args: impl Iterator<Item = String>) -> Result<BackupArgs> {
+    let restore = match args.next().as_deref() {
+        Some("create") => false,
+        Some("restore") => true,
+        Some(other) => bail!("unknown backup action '{}' (use create or restore)", other),
+        None => bail!("backup needs an action (create or restore)"),
+    };
+
+    let mut force = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "--force" if restore => force = true,
+            other if other.starts_with("--") => bail!("unknown backup option '{}'", other),
+            _ if path.is_some() => bail!("backup takes a single file"),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("backup needs a file\n\n{}", USAGE))?;
+
+    Ok(if restore {
+        BackupArgs::Restore { path, force }
+    } else {
+        BackupArgs::Create { path }
+    })
+}
+
+fn parse_import_args(args: impl Iterator<Item = String>) -> Result<ImportArgs> {
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            other if other.starts_with("--") => bail!("unknown import option '{}'", other),
+            _ if path.is_some() => bail!("import takes a single file"),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("import needs a file\n\n{}", USAGE))?;
+    Ok(ImportArgs { path })
+}
+
+fn parse_export_args(mut args: impl Iterator<Item = String>) -> Result<ExportArgs> {
+    let aliases = match args.next().as_deref() {
+        Some("aliases") => true,
+        Some("markdown" | "md") => false,
+        Some(other) => bail!(
+            "unknown export format '{}' (use aliases or markdown)",
+            other
+        ),
+        None => bail!("export needs a format (aliases or markdown)"),
+    };
+
+    let mut shell = None;
+    let mut sections = MarkdownSections::Group;
+    let mut path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--by" if !aliases => {
+                sections = match args.next().as_deref() {
+                    Some("group") => MarkdownSections::Group,
+                    Some("tag") => MarkdownSections::Tag,
+                    Some(other) => bail!("unknown section kind '{}' (use group or tag)", other),
+                    None => bail!("--by needs a value (group or tag)"),
+                };
+            }
+            "--shell" if aliases => shell = Some(parse_shell(args.next())?),
+            other if other.starts_with("--") => bail!("unknown export option '{}'", other),
+            _ if path.is_some() => bail!("export takes a single file"),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+    Ok(if aliases {
+        ExportArgs::Aliases { shell, path }
+    } else {
+        ExportArgs::Markdown { sections, path }
+    })
+}
+
+/// The shell named after `--shell`
+fn parse_shell(name: Option<String>) -> Result<ShellType> {
+    let name = name.ok_or_else(|| anyhow!("--shell needs a value"))?;
+    ShellType::from_name(&name).ok_or_else(|| {
+        anyhow!(
+            "unknown shell '{}' (use bash, zsh, fish, nu, pwsh, cmd, ksh, tcsh, xonsh or elvish)",
+            name
+        )
+    })
+}
+
+/// Parse ages like `30m`, `12h`, `7d` or `2w`
+pub fn parse_age(value: &str) -> Result<Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("age '{}' needs a unit (m, h, d, w)", value))?;
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow!("invalid age '{}'", value))?;
+
+    let seconds = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => bail!("unknown age unit '{}' (use m, h, d or w)", unit),
+    };
+    Ok(Duration::from_secs(amount * seconds))
+}
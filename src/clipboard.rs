@@ -0,0 +1,141 @@
+// clipboard.rs
+//! External clipboard backends. Entries never become part of a shell command or
+//! script as text: stdin-based tools (wl-copy, xclip, pbcopy) get the raw bytes,
+//! and PowerShell gets them base64-encoded, so quotes, newlines, `$()` and
+//! unicode arrive exactly as they were in the history.
+//!
+//! Kept free of other crate modules so `tests/clipboard_test.rs` can include it;
+//! the PowerShell helpers are built on every platform so they are tested everywhere.
+
+use std::fmt;
+
+/// Windows caps a command line at 32K characters; longer scripts are left to
+/// the copypasta fallback
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub const MAX_POWERSHELL_SCRIPT: usize = 30_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClipboardError {
+    /// Clipboard APIs and command lines treat NUL as the end of the text
+    ContainsNul,
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipboardError::ContainsNul => write!(f, "entry contains a NUL byte"),
+        }
+    }
+}
+
+/// Check that every backend can carry `text` unchanged
+pub fn validate(text: &str) -> Result<(), ClipboardError> {
+    if text.contains('\0') {
+        return Err(ClipboardError::ContainsNul);
+    }
+    Ok(())
+}
+
+/// Copy with the platform's clipboard tool; failures of the tool itself are
+/// only logged since the copypasta fallback runs afterwards
+pub fn copy(text: &str) -> Result<(), ClipboardError> {
+    validate(text)?;
+
+    #[cfg(target_os = "linux")]
+    copy_linux(text);
+
+    #[cfg(target_os = "macos")]
+    copy_macos(text);
+
+    #[cfg(target_os = "windows")]
+    copy_windows(text);
+
+    Ok(())
+}
+
+/// PowerShell script setting the clipboard to `text`, or `None` when it would
+/// not fit on a command line. Only base64 characters come from `text`.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn powershell_script(text: &str) -> Option<String> {
+    let script = format!(
+        "Set-Clipboard -Value ([Text.Encoding]::UTF8.GetString([Convert]::FromBase64String('{}')))",
+        base64(text.as_bytes())
+    );
+    (script.len() <= MAX_POWERSHELL_SCRIPT).then_some(script)
+}
+
+/// Standard base64 with padding
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn pipe_to(program: &str, args: &[&str], text: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    child.wait().map(|_| ())
+}
+
+#[cfg(target_os = "linux")]
+fn copy_linux(text: &str) {
+    use std::env;
+
+    if env::var("WAYLAND_DISPLAY").is_ok() {
+        let _ =
+            pipe_to("wl-copy", &[], text).map_err(|e| eprintln!("Wayland clipboard error: {e}"));
+    } else if env::var("DISPLAY").is_ok() {
+        let _ = pipe_to("xclip", &["-selection", "clipboard"], text)
+            .map_err(|e| eprintln!("X11 clipboard error: {e}"));
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn copy_macos(text: &str) {
+    let _ = pipe_to("pbcopy", &[], text).map_err(|e| eprintln!("macOS clipboard error: {e}"));
+}
+
+#[cfg(target_os = "windows")]
+fn copy_windows(text: &str) {
+    use std::process::{Command, Stdio};
+
+    let Some(script) = powershell_script(text) else {
+        return;
+    };
+    let _ = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .and_then(|mut child| child.wait().map(|_| ()))
+        .map_err(|e| eprintln!("Windows clipboard error: {e}"));
+}
@@ -0,0 +1,251 @@
+// config.rs
+//! User configuration, read from `config.json` in the platform config directory
+//! (e.g. `~/.config/term-kit/config.json`). Every field is optional.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use crate::search::CaseMode;
+use crate::sources::{HistoryFormat, HistorySource};
+use crate::storage::BookmarkFormat;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// `shell` (default) for the shell's history file, `atuin`, `mcfly`,
+    /// `all` for every shell's history merged, `file` for `history_file`, or
+    /// `command` for the output of `history_command`, or `remote` for the
+    /// login shell's history on `remote_host`
+    pub history_source: HistorySource,
+    /// Any history file, e.g. one synced from another machine
+    pub history_file: Option<PathBuf>,
+    /// `plain` (default, one command per line), `bash`, `zsh` or `fish`
+    pub history_format: HistoryFormat,
+    /// Shell command printing one entry per line, oldest first, for the
+    /// `command` source, e.g. `atuin history list --cmd-only`
+    pub history_command: Option<String>,
+    /// SSH destination for the `remote` source, e.g. `user@server`
+    pub remote_host: Option<String>,
+    /// With the `all` source, also read the other side of WSL: Windows'
+    /// PowerShell history from inside WSL, or the distributions' shell
+    /// histories from Windows
+    pub wsl_history: bool,
+    /// The time column (toggled with `t`) and how it shows when commands ran
+    pub timestamps: TimestampConfig,
+    /// Rules applied to everything term-kit writes out (reports, exports)
+    pub redaction: RedactionRules,
+    /// Commands left out of the history list
+    pub ignore: IgnoreRules,
+    /// Prefixes ignored when telling duplicates apart and searching
+    pub normalize: NormalizeRules,
+    /// Entries shown and searched at startup, and added on each scroll past
+    /// the end: a number (default 1000) or `"unlimited"`
+    pub history_limit: HistoryLimit,
+    /// Reload when the history file changes, e.g. after running commands in
+    /// another terminal
+    pub live_reload: bool,
+    /// Show the current mode and filter in the terminal title while running
+    pub set_title: bool,
+    /// Rank bookmarked commands above other search matches
+    pub boost_bookmarks: bool,
+    /// Show what the redaction `mask_patterns` match, e.g. AWS keys and
+    /// `--password=...`, as `****` in the list until revealed with `x`;
+    /// copying a masked command asks first
+    pub mask_secrets: bool,
+    /// `json` (default), `toml` or `sqlite` for the bookmark file; existing
+    /// bookmarks are converted the next time term-kit runs
+    pub bookmark_format: BookmarkFormat,
+    /// Days deleted bookmarks stay in the trash before being dropped for
+    /// good; 0 deletes them right away
+    pub bookmark_trash_days: u64,
+    /// Show repeated commands once, with their occurrence count
+    pub collapse_duplicates: bool,
+    /// Drop repeated commands (as compared after `normalize`) when loading,
+    /// keeping only the most recent run, like zsh's HIST_IGNORE_ALL_DUPS;
+    /// frequency sorting still counts every run
+    pub dedup_history: bool,
+    /// `smart` (default), `insensitive` or `sensitive`
+    pub search_case: CaseMode,
+    /// Feedback when a command is copied: `none`, `bell` or `flash`
+    pub copy_feedback: Feedback,
+    /// Feedback when an action fails: `none`, `bell` or `flash`
+    pub error_feedback: Feedback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HistoryLimit {
+    Entries(usize),
+    Unlimited(Unlimited),
+}
+
+/// The `"unlimited"` string accepted by `history_limit`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unlimited {
+    Unlimited,
+}
+
+impl Default for HistoryLimit {
+    fn default() -> Self {
+        HistoryLimit::Entries(1000)
+    }
+}
+
+impl HistoryLimit {
+    /// Entries per chunk; 0 is treated as 1 so scrolling still makes progress
+    pub fn entries(self) -> usize {
+        match self {
+            HistoryLimit::Entries(count) => count.max(1),
+            HistoryLimit::Unlimited(_) => usize::MAX,
+        }
+    }
+}
+
+/// Non-visual-status-bar feedback for users who don't watch the status bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feedback {
+    #[default]
+    None,
+    /// Ring the terminal bell
+    Bell,
+    /// Briefly invert the whole screen
+    Flash,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            history_source: HistorySource::default(),
+            history_file: None,
+            history_format: HistoryFormat::default(),
+            history_command: None,
+            remote_host: None,
+            wsl_history: false,
+            timestamps: TimestampConfig::default(),
+            redaction: RedactionRules::default(),
+            ignore: IgnoreRules::default(),
+            normalize: NormalizeRules::default(),
+            history_limit: HistoryLimit::default(),
+            live_reload: true,
+            set_title: true,
+            boost_bookmarks: false,
+            mask_secrets: true,
+            bookmark_format: BookmarkFormat::default(),
+            bookmark_trash_days: 30,
+            collapse_duplicates: false,
+            dedup_history: false,
+            search_case: CaseMode::default(),
+            copy_feedback: Feedback::default(),
+            error_feedback: Feedback::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionRules {
+    /// Regexes whose matches are replaced with `****`; a capture group, when
+    /// present, limits masking to that group
+    pub mask_patterns: Vec<String>,
+    /// Arguments removed together with their value, e.g. `--password`
+    pub strip_args: Vec<String>,
+    /// Regexes that drop the whole entry when they match
+    pub drop_patterns: Vec<String>,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        Self {
+            mask_patterns: vec![
+                r"(?i)(?:password|passwd|token|secret|api[_-]?key)=(\S+)".into(),
+                r"(?i)authorization:\s*(?:bearer|basic)\s+([^\s'\x22]+)".into(),
+                r"(?i)\bbearer\s+([^\s'\x22]+)".into(),
+                r"(?i)--(?:password|passwd|token|secret|api[_-]?key)\s+([^\s'\x22-]\S*)".into(),
+                r"\b(AKIA[0-9A-Z]{16})\b".into(),
+            ],
+            strip_args: Vec::new(),
+            drop_patterns: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimestampConfig {
+    /// Show the time column at startup
+    pub show: bool,
+    /// `relative` (default: "2h ago", then dates like "Mar 3" after a week)
+    /// or `absolute`
+    pub style: TimeStyle,
+    /// strftime-style format of absolute times, e.g. `%d.%m.%Y %H:%M`;
+    /// defaults to `%Y-%m-%d %H:%M`, or `%Y-%m-%d %-I:%M %p` with `clock_12h`
+    pub format: Option<String>,
+    /// Use a 12-hour clock in the default format
+    pub clock_12h: bool,
+    /// Month and weekday names to use instead of the English ones
+    pub locale: DateLocale,
+}
+
+/// Words for `%B %b %A %a %p`, e.g. `"months": ["Januar", "Februar", ...]`.
+/// Lists of the wrong length are ignored, and abbreviations left out are the
+/// first three letters of the names.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DateLocale {
+    /// January to December
+    pub months: Vec<String>,
+    pub short_months: Vec<String>,
+    /// Sunday to Saturday
+    pub weekdays: Vec<String>,
+    pub short_weekdays: Vec<String>,
+    /// Before and after noon, e.g. `["vorm.", "nachm."]`
+    pub am_pm: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeStyle {
+    #[default]
+    Relative,
+    Absolute,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IgnoreRules {
+    /// Whole commands to hide, compared after trimming, e.g. `ls` or `clear`
+    pub commands: Vec<String>,
+    /// Regexes hiding every entry they match, e.g. `^cd( |$)`
+    pub patterns: Vec<String>,
+}
+
+/// Spacing and a trailing `;` are always ignored; these are opt-in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizeRules {
+    /// Treat `sudo apt update` as `apt update` (also `doas`)
+    pub strip_sudo: bool,
+    /// Treat `RUST_LOG=debug cargo run` and `env X=1 cmd` as the bare command
+    pub strip_env: bool,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("", "", "term-kit")
+            .map(|dirs| dirs.config_dir().join("config.json"))
+    }
+
+    /// Load the config file, falling back to defaults when missing or invalid
+    pub fn load() -> Self {
+        let Some(content) = Self::path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return Self::default();
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            eprintln!("Failed to parse config, using defaults: {}", e);
+            Self::default()
+        })
+    }
+}
@@ -0,0 +1,117 @@
+// entry.rs
+//! A parsed history line plus the metadata its shell recorded with it.
+
+use std::borrow::Cow;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub command: String,
+    /// When the command started, in seconds since the Unix epoch
+    pub timestamp: Option<u64>,
+    /// How long it ran, in seconds
+    pub duration: Option<u64>,
+    /// Exit status, where the history tool recorded it (Atuin, McFly, Nushell)
+    pub exit_status: Option<i64>,
+    /// Directory it ran in, where the history tool recorded it
+    pub cwd: Option<String>,
+    /// Files and directories the command referred to when it ran (fish records these)
+    pub paths: Vec<String>,
+    /// Which history it came from, when several are merged, e.g. "zsh"
+    pub origin: Option<String>,
+}
+
+impl HistoryEntry {
+    /// An entry without metadata, for formats that only store the command
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            timestamp: None,
+            duration: None,
+            exit_status: None,
+            cwd: None,
+            paths: Vec::new(),
+            origin: None,
+        }
+    }
+}
+
+/// A line of a history file: its bytes as stored, newline included, and its
+/// text, decoded lossily and without the line ending
+pub struct HistoryLine<'a> {
+    pub raw: &'a [u8],
+    pub text: Cow<'a, str>,
+}
+
+/// Split a history file into lines the way `str::lines` would, keeping the
+/// raw bytes so files can be written back unchanged
+pub fn split_lines(content: &[u8]) -> Vec<HistoryLine<'_>> {
+    content
+        .split_inclusive(|b| *b == b'\n')
+        .map(|raw| {
+            let line = raw.strip_suffix(b"\n").unwrap_or(raw);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            HistoryLine {
+                raw,
+                text: String::from_utf8_lossy(line),
+            }
+        })
+        .collect()
+}
+
+/// An entry together with the lines of the file it was parsed from
+pub struct HistoryRecord {
+    pub lines: Range<usize>,
+    pub entry: HistoryEntry,
+}
+
+/// Metadata of a command's newest run, looked up by command text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunInfo {
+    pub timestamp: Option<u64>,
+    pub duration: Option<u64>,
+    pub exit_status: Option<i64>,
+    pub cwd: Option<String>,
+    pub paths: Vec<String>,
+    pub origin: Option<String>,
+}
+
+impl RunInfo {
+    pub fn is_empty(&self) -> bool {
+        self.timestamp.is_none()
+            && self.duration.is_none()
+            && self.exit_status.is_none()
+            && self.cwd.is_none()
+            && self.paths.is_empty()
+            && self.origin.is_none()
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Coarse age such as "just now", "5m ago" or "3d ago"
+pub fn format_age(timestamp: u64, now: u64) -> String {
+    let age = now.saturating_sub(timestamp);
+    match age {
+        0..=59 => "just now".to_string(),
+        60..=3_599 => format!("{}m ago", age / 60),
+        3_600..=86_399 => format!("{}h ago", age / 3_600),
+        86_400..=1_209_599 => format!("{}d ago", age / 86_400),
+        1_209_600..=31_535_999 => format!("{}w ago", age / 604_800),
+        _ => format!("{}y ago", age / 31_536_000),
+    }
+}
+
+/// Compact duration such as "45s", "12m 5s" or "2h 3m"
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3_599 => format!("{}m {}s", seconds / 60, seconds % 60),
+        _ => format!("{}h {}m", seconds / 3_600, seconds % 3_600 / 60),
+    }
+}
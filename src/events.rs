@@ -1,15 +1,34 @@
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::style::Print;
+use crossterm::terminal::SetTitle;
+use crossterm::ExecutableCommand;
 use ratatui::Terminal;
-use std::io::Result;
-use crate::app::{App, MoveDirection};
+use std::io::{stdout, Result};
+use crate::app::App;
+use crate::bus::UpdateBus;
+use crate::keymap::map_key;
 use crate::ui::draw_ui;
 
 pub fn handle_events<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
+    bus: &UpdateBus,
 ) -> Result<()> {
+    let mut title = String::new();
+
     loop {
+        // Background work is applied at most once per frame
+        for update in bus.drain() {
+            app.apply_update(update);
+        }
+
         terminal.draw(|f| draw_ui(f, app))?;
+        app.frame_drawn();
+
+        if app.config.set_title && app.terminal_title() != title {
+            title = app.terminal_title();
+            stdout().execute(SetTitle(&title))?;
+        }
 
         if app.should_quit {
             break;
@@ -17,70 +36,20 @@ pub fn handle_events<B: ratatui::backend::Backend>(
 
         if event::poll(std::time::Duration::from_millis(100))? {
             let event = event::read()?;
+            if let Event::Paste(text) = &event {
+                app.paste(text);
+            }
             if let Event::Key(key_event) = event {
                 if key_event.kind != KeyEventKind::Press {
                     continue;
                 }
 
-                match key_event.code {
-                    KeyCode::Char('h') => app.show_help = true,
-                    KeyCode::Char('q') => {
-                        app.should_quit = true;
-                    }
-
-                    KeyCode::Enter => {
-                        app.copy_selected();
-                    }
-
-                    KeyCode::Char('b') if !app.search_mode => {
-                        if app.bookmark_mode {
-                            app.delete_bookmark();
-                        } else {
-                            app.toggle_bookmark();
-                        }
-                    }
-
-                    KeyCode::Char('B') if !app.search_mode => {
-                        app.toggle_bookmark_mode();
-                        app.message = if app.bookmark_mode {
-                            "Switched to bookmark mode".to_string()
-                        } else {
-                            "Switched to history mode".to_string()
-                        };
-                    }
-
-                    KeyCode::Char('d') if app.bookmark_mode && !app.search_mode => {
-                        app.delete_bookmark();
-                    }
-
-                    KeyCode::Up | KeyCode::Char('k') => app.move_selection(MoveDirection::Up),
-                    KeyCode::Down | KeyCode::Char('j') => app.move_selection(MoveDirection::Down),
-
-                    KeyCode::Char('/') => {
-                        app.search_mode = true;
-                        app.clear_query();
-                    }
-
-                    KeyCode::Esc => {
-                        if app.search_mode {
-                            app.search_mode = false;
-                            app.clear_query();
-                        } else if app.show_help {
-                            app.show_help = false;
-                        } else if app.bookmark_mode {
-                            app.toggle_bookmark_mode();
-                        }
-                    }
-
-                    KeyCode::Char(c) if app.search_mode => {
-                        app.push_query(c);
-                    }
-
-                    KeyCode::Backspace if app.search_mode => {
-                        app.pop_query();
-                    }
+                if let Some(action) = map_key(key_event, app) {
+                    app.dispatch(action);
+                }
 
-                    _ => {}
+                if app.take_bell() {
+                    stdout().execute(Print('\x07'))?;
                 }
             }
         }
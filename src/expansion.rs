@@ -0,0 +1,108 @@
+// expansion.rs
+//! Finds the shell expansions in a command without running any of them, so the
+//! preview can warn about variables that would expand to nothing.
+
+/// Something the shell would substitute when the command runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expansion {
+    /// `$NAME` or `${NAME...}`; `set` tells whether it is in term-kit's environment
+    Variable { name: String, set: bool },
+    /// `$(...)` or a backquoted command, shown as written and never executed
+    Substitution(String),
+}
+
+/// Expansions in `cmd` in order of appearance, each listed once.
+/// Single-quoted text and backslash-escaped `$` are left alone, as the shell does.
+pub fn expansions(cmd: &str) -> Vec<Expansion> {
+    let chars: Vec<char> = cmd.chars().collect();
+    let mut found = Vec::new();
+    let mut single_quoted = false;
+    let mut double_quoted = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\'' if !double_quoted => single_quoted = !single_quoted,
+            _ if single_quoted => {}
+            '"' => double_quoted = !double_quoted,
+            '\\' => i += 1,
+            '`' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '`')
+                    .map_or(chars.len(), |offset| i + 1 + offset + 1);
+                let substitution = Expansion::Substitution(chars[i..end].iter().collect());
+                if !found.contains(&substitution) {
+                    found.push(substitution);
+                }
+                i = end;
+                continue;
+            }
+            '$' => {
+                let (expansion, end) = expansion_at(&chars, i);
+                if let Some(expansion) = expansion.filter(|e| !found.contains(e)) {
+                    found.push(expansion);
+                }
+                i = end;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    found
+}
+
+/// Parse the expansion starting at the `$` in `chars[start]`, returning it and
+/// the index just past it
+fn expansion_at(chars: &[char], start: usize) -> (Option<Expansion>, usize) {
+    let rest = &chars[start + 1..];
+    match rest.first() {
+        Some('(') => {
+            let mut depth = 0;
+            let mut end = chars.len();
+            for (offset, &c) in rest.iter().enumerate() {
+                match c {
+                    '(' => depth += 1,
+                    ')' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            end = start + 1 + offset + 1;
+                            break;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            let text = chars[start..end].iter().collect();
+            (Some(Expansion::Substitution(text)), end)
+        }
+        Some('{') => {
+            let end = rest
+                .iter()
+                .position(|&c| c == '}')
+                .map_or(chars.len(), |offset| start + 1 + offset + 1);
+            let name: String = rest[1..].iter().take_while(|c| is_name_char(**c)).collect();
+            (variable(name), end)
+        }
+        Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+            let name: String = rest.iter().take_while(|c| is_name_char(**c)).collect();
+            let end = start + 1 + name.len();
+            (variable(name), end)
+        }
+        // `$1`, `$?`, `$$` and friends depend on the running shell, not the environment
+        _ => (None, start + 1),
+    }
+}
+
+fn variable(name: String) -> Option<Expansion> {
+    if name.is_empty() {
+        return None;
+    }
+    let set = std::env::var_os(&name).is_some();
+    Some(Expansion::Variable { name, set })
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
@@ -0,0 +1,328 @@
+// export.rs
+//! `term-kit export`: bookmarks written out for use outside term-kit, e.g. as
+//! aliases the shell loads on startup or a cheatsheet for the team wiki.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use crate::app::ShellType;
+use crate::bookmark::Bookmark;
+use crate::cli::{ExportArgs, MarkdownSections};
+use crate::config::Config;
+use crate::redact::Redactor;
+
+/// Longest generated alias name, before any `_2` suffix
+const MAX_ALIAS_CHARS: usize = 24;
+/// Keywords of the supported shells, which can't name a function
+const RESERVED_NAMES: [&str; 20] = [
+    "if", "then", "else", "elif", "fi", "case", "esac", "for", "while", "until", "do", "done",
+    "in", "function", "select", "time", "begin", "end", "switch", "return",
+];
+
+/// How a shell defines aliases and functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    /// bash, zsh and ksh
+    Posix,
+    Fish,
+    PowerShell,
+}
+
+impl Dialect {
+    fn for_shell(shell: &ShellType) -> Result<Self> {
+        Ok(match shell {
+            ShellType::Bash | ShellType::Zsh | ShellType::Ksh => Dialect::Posix,
+            ShellType::Fish => Dialect::Fish,
+            ShellType::PowerShell => Dialect::PowerShell,
+            other => bail!(
+                "aliases can't be exported for {} (use bash, zsh, ksh, fish or pwsh)",
+                other.name()
+            ),
+        })
+    }
+
+    fn default_file(self) -> &'static str {
+        match self {
+            Dialect::Posix => "term-kit-aliases.sh",
+            Dialect::Fish => "term-kit-aliases.fish",
+            Dialect::PowerShell => "term-kit-aliases.ps1",
+        }
+    }
+
+    /// Lines defining `name` to run `cmd`; single-line commands become
+    /// aliases, which keep taking arguments, longer ones functions
+    fn define(self, name: &str, cmd: &str) -> String {
+        let multiline = cmd.contains('\n');
+        match self {
+            Dialect::Posix if multiline => format!("{}() {{\n{}\n}}", name, cmd),
+            Dialect::Posix => format!("alias {}='{}'", name, cmd.replace('\'', r"'\''")),
+            Dialect::Fish if multiline => format!("function {}\n{}\nend", name, cmd),
+            Dialect::Fish => format!(
+                "alias {} '{}'",
+                name,
+                cmd.replace('\\', r"\\").replace('\'', r"\'")
+            ),
+            // Set-Alias can't carry arguments; PowerShell also closes single
+            // quotes on the typographic ones, so those are doubled as well
+            Dialect::PowerShell => {
+                let mut quoted = String::new();
+                for c in cmd.chars() {
+                    quoted.push(c);
+                    if matches!(c, '\'' | '‘' | '’' | '‚' | '‛') {
+                        quoted.push(c);
+                    }
+                }
+                format!(
+                    "function {} {{ Invoke-Expression ('{}' + ' ' + $args) }}",
+                    name, quoted
+                )
+            }
+        }
+    }
+
+    fn source_hint(self, path: &str) -> String {
+        match self {
+            Dialect::Posix => format!("add `source {}` to your shell's rc file", path),
+            Dialect::Fish => format!("add `source {}` to ~/.config/fish/config.fish", path),
+            Dialect::PowerShell => format!("add `. {}` to your $PROFILE", path),
+        }
+    }
+}
+
+pub fn run(args: &ExportArgs) -> Result<()> {
+    match args {
+        ExportArgs::Aliases { shell, path } => aliases(
+            shell.clone().unwrap_or_else(ShellType::detect),
+            path.clone(),
+        ),
+        ExportArgs::Markdown { sections, path } => markdown(*sections, path.clone()),
+    }
+}
+
+fn active_bookmarks() -> Result<Vec<Bookmark>> {
+    let store = Config::load().bookmark_format.store();
+    let (_, bookmarks) = store
+        .load()
+        .with_context(|| format!("Failed to read {}", store.location().display()))?;
+    Ok(bookmarks.into_iter().filter(|b| !b.is_trashed()).collect())
+}
+
+/// Write every active bookmark as an alias named after its title, or its
+/// command's words when it has none
+fn aliases(shell: ShellType, path: Option<PathBuf>) -> Result<()> {
+    let dialect = Dialect::for_shell(&shell)?;
+    let bookmarks = active_bookmarks()?;
+
+    let mut out = format!(
+        "# Generated by `term-kit export aliases` from {} bookmarks; regenerate instead of editing\n",
+        bookmarks.len()
+    );
+    let mut taken = HashSet::new();
+    for bookmark in &bookmarks {
+        let base = alias_name(bookmark.title.as_deref().unwrap_or(&bookmark.command));
+        let mut name = base.clone();
+        let mut n = 1;
+        while !taken.insert(name.clone()) {
+            n += 1;
+            name = format!("{}_{}", base, n);
+        }
+
+        out.push('\n');
+        if let Some(note) = &bookmark.note {
+            for line in note.lines() {
+                out.push_str(&format!("# {}\n", line));
+            }
+        }
+        out.push_str(&dialect.define(&name, &bookmark.command));
+        out.push('\n');
+    }
+
+    let path = path.unwrap_or_else(|| PathBuf::from(dialect.default_file()));
+    fs::write(&path, out).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!(
+        "Wrote {} aliases to {}; {}",
+        bookmarks.len(),
+        path.display(),
+        dialect.source_hint(&path.display().to_string())
+    );
+    Ok(())
+}
+
+/// Write the bookmarks as a Markdown cheatsheet, one section per group or
+/// tag, with the commands redacted as for reports
+fn markdown(sections: MarkdownSections, path: Option<PathBuf>) -> Result<()> {
+    let config = Config::load();
+    let redactor = Redactor::new(&config.redaction);
+    for invalid in &redactor.invalid {
+        eprintln!("Ignoring invalid redaction pattern {}", invalid);
+    }
+    let mut bookmarks = active_bookmarks()?;
+    bookmarks.retain_mut(|bookmark| match redactor.apply(&bookmark.command) {
+        Some(command) => {
+            bookmark.command = command;
+            true
+        }
+        None => false,
+    });
+
+    let mut names: Vec<&str> = Vec::new();
+    for bookmark in &bookmarks {
+        let keys = match sections {
+            MarkdownSections::Group => bookmark.group.iter().map(String::as_str).collect(),
+            MarkdownSections::Tag => bookmark.tags.iter().map(String::as_str).collect::<Vec<_>>(),
+        };
+        for key in keys {
+            if !names.contains(&key) {
+                names.push(key);
+            }
+        }
+    }
+    if sections == MarkdownSections::Tag {
+        names.sort_unstable();
+    }
+
+    // Ungrouped bookmarks come first, as in the list; untagged ones last
+    let mut out = String::from("# Bookmarks\n");
+    let loose: Vec<&Bookmark> = bookmarks
+        .iter()
+        .filter(|b| match sections {
+            MarkdownSections::Group => b.group.is_none(),
+            MarkdownSections::Tag => b.tags.is_empty(),
+        })
+        .collect();
+    let mut listed: Vec<(String, Vec<&Bookmark>)> = names
+        .iter()
+        .map(|name| {
+            let members = bookmarks
+                .iter()
+                .filter(|b| match sections {
+                    MarkdownSections::Group => b.group.as_deref() == Some(*name),
+                    MarkdownSections::Tag => b.tags.iter().any(|tag| tag == name),
+                })
+                .collect();
+            match sections {
+                MarkdownSections::Group => (name.to_string(), members),
+                MarkdownSections::Tag => (format!("#{}", name), members),
+            }
+        })
+        .collect();
+    if !loose.is_empty() {
+        match sections {
+            MarkdownSections::Group if listed.is_empty() => {
+                listed.insert(0, (String::new(), loose))
+            }
+            MarkdownSections::Group => listed.insert(0, ("Ungrouped".to_string(), loose)),
+            MarkdownSections::Tag if listed.is_empty() => listed.push((String::new(), loose)),
+            MarkdownSections::Tag => listed.push(("Untagged".to_string(), loose)),
+        }
+    }
+
+    let language = fence_language(&ShellType::detect());
+    for (heading, members) in &listed {
+        if !heading.is_empty() {
+            let _ = writeln!(out, "\n## {}", heading);
+        }
+        for bookmark in members {
+            write_markdown_entry(&mut out, bookmark, sections, language);
+        }
+    }
+
+    let path = path.unwrap_or_else(|| PathBuf::from("term-kit-bookmarks.md"));
+    fs::write(&path, out).with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("Wrote {} bookmarks to {}", bookmarks.len(), path.display());
+    Ok(())
+}
+
+/// A bookmark's title, note, command and the labels its section doesn't show
+fn write_markdown_entry(
+    out: &mut String,
+    bookmark: &Bookmark,
+    sections: MarkdownSections,
+    language: &str,
+) {
+    out.push('\n');
+    if let Some(title) = &bookmark.title {
+        let _ = writeln!(out, "**{}**\n", title);
+    }
+    if let Some(note) = &bookmark.note {
+        let _ = writeln!(out, "{}\n", note);
+    }
+
+    // A fence longer than any backtick run inside the command
+    let longest_run = bookmark
+        .command
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let _ = writeln!(
+        out,
+        "{}{}\n{}\n{}",
+        fence, language, bookmark.command, fence
+    );
+
+    let labels = match sections {
+        MarkdownSections::Group => (!bookmark.tags.is_empty()).then(|| {
+            let tags: Vec<String> = bookmark
+                .tags
+                .iter()
+                .map(|tag| format!("`#{}`", tag))
+                .collect();
+            format!("Tags: {}", tags.join(" "))
+        }),
+        MarkdownSections::Tag => bookmark
+            .group
+            .as_ref()
+            .map(|group| format!("Group: {}", group)),
+    };
+    if let Some(labels) = labels {
+        let _ = writeln!(out, "\n{}", labels);
+    }
+}
+
+/// Info string that highlights `shell`'s commands, or none to leave them plain
+fn fence_language(shell: &ShellType) -> &'static str {
+    match shell {
+        ShellType::Bash | ShellType::Zsh | ShellType::Ksh => "sh",
+        ShellType::Fish => "fish",
+        ShellType::PowerShell => "powershell",
+        ShellType::Cmd => "bat",
+        ShellType::Nushell => "nu",
+        _ => "",
+    }
+}
+
+/// `text` as a name every supported shell accepts: lowercase letters, digits
+/// and underscores, e.g. "nuke_docker" for "Nuke Docker!"
+fn alias_name(text: &str) -> String {
+    let words = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase);
+    // Whole words only, unless the first is already too long
+    let mut name = String::new();
+    for word in words {
+        if name.is_empty() {
+            name = word;
+            name.truncate(MAX_ALIAS_CHARS);
+        } else if name.len() + 1 + word.len() <= MAX_ALIAS_CHARS {
+            name.push('_');
+            name.push_str(&word);
+        } else {
+            break;
+        }
+    }
+
+    if name.is_empty() {
+        "bookmark".to_string()
+    } else if name.starts_with(|c: char| c.is_ascii_digit())
+        || RESERVED_NAMES.contains(&name.as_str())
+    {
+        format!("bm_{}", name)
+    } else {
+        name
+    }
+}
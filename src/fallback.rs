@@ -0,0 +1,96 @@
+// fallback.rs
+//! Line-based picker used when the TUI can't work: stdout is not a terminal, or
+//! the terminal is too short for the layout. The list and prompts go to stderr so
+//! the chosen command can be captured from stdout, e.g. `cmd=$(term-kit)`.
+
+use anyhow::Result;
+use std::io::{self, BufRead, IsTerminal, Write};
+use crate::app::{Action, App};
+use crate::views::display_command;
+
+/// Fewer rows than this can't fit the header, search and status bars plus a list
+pub const MIN_TUI_ROWS: u16 = 10;
+
+/// Entries listed per prompt
+const PAGE_SIZE: usize = 20;
+
+/// Whether the full-screen UI would render broken here
+pub fn tui_unavailable() -> bool {
+    if !io::stdout().is_terminal() {
+        return true;
+    }
+    crossterm::terminal::size().is_ok_and(|(_, rows)| rows < MIN_TUI_ROWS)
+}
+
+pub fn run(app: &mut App) -> Result<()> {
+    app.load_history_now();
+
+    let stdin = io::stdin();
+    let mut stderr = io::stderr();
+    let mut line = String::new();
+
+    if let Some(error) = app.history_error() {
+        writeln!(stderr, "Could not read the history file: {}", error)?;
+    }
+    if !app.message.is_empty() {
+        writeln!(stderr, "{}", app.message)?;
+    }
+
+    loop {
+        let list = app.current_list();
+        if list.is_empty() {
+            writeln!(stderr, "No matching entries.")?;
+        }
+        for (i, cmd) in list.iter().enumerate().take(PAGE_SIZE).rev() {
+            writeln!(stderr, "{:3}  {}", i + 1, display_command(app, cmd))?;
+        }
+        write!(stderr, "Number to copy, text to filter, empty to quit: ")?;
+        stderr.flush()?;
+
+        line.clear();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let input = line.trim();
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        match input.parse::<usize>() {
+            Ok(n) if (1..=app.current_list().len()).contains(&n) => {
+                app.selected = n - 1;
+                let cmd = app.current_list()[app.selected].clone();
+                // There is no menu to confirm in, so ask here as the TUI would
+                let secret = app.shown_command(&cmd) != cmd;
+                if secret {
+                    write!(
+                        stderr,
+                        "{} contains a secret. Copy and print it unmasked? [y/N] ",
+                        display_command(app, &cmd)
+                    )?;
+                    stderr.flush()?;
+                    line.clear();
+                    stdin.lock().read_line(&mut line)?;
+                    if !matches!(line.trim().to_lowercase().as_str(), "y" | "yes") {
+                        writeln!(stderr, "Not copied.")?;
+                        return Ok(());
+                    }
+                }
+                app.dispatch(Action::CopySelection);
+                if secret {
+                    app.dispatch(Action::CopySecret);
+                }
+                if !app.message.is_empty() {
+                    writeln!(stderr, "{}", app.message)?;
+                }
+                println!("{}", cmd);
+                return Ok(());
+            }
+            _ => {
+                app.clear_query();
+                input.chars().for_each(|c| app.push_query(c));
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// ignore.rs
+//! Commands the user never wants listed (`ls`, `clear`, ...), dropped when the
+//! history is loaded.
+
+use regex::Regex;
+use crate::config::IgnoreRules;
+use crate::entry::HistoryEntry;
+
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreList {
+    commands: Vec<String>,
+    patterns: Vec<Regex>,
+    /// Configured patterns that failed to compile, with the reason
+    pub invalid: Vec<String>,
+}
+
+impl IgnoreList {
+    pub fn new(rules: &IgnoreRules) -> Self {
+        let mut invalid = Vec::new();
+        let patterns = rules
+            .patterns
+            .iter()
+            .filter_map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| invalid.push(format!("'{}': {}", pattern, e)))
+                    .ok()
+            })
+            .collect();
+
+        Self {
+            commands: rules
+                .commands
+                .iter()
+                .map(|cmd| cmd.trim().to_string())
+                .collect(),
+            patterns,
+            invalid,
+        }
+    }
+
+    pub fn is_ignored(&self, cmd: &str) -> bool {
+        let trimmed = cmd.trim();
+        self.commands.iter().any(|ignored| ignored == trimmed)
+            || self.patterns.iter().any(|re| re.is_match(cmd))
+    }
+
+    pub fn apply(&self, history: &mut Vec<HistoryEntry>) {
+        if !self.commands.is_empty() || !self.patterns.is_empty() {
+            history.retain(|entry| !self.is_ignored(&entry.command));
+        }
+    }
+}
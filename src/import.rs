@@ -0,0 +1,307 @@
+// import.rs
+//! `term-kit import` and the bookmark view's `I`: another bookmark file merged
+//! into ours, e.g. a teammate's set of deploy commands. Commands already
+//! bookmarked in another spacing or option order are not added twice.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use anyhow::{anyhow, bail, Context, Result};
+use crate::bookmark::{self, Bookmark};
+use crate::cli::ImportArgs;
+use crate::config::Config;
+use crate::normalize::{self, Normalizer};
+use crate::toml;
+
+/// How the imported bookmarks were merged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub added: usize,
+    /// Already bookmarked, and got tags or a title, note or group from the file
+    pub updated: usize,
+    /// Already bookmarked with nothing new
+    pub skipped: usize,
+    /// Bookmarks whose own title, note or group was kept over a different imported one
+    pub conflicts: usize,
+}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} already bookmarked",
+            self.added, self.updated, self.skipped
+        )?;
+        if self.conflicts > 0 {
+            write!(
+                f,
+                " ({} kept their own title, note or group)",
+                self.conflicts
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run(args: &ImportArgs) -> Result<()> {
+    let config = Config::load();
+    let incoming = read(&args.path)?;
+    let store = config.bookmark_format.store();
+    let path = store.location();
+    let (version, mut bookmarks) = store
+        .load()
+        .with_context(|| format!("Failed to read {}, nothing imported", path.display()))?;
+    if version > bookmark::FILE_VERSION {
+        bail!(
+            "{} was saved by a newer term-kit (format {}); please upgrade first",
+            path.display(),
+            version
+        );
+    }
+
+    let summary = merge(
+        &mut bookmarks,
+        incoming,
+        &Normalizer::new(&config.normalize),
+    );
+    if summary.added + summary.updated > 0 {
+        store
+            .save(&bookmarks)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    println!("Imported {}: {}", args.path.display(), summary);
+    Ok(())
+}
+
+/// Bookmarks in a bookmark file of either format, a backup bundle, or a
+/// plain list of commands with one per line
+pub fn read(path: &Path) -> Result<Vec<Bookmark>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "toml")
+    {
+        let document = toml::from_str(&content)
+            .map_err(|e| anyhow!("{} is not a bookmark file: {}", path.display(), e))?;
+        let (_, bookmarks) = bookmark::parse_value(document)
+            .with_context(|| format!("{} is not a bookmark file", path.display()))?;
+        return Ok(bookmarks);
+    }
+    if !content.trim_start().starts_with(['[', '{']) {
+        return Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Bookmark::new)
+            .collect());
+    }
+
+    let value: serde_json::Value = serde_json::from_str(&content)
+        .with_context(|| format!("{} is not a bookmark file", path.display()))?;
+    // A `term-kit backup create` bundle holds the bookmark file as a string
+    let Some(files) = value.get("files") else {
+        let (_, bookmarks) = bookmark::parse_file(&content)
+            .with_context(|| format!("{} is not a bookmark file", path.display()))?;
+        return Ok(bookmarks);
+    };
+    let bundled = files
+        .get("bookmarks")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| anyhow!("the backup in {} has no bookmarks", path.display()))?;
+    let (_, bookmarks) = bookmark::parse_file(bundled)
+        .with_context(|| format!("the bookmarks in {} are damaged", path.display()))?;
+    Ok(bookmarks)
+}
+
+/// Add the `incoming` bookmarks that aren't in `bookmarks` yet. Ones that are
+/// get the tags they lack, and a title, note or group where they have none;
+/// their own are kept otherwise. Pins, use counts and the trash stay local.
+pub fn merge(
+    bookmarks: &mut Vec<Bookmark>,
+    incoming: Vec<Bookmark>,
+    normalizer: &Normalizer,
+) -> ImportSummary {
+    let key = |cmd: &str| normalize::option_order_key(&normalizer.normalize(cmd));
+    let mut summary = ImportSummary::default();
+
+    for imported in incoming.into_iter().filter(|b| !b.is_trashed()) {
+        let imported_key = key(&imported.command);
+        let Some(existing) = bookmarks
+            .iter_mut()
+            .find(|b| key(&b.command) == imported_key)
+        else {
+            bookmarks.push(Bookmark {
+                pinned: false,
+                uses: 0,
+                last_used: None,
+                ..imported
+            });
+            summary.added += 1;
+            continue;
+        };
+
+        let before = existing.clone();
+        for tag in imported.tags {
+            if !existing.tags.contains(&tag) {
+                existing.tags.push(tag);
+            }
+        }
+        let mut conflict = false;
+        for (ours, theirs) in [
+            (&mut existing.title, imported.title),
+            (&mut existing.note, imported.note),
+            (&mut existing.group, imported.group),
+        ] {
+            match (ours.as_ref(), theirs) {
+                (None, theirs) => *ours = theirs,
+                (Some(ours), Some(theirs)) if *ours != theirs => conflict = true,
+                _ => {}
+            }
+        }
+
+        if existing.is_trashed() {
+            // Importing it again means it is wanted after all
+            existing.trashed_at = None;
+            summary.added += 1;
+        } else if *existing != before {
+            summary.updated += 1;
+        } else {
+            summary.skipped += 1;
+        }
+        if conflict {
+            summary.conflicts += 1;
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NormalizeRules;
+
+    fn import(bookmarks: &mut Vec<Bookmark>, incoming: Vec<Bookmark>) -> ImportSummary {
+        merge(
+            bookmarks,
+            incoming,
+            &Normalizer::new(&NormalizeRules::default()),
+        )
+    }
+
+    fn tagged(command: &str, tags: &[&str]) -> Bookmark {
+        Bookmark {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Bookmark::new(command)
+        }
+    }
+
+    #[test]
+    fn same_command_in_other_spacing_or_option_order_is_skipped() {
+        let mut bookmarks = vec![Bookmark::new("ls -la /tmp")];
+        let summary = import(
+            &mut bookmarks,
+            vec![Bookmark::new("ls /tmp  -al"), Bookmark::new("git status")],
+        );
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 1,
+                skipped: 1,
+                ..ImportSummary::default()
+            }
+        );
+        assert_eq!(
+            bookmarks,
+            [Bookmark::new("ls -la /tmp"), Bookmark::new("git status")]
+        );
+    }
+
+    #[test]
+    fn added_bookmarks_start_unpinned_and_unused() {
+        let mut bookmarks = Vec::new();
+        let imported = Bookmark {
+            pinned: true,
+            uses: 9,
+            last_used: Some(1700000000),
+            ..tagged("make deploy", &["prod"])
+        };
+        import(&mut bookmarks, vec![imported]);
+        assert_eq!(bookmarks, [tagged("make deploy", &["prod"])]);
+    }
+
+    #[test]
+    fn tags_are_merged() {
+        let mut bookmarks = vec![tagged("make deploy", &["prod", "ci"])];
+        let summary = import(
+            &mut bookmarks,
+            vec![tagged("make deploy", &["ops", "prod"])],
+        );
+        assert_eq!(summary.updated, 1);
+        assert_eq!(bookmarks[0].tags, ["prod", "ci", "ops"]);
+    }
+
+    #[test]
+    fn own_title_wins_and_counts_as_conflict() {
+        let ours = Bookmark {
+            title: Some("deploy".to_string()),
+            ..Bookmark::new("make deploy")
+        };
+        let theirs = Bookmark {
+            title: Some("ship it".to_string()),
+            note: Some("needs VPN".to_string()),
+            ..Bookmark::new("make deploy")
+        };
+        let mut bookmarks = vec![ours];
+        let summary = import(&mut bookmarks, vec![theirs]);
+        assert_eq!(
+            summary,
+            ImportSummary {
+                updated: 1,
+                conflicts: 1,
+                ..ImportSummary::default()
+            }
+        );
+        assert_eq!(bookmarks[0].title.as_deref(), Some("deploy"));
+        assert_eq!(bookmarks[0].note.as_deref(), Some("needs VPN"));
+    }
+
+    #[test]
+    fn importing_a_trashed_bookmark_restores_it() {
+        let mut bookmarks = vec![Bookmark {
+            trashed_at: Some(1700000000),
+            ..Bookmark::new("make deploy")
+        }];
+        let summary = import(&mut bookmarks, vec![Bookmark::new("make deploy")]);
+        assert_eq!(summary.added, 1);
+        assert!(!bookmarks[0].is_trashed());
+    }
+
+    #[test]
+    fn trash_of_the_imported_file_is_left_out() {
+        let mut bookmarks = Vec::new();
+        let trashed = Bookmark {
+            trashed_at: Some(1700000000),
+            ..Bookmark::new("make deploy")
+        };
+        assert_eq!(
+            import(&mut bookmarks, vec![trashed]),
+            ImportSummary::default()
+        );
+        assert!(bookmarks.is_empty());
+    }
+
+    #[test]
+    fn summary_mentions_conflicts() {
+        let summary = ImportSummary {
+            added: 2,
+            updated: 1,
+            skipped: 3,
+            conflicts: 1,
+        };
+        assert_eq!(
+            summary.to_string(),
+            "2 added, 1 updated, 3 already bookmarked (1 kept their own title, note or group)"
+        );
+    }
+}
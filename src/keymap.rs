@@ -0,0 +1,136 @@
+// keymap.rs
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::app::{Action, App, CursorMotion, MoveDirection};
+use crate::search::SearchDisplay;
+use crate::views::view_for;
+
+/// Translate a key press into an action for the current app state
+pub fn map_key(key: KeyEvent, app: &App) -> Option<Action> {
+    // A pending `Q`/`@` consumes the next key as the register name
+    if app.pending_register.is_some() {
+        return match key.code {
+            KeyCode::Char(c) if c.is_ascii_lowercase() => Some(Action::Register(c)),
+            _ => Some(Action::Cancel),
+        };
+    }
+
+    if app.prompt.is_some() {
+        return map_prompt_key(key);
+    }
+
+    if app.menu.is_some() {
+        return map_menu_key(key);
+    }
+
+    if app.search_mode {
+        return map_search_key(key, app);
+    }
+
+    // Global keys take precedence over the view's own keys
+    match key.code {
+        KeyCode::Char('h') => return Some(Action::ShowHelp),
+        KeyCode::Char('q') => return Some(Action::Quit),
+        KeyCode::Enter => return Some(Action::CopySelection),
+        _ => {}
+    }
+
+    if let Some(action) = view_for(app.mode).map_key(key) {
+        return Some(action);
+    }
+
+    let action = match key.code {
+        KeyCode::Char('B') => Action::NextMode,
+        KeyCode::Tab => Action::RecentMode(MoveDirection::Down),
+        KeyCode::BackTab => Action::RecentMode(MoveDirection::Up),
+        KeyCode::Char('s') => Action::CycleSort,
+        KeyCode::Char('o') => Action::ToggleOrder,
+        KeyCode::Char('u') => Action::ToggleCollapse,
+        KeyCode::Char('m') => Action::OpenMenu,
+        KeyCode::Char('e') => Action::ToggleExpansions,
+        KeyCode::Char('n') => Action::JumpMatch(MoveDirection::Down),
+        KeyCode::Char('N') => Action::JumpMatch(MoveDirection::Up),
+        KeyCode::Char('p') => Action::OpenPresets,
+        KeyCode::Char('S') => Action::OpenShells,
+        KeyCode::Char('r') => Action::ReloadHistory,
+        KeyCode::Char(c @ '1'..='9') => Action::ApplyPreset(c as usize - '1' as usize),
+        KeyCode::Char('Q') => Action::RecordMacro,
+        KeyCode::Char('@') => Action::PlayMacro,
+        KeyCode::Char('z') => Action::ZoxideAdd,
+        KeyCode::Char('Z') => Action::CopyZoxideJump,
+        KeyCode::Char('x') => Action::ToggleSecrets,
+
+        KeyCode::Left => Action::CycleChip(MoveDirection::Up),
+        KeyCode::Right => Action::CycleChip(MoveDirection::Down),
+
+        KeyCode::Up | KeyCode::Char('k') => Action::Move(MoveDirection::Up),
+        KeyCode::Down | KeyCode::Char('j') => Action::Move(MoveDirection::Down),
+
+        KeyCode::Char('/') => Action::StartSearch,
+        KeyCode::Esc => Action::Cancel,
+
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// While searching, printable keys edit the query instead of triggering commands
+fn map_search_key(key: KeyEvent, app: &App) -> Option<Action> {
+    let action = match key.code {
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Action::ToggleMatchMode
+        }
+        KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => Action::SavePreset,
+        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Action::ToggleSearchDisplay
+        }
+        KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Action::SearchDeleteWord
+        }
+        KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => Action::SearchClear,
+        KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            Action::PasteClipboard
+        }
+        KeyCode::Char(c) => Action::SearchInput(c),
+        KeyCode::Backspace => Action::SearchBackspace,
+        KeyCode::Left => Action::SearchCursor(CursorMotion::Left),
+        KeyCode::Right => Action::SearchCursor(CursorMotion::Right),
+        KeyCode::Home => Action::SearchCursor(CursorMotion::Home),
+        KeyCode::End => Action::SearchCursor(CursorMotion::End),
+        KeyCode::Tab => Action::AcceptTopMatch,
+        KeyCode::Enter if app.search_display == SearchDisplay::Highlight => Action::CommitSearch,
+        KeyCode::Enter => Action::CopySelection,
+        KeyCode::Up => Action::RecallQuery(MoveDirection::Up),
+        KeyCode::Down => Action::RecallQuery(MoveDirection::Down),
+        KeyCode::Esc => Action::Cancel,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// While the context menu is open it owns navigation and Enter
+fn map_menu_key(key: KeyEvent) -> Option<Action> {
+    let action = match key.code {
+        KeyCode::Up | KeyCode::Char('k') => Action::Move(MoveDirection::Up),
+        KeyCode::Down | KeyCode::Char('j') => Action::Move(MoveDirection::Down),
+        KeyCode::Enter => Action::MenuAccept,
+        KeyCode::Esc | KeyCode::Char('m') | KeyCode::Char('q') => Action::Cancel,
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Typing a preset name or bookmark tags
+fn map_prompt_key(key: KeyEvent) -> Option<Action> {
+    let action = match key.code {
+        KeyCode::Char(c) => Action::PromptInput(c),
+        KeyCode::Backspace => Action::PromptBackspace,
+        KeyCode::Left => Action::PromptCursor(CursorMotion::Left),
+        KeyCode::Right => Action::PromptCursor(CursorMotion::Right),
+        KeyCode::Home => Action::PromptCursor(CursorMotion::Home),
+        KeyCode::End => Action::PromptCursor(CursorMotion::End),
+        KeyCode::Enter => Action::ConfirmPrompt,
+        KeyCode::Esc => Action::Cancel,
+        _ => return None,
+    };
+    Some(action)
+}
@@ -1,9 +1,46 @@
 mod app;
+mod backup;
+mod bookmark;
+mod bus;
+mod clean;
+mod cli;
+mod clipboard;
+mod config;
+mod entry;
 mod events;
+mod expansion;
+mod export;
+mod fallback;
+mod ignore;
+mod import;
+mod keymap;
+mod menu;
+mod merge;
+mod normalize;
+mod presets;
+mod process;
+mod profile;
+mod recall;
+mod redact;
+mod report;
+mod rewrite;
+mod search;
+mod sources;
+mod sqlite;
+mod stats;
+mod storage;
+mod timefmt;
+mod toml;
 mod ui;
+mod views;
+mod watch;
+mod wsl;
+mod zoxide;
 
 use anyhow::Result;
 use crossterm::{
+    event::{DisableBracketedPaste, EnableBracketedPaste},
+    style::Print,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
@@ -11,24 +48,81 @@ use ratatui::prelude::*;
 use std::io::{stdout, Stdout};
 
 fn main() -> Result<()> {
-    let mut terminal = setup_terminal()?;
-    let mut app = app::App::new();
+    let browse = match cli::parse_args(std::env::args().skip(1))? {
+        cli::Command::Browse(args) => args,
+        cli::Command::Report(args) => return report::run(&args),
+        cli::Command::Clean(args) => return clean::run(&args),
+        cli::Command::Backup(args) => return backup::run(&args),
+        cli::Command::Import(args) => return import::run(&args),
+        cli::Command::Export(args) => return export::run(&args),
+        cli::Command::Help => {
+            print!("{}", cli::usage());
+            return Ok(());
+        }
+    };
 
+    let mut config = config::Config::load();
+    if let Some(source) = browse.source {
+        config.history_source = source;
+    }
+    if let Some(path) = browse.history_file.clone() {
+        config.history_source = sources::HistorySource::File;
+        config.history_file = Some(path);
+    }
+    if let Some(command) = browse.history_command.clone() {
+        config.history_source = sources::HistorySource::Command;
+        config.history_command = Some(command);
+    }
+    if let Some(host) = browse.remote.clone() {
+        config.history_source = sources::HistorySource::Remote;
+        config.remote_host = Some(host);
+    }
+    if let Some(format) = browse.history_format {
+        config.history_format = format;
+    }
+    let set_title = config.set_title;
+    let mut app = app::App::new(config);
+    if let Some(shell) = browse.shell.clone() {
+        app.set_shell(shell);
+    }
+    if fallback::tui_unavailable() {
+        return fallback::run(&mut app);
+    }
 
-    events::handle_events(&mut terminal, &mut app)?;
+    let mut terminal = setup_terminal(set_title)?;
+    let bus = bus::UpdateBus::new();
+    app.start_history_load(bus.sender());
 
-    restore_terminal(&mut terminal)?;
+    events::handle_events(&mut terminal, &mut app, &bus)?;
+
+    restore_terminal(&mut terminal, set_title)?;
+    if browse.profile_startup {
+        eprint!("{}", app.startup_profile().render());
+    }
     Ok(())
 }
 
-fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
+fn setup_terminal(set_title: bool) -> Result<Terminal<CrosstermBackend<Stdout>>> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
+    // Pasted text arrives as one event instead of a burst of key presses
+    stdout().execute(EnableBracketedPaste)?;
+    if set_title {
+        // Save the current title on the terminal's title stack (XTWINOPS)
+        stdout().execute(Print("\x1b[22;0t"))?;
+    }
     Ok(Terminal::new(CrosstermBackend::new(stdout()))?)
 }
 
-fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+fn restore_terminal(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    set_title: bool,
+) -> Result<()> {
     disable_raw_mode()?;
+    stdout().execute(DisableBracketedPaste)?;
+    if set_title {
+        stdout().execute(Print("\x1b[23;0t"))?;
+    }
     stdout().execute(LeaveAlternateScreen)?;
     terminal.show_cursor()?;
     Ok(())
@@ -0,0 +1,55 @@
+// menu.rs
+//! Popup menus: the actions that apply to the selected entry, so less common
+//! keys can be found without opening the help, and the saved search presets.
+
+use crate::app::{Action, MoveDirection};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem {
+    pub label: String,
+    /// Key that triggers the action directly, shown next to the label
+    pub key: &'static str,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContextMenu {
+    pub title: &'static str,
+    pub items: Vec<MenuItem>,
+    pub selected: usize,
+}
+
+impl ContextMenu {
+    pub fn new(title: &'static str, items: Vec<MenuItem>) -> Self {
+        Self {
+            title,
+            items,
+            selected: 0,
+        }
+    }
+
+    /// Move the highlight, wrapping around at either end
+    pub fn move_selection(&mut self, direction: MoveDirection) {
+        if self.items.is_empty() {
+            return;
+        }
+        self.selected = match direction {
+            MoveDirection::Up => (self.selected + self.items.len() - 1) % self.items.len(),
+            MoveDirection::Down => (self.selected + 1) % self.items.len(),
+        };
+    }
+
+    pub fn selected_action(&self) -> Option<Action> {
+        self.items.get(self.selected).map(|item| item.action)
+    }
+}
+
+impl MenuItem {
+    pub fn new(label: impl Into<String>, key: &'static str, action: Action) -> Self {
+        Self {
+            label: label.into(),
+            key,
+            action,
+        }
+    }
+}
@@ -0,0 +1,438 @@
+// merge.rs
+//! Three-way merge of bookmark sets, used when the bookmark file changed on disk
+//! (another instance, a synced dotfile directory, ...) since we last read it.
+
+use crate::bookmark::Bookmark;
+
+/// Which copy of the bookmarks introduced a change
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// Both sides edited the bookmark, differently; accepting takes the
+    /// on-disk edit, rejecting keeps ours
+    Changed {
+        ours: Box<Bookmark>,
+        theirs: Box<Bookmark>,
+    },
+    /// Both sides reordered the bookmarks, differently; accepting takes the
+    /// on-disk order, rejecting keeps ours. Has no command.
+    Reordered,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BookmarkChange {
+    pub command: String,
+    pub side: Side,
+    pub kind: ChangeKind,
+    pub accepted: bool,
+}
+
+/// Pending merge between our in-memory bookmarks and the ones found on disk.
+/// Bookmarks are told apart by command; edits made on one side only are kept,
+/// edits made on both are listed as `ChangeKind::Changed`. The same goes for
+/// the order of the list, see `ChangeKind::Reordered`.
+#[derive(Debug, Clone, Default)]
+pub struct BookmarkMerge {
+    base: Vec<Bookmark>,
+    local: Vec<Bookmark>,
+    /// The on-disk bookmarks the merge was computed against
+    pub remote: Vec<Bookmark>,
+    pub changes: Vec<BookmarkChange>,
+    /// Commands of `changes`, in the same order, for list navigation and copying
+    pub commands: Vec<String>,
+}
+
+fn find<'a>(bookmarks: &'a [Bookmark], command: &str) -> Option<&'a Bookmark> {
+    bookmarks
+        .iter()
+        .find(|bookmark| bookmark.command == command)
+}
+
+/// Commands of `bookmarks` that every one of `others` has too, in order
+fn shared_order<'a>(bookmarks: &'a [Bookmark], others: &[&[Bookmark]]) -> Vec<&'a str> {
+    bookmarks
+        .iter()
+        .filter(|b| others.iter().all(|other| find(other, &b.command).is_some()))
+        .map(|b| b.command.as_str())
+        .collect()
+}
+
+/// Whether `side` lists the bookmarks it shares with `base` in another order
+fn reordered(side: &[Bookmark], base: &[Bookmark]) -> bool {
+    shared_order(side, &[base]) != shared_order(base, &[side])
+}
+
+/// Whether `a` and `b` only differ in what copying them records, which
+/// isn't worth asking about
+fn same_edits(a: &Bookmark, b: &Bookmark) -> bool {
+    Bookmark {
+        uses: a.uses,
+        last_used: a.last_used,
+        ..b.clone()
+    } == *a
+}
+
+impl BookmarkMerge {
+    /// Diff both sides against the last state they agreed on
+    pub fn new(base: Vec<Bookmark>, local: &[Bookmark], remote: Vec<Bookmark>) -> Self {
+        // Changes both sides agree on are applied directly instead of being listed
+        let mut base: Vec<Bookmark> = base
+            .into_iter()
+            .filter(|b| find(local, &b.command).is_some() || find(&remote, &b.command).is_some())
+            .collect();
+        for bookmark in &remote {
+            if find(local, &bookmark.command).is_some() && find(&base, &bookmark.command).is_none()
+            {
+                base.push(bookmark.clone());
+            }
+        }
+
+        let mut changes = Vec::new();
+        for (side, current) in [(Side::Local, local), (Side::Remote, remote.as_slice())] {
+            for bookmark in current.iter().filter(|b| find(&base, &b.command).is_none()) {
+                changes.push(BookmarkChange::accepted(
+                    &bookmark.command,
+                    side,
+                    ChangeKind::Added,
+                ));
+            }
+            for bookmark in base.iter().filter(|b| find(current, &b.command).is_none()) {
+                changes.push(BookmarkChange::accepted(
+                    &bookmark.command,
+                    side,
+                    ChangeKind::Removed,
+                ));
+            }
+        }
+        for bookmark in &base {
+            if let (Some(ours), Some(theirs)) = (
+                find(local, &bookmark.command),
+                find(&remote, &bookmark.command),
+            ) {
+                if ours != bookmark && theirs != bookmark && !same_edits(ours, theirs) {
+                    changes.push(BookmarkChange {
+                        command: bookmark.command.clone(),
+                        side: Side::Remote,
+                        kind: ChangeKind::Changed {
+                            ours: Box::new(ours.clone()),
+                            theirs: Box::new(theirs.clone()),
+                        },
+                        accepted: false,
+                    });
+                }
+            }
+        }
+
+        if reordered(local, &base)
+            && reordered(&remote, &base)
+            && shared_order(local, &[&base, &remote]) != shared_order(&remote, &[&base, local])
+        {
+            changes.push(BookmarkChange {
+                command: String::new(),
+                side: Side::Remote,
+                kind: ChangeKind::Reordered,
+                accepted: false,
+            });
+        }
+
+        let commands = changes.iter().map(|c| c.command.clone()).collect();
+        Self {
+            base,
+            local: local.to_vec(),
+            remote,
+            changes,
+            commands,
+        }
+    }
+
+    /// Bookmarks after applying every accepted change to the base, in the
+    /// order of the side that reordered them
+    pub fn resolve(&self) -> Vec<Bookmark> {
+        let mut merged: Vec<Bookmark> =
+            self.base
+                .iter()
+                .filter(|b| {
+                    !self.changes.iter().any(|c| {
+                        c.accepted && c.kind == ChangeKind::Removed && c.command == b.command
+                    })
+                })
+                .map(|base| {
+                    let edited_on_both = self.changes.iter().find_map(|c| match &c.kind {
+                        ChangeKind::Changed { ours, theirs } if c.command == base.command => {
+                            Some(if c.accepted { theirs } else { ours })
+                        }
+                        _ => None,
+                    });
+                    if let Some(chosen) = edited_on_both {
+                        return Bookmark::clone(chosen);
+                    }
+                    // Otherwise whichever side edited the bookmark wins
+                    match find(&self.local, &base.command) {
+                        Some(local) if local != base => local.clone(),
+                        _ => find(&self.remote, &base.command).unwrap_or(base).clone(),
+                    }
+                })
+                .collect();
+
+        for change in &self.changes {
+            if change.accepted
+                && change.kind == ChangeKind::Added
+                && find(&merged, &change.command).is_none()
+            {
+                let side = match change.side {
+                    Side::Local => &self.local,
+                    Side::Remote => &self.remote,
+                };
+                let added = find(side, &change.command).cloned();
+                merged.push(added.unwrap_or_else(|| Bookmark::new(change.command.clone())));
+            }
+        }
+
+        let disk_order = self
+            .changes
+            .iter()
+            .any(|c| c.accepted && c.kind == ChangeKind::Reordered);
+        let order = match (
+            reordered(&self.local, &self.base),
+            reordered(&self.remote, &self.base),
+        ) {
+            (false, false) => return merged,
+            (true, false) => &self.local,
+            (true, true) if !disk_order => &self.local,
+            _ => &self.remote,
+        };
+        // Bookmarks that side doesn't have go last, like additions
+        merged.sort_by_cached_key(|bookmark| {
+            order
+                .iter()
+                .position(|b| b.command == bookmark.command)
+                .unwrap_or(order.len())
+        });
+        merged
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(change) = self.changes.get_mut(index) {
+            change.accepted = !change.accepted;
+        }
+    }
+}
+
+impl BookmarkChange {
+    fn accepted(command: &str, side: Side, kind: ChangeKind) -> Self {
+        Self {
+            command: command.to_string(),
+            side,
+            kind,
+            accepted: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(commands: &[&str]) -> Vec<Bookmark> {
+        commands
+            .iter()
+            .map(|command| Bookmark::new(*command))
+            .collect()
+    }
+
+    fn commands(bookmarks: &[Bookmark]) -> Vec<&str> {
+        bookmarks
+            .iter()
+            .map(|bookmark| bookmark.command.as_str())
+            .collect()
+    }
+
+    fn tagged(command: &str, tag: &str) -> Bookmark {
+        Bookmark {
+            tags: vec![tag.to_string()],
+            ..Bookmark::new(command)
+        }
+    }
+
+    fn kinds(merge: &BookmarkMerge) -> Vec<(&str, Side, ChangeKind)> {
+        merge
+            .changes
+            .iter()
+            .map(|change| (change.command.as_str(), change.side, change.kind.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_sides_list_nothing() {
+        let merge = BookmarkMerge::new(list(&["a", "b"]), &list(&["a", "b"]), list(&["a", "b"]));
+        assert!(merge.changes.is_empty());
+        assert_eq!(commands(&merge.resolve()), ["a", "b"]);
+    }
+
+    #[test]
+    fn additions_from_both_sides() {
+        let merge = BookmarkMerge::new(list(&["a"]), &list(&["a", "b"]), list(&["a", "c"]));
+        assert_eq!(
+            kinds(&merge),
+            [
+                ("b", Side::Local, ChangeKind::Added),
+                ("c", Side::Remote, ChangeKind::Added)
+            ]
+        );
+        assert_eq!(merge.commands, ["b", "c"]);
+        assert_eq!(commands(&merge.resolve()), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn removals_can_be_rejected() {
+        let mut merge = BookmarkMerge::new(
+            list(&["a", "b", "c"]),
+            &list(&["a", "c"]),
+            list(&["a", "b"]),
+        );
+        assert_eq!(
+            kinds(&merge),
+            [
+                ("b", Side::Local, ChangeKind::Removed),
+                ("c", Side::Remote, ChangeKind::Removed)
+            ]
+        );
+        assert_eq!(commands(&merge.resolve()), ["a"]);
+        merge.toggle(1);
+        assert_eq!(commands(&merge.resolve()), ["a", "c"]);
+    }
+
+    #[test]
+    fn rejected_addition_is_left_out() {
+        let mut merge = BookmarkMerge::new(list(&["a"]), &list(&["a"]), list(&["a", "b"]));
+        merge.toggle(0);
+        assert!(!merge.changes[0].accepted);
+        assert_eq!(commands(&merge.resolve()), ["a"]);
+    }
+
+    #[test]
+    fn changes_both_sides_made_are_applied() {
+        let merge = BookmarkMerge::new(list(&["a", "b"]), &list(&["a", "c"]), list(&["a", "c"]));
+        assert!(merge.changes.is_empty());
+        assert_eq!(commands(&merge.resolve()), ["a", "c"]);
+    }
+
+    #[test]
+    fn edits_from_one_side_are_kept() {
+        let merge = BookmarkMerge::new(
+            list(&["a", "b"]),
+            &[tagged("a", "ours"), Bookmark::new("b")],
+            vec![Bookmark::new("a"), tagged("b", "theirs")],
+        );
+        assert!(merge.changes.is_empty());
+        assert_eq!(
+            merge.resolve(),
+            [tagged("a", "ours"), tagged("b", "theirs")]
+        );
+    }
+
+    #[test]
+    fn edits_from_both_sides_conflict() {
+        let mut merge = BookmarkMerge::new(
+            list(&["a"]),
+            &[tagged("a", "ours")],
+            vec![tagged("a", "theirs")],
+        );
+        let changed = ChangeKind::Changed {
+            ours: Box::new(tagged("a", "ours")),
+            theirs: Box::new(tagged("a", "theirs")),
+        };
+        assert_eq!(kinds(&merge), [("a", Side::Remote, changed)]);
+        assert!(!merge.changes[0].accepted);
+        assert_eq!(merge.resolve(), [tagged("a", "ours")]);
+        merge.toggle(0);
+        assert_eq!(merge.resolve(), [tagged("a", "theirs")]);
+    }
+
+    #[test]
+    fn same_edit_on_both_sides_is_no_conflict() {
+        let merge = BookmarkMerge::new(
+            list(&["a"]),
+            &[tagged("a", "new")],
+            vec![tagged("a", "new")],
+        );
+        assert!(merge.changes.is_empty());
+        assert_eq!(merge.resolve(), [tagged("a", "new")]);
+    }
+
+    #[test]
+    fn use_counts_alone_are_no_conflict() {
+        let ours = vec![Bookmark {
+            uses: 3,
+            last_used: Some(1700000000),
+            ..tagged("a", "new")
+        }];
+        let merge = BookmarkMerge::new(list(&["a"]), &ours, vec![tagged("a", "new")]);
+        assert!(merge.changes.is_empty());
+        assert_eq!(merge.resolve(), ours);
+    }
+
+    #[test]
+    fn order_of_the_side_that_reordered_is_kept() {
+        let merge = BookmarkMerge::new(
+            list(&["a", "b", "c"]),
+            &list(&["c", "a", "b"]),
+            list(&["a", "b", "c", "d"]),
+        );
+        assert_eq!(kinds(&merge), [("d", Side::Remote, ChangeKind::Added)]);
+        assert_eq!(commands(&merge.resolve()), ["c", "a", "b", "d"]);
+    }
+
+    #[test]
+    fn reorder_on_disk_survives_local_edits() {
+        let merge = BookmarkMerge::new(
+            list(&["a", "b"]),
+            &[tagged("a", "ours"), Bookmark::new("b")],
+            list(&["b", "a"]),
+        );
+        assert!(merge.changes.is_empty());
+        assert_eq!(merge.resolve(), [Bookmark::new("b"), tagged("a", "ours")]);
+    }
+
+    #[test]
+    fn additions_keep_their_place_in_the_order() {
+        let merge = BookmarkMerge::new(
+            list(&["a", "b"]),
+            &list(&["a", "b", "y"]),
+            list(&["b", "x", "a"]),
+        );
+        assert_eq!(commands(&merge.resolve()), ["b", "x", "a", "y"]);
+    }
+
+    #[test]
+    fn reorders_on_both_sides_conflict() {
+        let mut merge = BookmarkMerge::new(
+            list(&["a", "b", "c"]),
+            &list(&["b", "a", "c"]),
+            list(&["a", "c", "b"]),
+        );
+        assert_eq!(kinds(&merge), [("", Side::Remote, ChangeKind::Reordered)]);
+        assert!(!merge.changes[0].accepted);
+        assert_eq!(commands(&merge.resolve()), ["b", "a", "c"]);
+        merge.toggle(0);
+        assert_eq!(commands(&merge.resolve()), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn same_reorder_on_both_sides_is_no_conflict() {
+        let merge = BookmarkMerge::new(
+            list(&["a", "b", "c"]),
+            &list(&["c", "b", "a"]),
+            list(&["c", "b", "a", "d"]),
+        );
+        assert_eq!(kinds(&merge), [("d", Side::Remote, ChangeKind::Added)]);
+        assert_eq!(commands(&merge.resolve()), ["c", "b", "a", "d"]);
+    }
+}
@@ -0,0 +1,144 @@
+// normalize.rs
+//! The form commands are compared in, so that near-identical entries count as
+//! duplicates and match the same searches: spacing and trailing `;` never
+//! matter, and leading `sudo` or `VAR=value` prefixes can be ignored too.
+
+use std::borrow::Cow;
+use crate::config::NormalizeRules;
+
+/// Privilege wrappers dropped by `strip_sudo`
+const SUDO_COMMANDS: [&str; 2] = ["sudo", "doas"];
+/// Their options that take a value, e.g. `sudo -u postgres psql`
+const SUDO_VALUE_OPTIONS: [&str; 6] = ["-u", "-g", "-h", "-p", "-C", "-D"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Normalizer {
+    strip_sudo: bool,
+    strip_env: bool,
+}
+
+impl Normalizer {
+    pub fn new(rules: &NormalizeRules) -> Self {
+        Self {
+            strip_sudo: rules.strip_sudo,
+            strip_env: rules.strip_env,
+        }
+    }
+
+    /// `cmd` in comparable form; borrowed when already normalized
+    pub fn normalize<'a>(&self, cmd: &'a str) -> Cow<'a, str> {
+        let mut rest = cmd.trim_start();
+        loop {
+            let stripped = self.strip_prefix(rest);
+            if stripped.len() == rest.len() {
+                break;
+            }
+            rest = stripped.trim_start();
+        }
+
+        let rest = rest.trim_end_matches(|c: char| c == ';' || c.is_whitespace());
+        let irregular = rest.split(' ').any(str::is_empty)
+            || rest.contains(|c: char| c.is_whitespace() && c != ' ');
+        if irregular {
+            Cow::Owned(rest.split_whitespace().collect::<Vec<_>>().join(" "))
+        } else {
+            Cow::Borrowed(rest)
+        }
+    }
+
+    /// `cmd` without one leading wrapper or assignment, or unchanged
+    fn strip_prefix<'a>(&self, cmd: &'a str) -> &'a str {
+        if self.strip_env {
+            if let Some(rest) = strip_assignment(cmd) {
+                return rest;
+            }
+            if let Some(rest) = cmd
+                .strip_prefix("env ")
+                .filter(|rest| strip_assignment(rest.trim_start()).is_some())
+            {
+                return rest;
+            }
+        }
+        if self.strip_sudo {
+            for sudo in SUDO_COMMANDS {
+                let Some(mut rest) = cmd
+                    .strip_prefix(sudo)
+                    .filter(|rest| rest.starts_with(char::is_whitespace))
+                else {
+                    continue;
+                };
+                // Options up to the command, with the values of those that take one
+                loop {
+                    let trimmed = rest.trim_start();
+                    let (option, after) = trimmed
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((trimmed, ""));
+                    if !option.starts_with('-') || option == "--" {
+                        rest = if option == "--" { after } else { trimmed };
+                        break;
+                    }
+                    rest = after;
+                    if SUDO_VALUE_OPTIONS.contains(&option) {
+                        rest = rest
+                            .trim_start()
+                            .split_once(char::is_whitespace)
+                            .map_or("", |(_, after)| after);
+                    }
+                }
+                // A bare `sudo -i` is a command of its own
+                if !rest.trim().is_empty() {
+                    return rest;
+                }
+            }
+        }
+        cmd
+    }
+}
+
+/// What follows a leading `NAME=value`, with the value possibly quoted
+fn strip_assignment(cmd: &str) -> Option<&str> {
+    let (name, value) = cmd.split_once('=')?;
+    let mut chars = name.chars();
+    if !chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+
+    let mut quote = None;
+    for (i, c) in value.char_indices() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            // Nothing after the assignment means it is the whole command
+            None if c.is_whitespace() => {
+                return Some(&value[i..]).filter(|rest| !rest.trim().is_empty())
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// `cmd` with its options sorted after the other words, so that `ls -la /tmp`
+/// and `ls /tmp -al` give the same key; short option clusters are split up
+pub fn option_order_key(cmd: &str) -> String {
+    let mut words = Vec::new();
+    let mut options = Vec::new();
+    for word in cmd.split_whitespace() {
+        match word.strip_prefix('-') {
+            Some(short) if !short.is_empty() && !short.starts_with('-') && !short.contains('=') => {
+                options.extend(short.chars().map(|c| format!("-{}", c)));
+            }
+            Some(_) if word != "-" && word != "--" => options.push(word.to_string()),
+            _ => words.push(word.to_string()),
+        }
+    }
+    options.sort();
+    options.dedup();
+    words.extend(options);
+    words.join(" ")
+}
@@ -0,0 +1,55 @@
+// presets.rs
+//! Named search queries ("k8s", "deploys", ...) saved next to the bookmarks and
+//! applied from a picker or with the number keys.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::search::MatchMode;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Presets {
+    path: PathBuf,
+    pub items: Vec<Preset>,
+}
+
+impl Presets {
+    pub fn load(path: PathBuf) -> Self {
+        let items = fs::read_to_string(&path)
+            .map(|content| serde_json::from_str(&content).unwrap_or_default())
+            .unwrap_or_default();
+        Self { path, items }
+    }
+
+    /// Add a preset, replacing an existing one with the same name in place,
+    /// and write the presets; the preset is kept for this session even if
+    /// that fails
+    pub fn save_preset(&mut self, preset: Preset) -> io::Result<()> {
+        match self.items.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => self.items.push(preset),
+        }
+        self.save()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Preset> {
+        self.items.get(index)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.items)?)
+    }
+}
@@ -0,0 +1,152 @@
+// process.rs
+//! Finds the shell term-kit was started from by walking up the process tree,
+//! since $SHELL only names the login shell.
+
+use crate::app::ShellType;
+
+/// Wrappers such as `sudo`, `sh -c` or `cargo run` sit between term-kit and
+/// the interactive shell; give up after this many ancestors
+const MAX_DEPTH: usize = 16;
+
+/// The nearest ancestor process that is a shell term-kit knows
+pub fn parent_shell() -> Option<ShellType> {
+    let (_, mut pid) = process_info(std::process::id())?;
+    for _ in 0..MAX_DEPTH {
+        // 0 and 1 are the idle process or init, which no shell sits above
+        if pid <= 1 {
+            return None;
+        }
+        let (name, parent) = process_info(pid)?;
+        if let Some(shell) = shell_named(&name) {
+            return Some(shell);
+        }
+        pid = parent;
+    }
+    None
+}
+
+/// Login shells show up as e.g. "-zsh", `ps` may print a full path and
+/// Windows names end in ".exe"
+fn shell_named(name: &str) -> Option<ShellType> {
+    let name = name.trim().trim_start_matches('-');
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let name = match name.len().checked_sub(4) {
+        Some(stem)
+            if name
+                .get(stem..)
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(".exe")) =>
+        {
+            &name[..stem]
+        }
+        _ => name,
+    };
+    ShellType::from_name(name)
+}
+
+/// Executable name and parent pid of `pid`
+#[cfg(target_os = "linux")]
+fn process_info(pid: u32) -> Option<(String, u32)> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // "pid (comm) state ppid ...", where comm may itself contain spaces and ')'
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let name = stat.get(open + 1..close)?.to_string();
+    let parent = stat
+        .get(close + 1..)?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    Some((name, parent))
+}
+
+/// Executable name and parent pid of `pid`, from `ps` where there is no /proc
+#[cfg(all(unix, not(target_os = "linux")))]
+fn process_info(pid: u32) -> Option<(String, u32)> {
+    use std::process::{Command, Stdio};
+
+    let output = Command::new("ps")
+        .args(["-o", "ppid=", "-o", "comm=", "-p"])
+        .arg(pid.to_string())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let output = String::from_utf8_lossy(&output.stdout);
+    let (parent, name) = output.trim().split_once(char::is_whitespace)?;
+    Some((name.to_string(), parent.parse().ok()?))
+}
+
+/// Executable name and parent pid of `pid`, from a Toolhelp process snapshot.
+/// Spawning PowerShell to ask WMI would add about a second to startup.
+#[cfg(windows)]
+fn process_info(pid: u32) -> Option<(String, u32)> {
+    const TH32CS_SNAPPROCESS: u32 = 0x2;
+    const INVALID_HANDLE_VALUE: isize = -1;
+
+    #[repr(C)]
+    struct ProcessEntry32W {
+        size: u32,
+        usage: u32,
+        process_id: u32,
+        default_heap_id: usize,
+        module_id: u32,
+        threads: u32,
+        parent_process_id: u32,
+        priority_class_base: i32,
+        flags: u32,
+        exe_file: [u16; 260],
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateToolhelp32Snapshot(flags: u32, process_id: u32) -> isize;
+        fn Process32FirstW(snapshot: isize, entry: *mut ProcessEntry32W) -> i32;
+        fn Process32NextW(snapshot: isize, entry: *mut ProcessEntry32W) -> i32;
+        fn CloseHandle(handle: isize) -> i32;
+    }
+
+    let mut entry = ProcessEntry32W {
+        size: std::mem::size_of::<ProcessEntry32W>() as u32,
+        usage: 0,
+        process_id: 0,
+        default_heap_id: 0,
+        module_id: 0,
+        threads: 0,
+        parent_process_id: 0,
+        priority_class_base: 0,
+        flags: 0,
+        exe_file: [0; 260],
+    };
+
+    // SAFETY: the snapshot handle is checked before use and closed once, and
+    // `entry` is a correctly sized PROCESSENTRY32W with `size` set as required
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if snapshot == INVALID_HANDLE_VALUE {
+            return None;
+        }
+        let mut found = None;
+        let mut more = Process32FirstW(snapshot, &mut entry) != 0;
+        while more {
+            if entry.process_id == pid {
+                let len = entry
+                    .exe_file
+                    .iter()
+                    .position(|c| *c == 0)
+                    .unwrap_or(entry.exe_file.len());
+                let name = String::from_utf16_lossy(&entry.exe_file[..len]);
+                found = Some((name, entry.parent_process_id));
+                break;
+            }
+            more = Process32NextW(snapshot, &mut entry) != 0;
+        }
+        CloseHandle(snapshot);
+        found
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn process_info(_pid: u32) -> Option<(String, u32)> {
+    None
+}
@@ -0,0 +1,64 @@
+// profile.rs
+//! Startup timing, printed on exit with `--profile-startup` so slow history
+//! loading can be measured and reported with numbers.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// A named step and how long it took
+pub type Phase = (&'static str, Duration);
+
+#[derive(Debug, Clone)]
+pub struct StartupProfile {
+    started: Instant,
+    /// Individual steps, in the order they ran
+    phases: Vec<Phase>,
+    /// Points reached, measured from `started`
+    milestones: Vec<Phase>,
+}
+
+impl Default for StartupProfile {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            phases: Vec::new(),
+            milestones: Vec::new(),
+        }
+    }
+}
+
+impl StartupProfile {
+    /// Run `f`, recording how long it took under `name`
+    pub fn time<T>(phases: &mut Vec<Phase>, name: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let value = f();
+        phases.push((name, started.elapsed()));
+        value
+    }
+
+    pub fn record(&mut self, phases: impl IntoIterator<Item = Phase>) {
+        self.phases.extend(phases);
+    }
+
+    /// Note the first time `name` is reached; later calls are ignored
+    pub fn mark(&mut self, name: &'static str) {
+        if !self.milestones.iter().any(|(n, _)| *n == name) {
+            self.milestones.push((name, self.started.elapsed()));
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::from("Startup profile:\n");
+        for (name, duration) in &self.phases {
+            let _ = writeln!(out, "  {:<20} {:>9.2} ms", name, millis(*duration));
+        }
+        for (name, at) in &self.milestones {
+            let _ = writeln!(out, "  {:<20} {:>9.2} ms after start", name, millis(*at));
+        }
+        out
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
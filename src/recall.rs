@@ -0,0 +1,89 @@
+// recall.rs
+//! Past search queries, recalled with Up/Down in the search bar like readline
+//! history and kept next to the bookmarks between sessions.
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Oldest queries are dropped beyond this many
+const CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct QueryHistory {
+    path: PathBuf,
+    /// Oldest first
+    queries: VecDeque<String>,
+    /// Position of the query being shown while recalling
+    cursor: Option<usize>,
+}
+
+impl QueryHistory {
+    pub fn load(path: PathBuf) -> Self {
+        let queries = fs::read_to_string(&path)
+            .map(|content| serde_json::from_str(&content).unwrap_or_default())
+            .unwrap_or_default();
+        Self {
+            path,
+            queries,
+            cursor: None,
+        }
+    }
+
+    /// Remember a query that led somewhere, moving repeats to the newest slot,
+    /// and write the queries; it is remembered for this session even if that
+    /// fails
+    pub fn push(&mut self, query: &str) -> io::Result<()> {
+        self.cursor = None;
+        if query.is_empty() {
+            return Ok(());
+        }
+
+        self.queries.retain(|q| q != query);
+        self.queries.push_back(query.to_string());
+        while self.queries.len() > CAPACITY {
+            self.queries.pop_front();
+        }
+        self.save()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_recalling(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    /// Stop recalling, e.g. because the recalled query is being edited
+    pub fn reset(&mut self) {
+        self.cursor = None;
+    }
+
+    /// Step back to an older query
+    pub fn older(&mut self) -> Option<&str> {
+        let index = match self.cursor {
+            Some(0) => 0,
+            Some(index) => index - 1,
+            None => self.queries.len().checked_sub(1)?,
+        };
+        self.cursor = Some(index);
+        self.queries.get(index).map(String::as_str)
+    }
+
+    /// Step forward to a newer query; `None` once past the newest one
+    pub fn newer(&mut self) -> Option<&str> {
+        let index = self.cursor? + 1;
+        if index >= self.queries.len() {
+            self.cursor = None;
+            return None;
+        }
+        self.cursor = Some(index);
+        self.queries.get(index).map(String::as_str)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, serde_json::to_string_pretty(&self.queries)?)
+    }
+}
@@ -0,0 +1,103 @@
+// report.rs
+//! `term-kit report`: a pasteable summary of recent shell usage.
+
+use anyhow::Result;
+use std::fmt::Write;
+use crate::app::ShellType;
+use crate::cli::{ReportArgs, ReportFormat};
+use crate::config::Config;
+use crate::entry;
+use crate::redact::Redactor;
+use crate::stats::HistoryStats;
+use crate::views::truncate_for_display;
+
+pub fn run(args: &ReportArgs) -> Result<()> {
+    let config = Config::load();
+    let provider = config
+        .history_source
+        .provider(&ShellType::detect(), &config);
+    let mut entries = provider.load(&mut Vec::new()).unwrap_or_default();
+
+    // Without any timestamps the window can't be applied, which render() notes
+    let timed = entries.iter().any(|entry| entry.timestamp.is_some());
+    if let Some(since) = args.since.filter(|_| timed) {
+        let cutoff = entry::now().saturating_sub(since.as_secs());
+        entries.retain(|entry| entry.timestamp.is_some_and(|timestamp| timestamp >= cutoff));
+    }
+
+    let history: Vec<String> = entries.into_iter().map(|entry| entry.command).collect();
+    let redactor = Redactor::new(&config.redaction);
+    for invalid in &redactor.invalid {
+        eprintln!("Ignoring invalid redaction pattern {}", invalid);
+    }
+    let history = redactor.apply_all(&history);
+
+    let untimed_hint = (!timed && provider.records_timestamps())
+        .then_some("Turn on timestamps in your shell (zsh: setopt EXTENDED_HISTORY, bash: HISTTIMEFORMAT) to limit it.");
+    print!(
+        "{}",
+        render(&HistoryStats::compute(&history), args, timed, untimed_hint)
+    );
+    Ok(())
+}
+
+fn render(
+    stats: &HistoryStats,
+    args: &ReportArgs,
+    timed: bool,
+    untimed_hint: Option<&str>,
+) -> String {
+    let markdown = args.format == ReportFormat::Markdown;
+    let mut out = String::new();
+
+    let heading = |out: &mut String, title: &str| {
+        if markdown {
+            let _ = writeln!(out, "## {}\n", title);
+        } else {
+            let _ = writeln!(out, "{}\n{}\n", title, "-".repeat(title.len()));
+        }
+    };
+
+    if markdown {
+        let _ = writeln!(out, "# Shell usage report\n");
+    } else {
+        let _ = writeln!(out, "Shell usage report\n==================\n");
+    }
+
+    if args.since.is_some() && !timed {
+        let _ = writeln!(
+            out,
+            "Note: the history has no timestamps, so this covers all loaded entries.{}\n",
+            untimed_hint
+                .map(|hint| format!(" {}", hint))
+                .unwrap_or_default()
+        );
+    }
+
+    let _ = writeln!(out, "{} commands, {} unique\n", stats.total, stats.unique);
+
+    heading(&mut out, "Most used commands");
+    write_table(&mut out, &stats.top_commands, markdown);
+
+    heading(&mut out, "Most used programs");
+    write_table(&mut out, &stats.top_programs, markdown);
+
+    out
+}
+
+fn write_table(out: &mut String, rows: &[(String, usize)], markdown: bool) {
+    if rows.is_empty() {
+        let _ = writeln!(out, "(none)\n");
+        return;
+    }
+
+    for (i, (name, count)) in rows.iter().enumerate() {
+        let name = truncate_for_display(name);
+        if markdown {
+            let _ = writeln!(out, "{}. `{}` ({}×)", i + 1, name.replace('`', "'"), count);
+        } else {
+            let _ = writeln!(out, "{:>3}. {:<40} {}", i + 1, name, count);
+        }
+    }
+    let _ = writeln!(out);
+}
@@ -0,0 +1,265 @@
+// rewrite.rs
+//! Deleting entries from history files in place. Every other line is written
+//! back byte for byte, after the original is copied next to it.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::entry::{self, split_lines};
+use crate::sources::HistoryFormat;
+
+/// What `remove_entries` did
+#[derive(Debug, Default)]
+pub struct Removal {
+    /// Entries deleted
+    pub removed: usize,
+    /// Copies of the files from before; files where nothing matched aren't
+    /// touched and have none
+    pub backups: Vec<PathBuf>,
+}
+
+impl Removal {
+    /// The backups for a message, e.g. "~/.zsh_history.1700000000.bak"
+    pub fn backup_list(&self) -> String {
+        self.backups
+            .iter()
+            .map(|backup| backup.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// `remove_entries` for every file of a history kept in several, e.g. fish's
+/// session files
+pub fn remove_from_files(
+    paths: &[PathBuf],
+    format: HistoryFormat,
+    matches: &dyn Fn(&str) -> bool,
+) -> io::Result<Removal> {
+    let mut total = Removal::default();
+    for path in paths.iter().filter(|path| path.exists()) {
+        let removal = remove_entries(path, format, matches)?;
+        total.removed += removal.removed;
+        total.backups.extend(removal.backups);
+    }
+    Ok(total)
+}
+
+/// Delete every entry of `path` whose command `matches`, parsing it as `format`
+pub fn remove_entries(
+    path: &Path,
+    format: HistoryFormat,
+    matches: &dyn Fn(&str) -> bool,
+) -> io::Result<Removal> {
+    let content = fs::read(path)?;
+    let lines = split_lines(&content);
+
+    let mut dropped = vec![false; lines.len()];
+    let mut removed = 0;
+    for record in format.records(&lines) {
+        if matches(&record.entry.command) {
+            dropped[record.lines].fill(true);
+            removed += 1;
+        }
+    }
+    if removed == 0 {
+        return Ok(Removal::default());
+    }
+
+    let backup = backup_path(path);
+    fs::copy(path, &backup)?;
+    let kept: Vec<u8> = lines
+        .iter()
+        .zip(&dropped)
+        .filter(|(_, dropped)| !**dropped)
+        .flat_map(|(line, _)| line.raw.iter().copied())
+        .collect();
+    // Written in place rather than renamed over, so the file keeps its permissions
+    fs::write(path, kept)?;
+    Ok(Removal {
+        removed,
+        backups: vec![backup],
+    })
+}
+
+/// `~/.zsh_history` is backed up as `~/.zsh_history.1700000000.bak`, with a
+/// counter added when that is taken
+fn backup_path(path: &Path) -> PathBuf {
+    let stamp = entry::now();
+    let mut attempt = 0;
+    loop {
+        let mut name = path.as_os_str().to_owned();
+        match attempt {
+            0 => name.push(format!(".{}.bak", stamp)),
+            n => name.push(format!(".{}-{}.bak", stamp, n)),
+        }
+        let candidate = PathBuf::from(name);
+        if !candidate.exists() {
+            return candidate;
+        }
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir, one per test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("term-kit-rewrite-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("scratch dir");
+        dir
+    }
+
+    /// Remove the entries matching `target` from a file holding `before`, and
+    /// check it ends up as `after` with the backup holding `before`
+    fn check(
+        name: &str,
+        format: HistoryFormat,
+        before: &str,
+        target: &str,
+        removed: usize,
+        after: &str,
+    ) {
+        let dir = scratch_dir(name);
+        let path = dir.join("history");
+        fs::write(&path, before).expect("write history");
+
+        let removal = remove_entries(&path, format, &|command| command == target).expect("remove");
+        assert_eq!(removal.removed, removed);
+        assert_eq!(fs::read_to_string(&path).expect("read history"), after);
+        assert_eq!(removal.backups.len(), 1);
+        assert_eq!(
+            fs::read_to_string(&removal.backups[0]).expect("backup"),
+            before
+        );
+
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+
+    #[test]
+    fn bash_drops_timestamp_with_command() {
+        check(
+            "bash-timestamps",
+            HistoryFormat::Bash,
+            "#1700000000\nls -la\n#1700000001\nexport TOKEN=abc\n#1700000002\ngit status\n",
+            "export TOKEN=abc",
+            1,
+            "#1700000000\nls -la\n#1700000002\ngit status\n",
+        );
+    }
+
+    #[test]
+    fn bash_drops_whole_heredoc() {
+        check(
+            "bash-heredoc",
+            HistoryFormat::Bash,
+            "echo before\ncat <<EOF\nsecret\nEOF\necho after\n",
+            "cat <<EOF\nsecret\nEOF",
+            1,
+            "echo before\necho after\n",
+        );
+    }
+
+    #[test]
+    fn bash_keeps_other_lines_byte_for_byte() {
+        // No trailing newline and a CRLF line stay as they were
+        check(
+            "bash-bytes",
+            HistoryFormat::Bash,
+            "ls\r\nrm -rf build\nls",
+            "rm -rf build",
+            1,
+            "ls\r\nls",
+        );
+    }
+
+    #[test]
+    fn zsh_drops_continuation_lines() {
+        check(
+            "zsh-continuation",
+            HistoryFormat::Zsh,
+            concat!(
+                ": 1700000000:0;ls\n",
+                ": 1700000001:0;curl -H token \\\\\n  https://example.com\n",
+                ": 1700000002:3;make\n",
+            ),
+            "curl -H token \\\n  https://example.com",
+            1,
+            ": 1700000000:0;ls\n: 1700000002:3;make\n",
+        );
+    }
+
+    #[test]
+    fn fish_drops_record_with_its_fields() {
+        check(
+            "fish-record",
+            HistoryFormat::Fish,
+            concat!(
+                "- cmd: ls\n  when: 1700000000\n",
+                "- cmd: vim notes.txt\n  when: 1700000001\n  paths:\n    - notes.txt\n",
+                "- cmd: make\n  when: 1700000002\n",
+            ),
+            "vim notes.txt",
+            1,
+            "- cmd: ls\n  when: 1700000000\n- cmd: make\n  when: 1700000002\n",
+        );
+    }
+
+    #[test]
+    fn every_match_is_removed() {
+        check(
+            "repeats",
+            HistoryFormat::Plain,
+            "ls\npwd\nls\n",
+            "ls",
+            2,
+            "pwd\n",
+        );
+    }
+
+    #[test]
+    fn untouched_without_matches() {
+        let dir = scratch_dir("no-match");
+        let path = dir.join("history");
+        fs::write(&path, "ls\npwd\n").expect("write history");
+
+        let removal = remove_entries(&path, HistoryFormat::Plain, &|command| command == "make")
+            .expect("remove");
+        assert_eq!(removal.removed, 0);
+        assert!(removal.backups.is_empty());
+        assert_eq!(fs::read_dir(&dir).expect("list dir").count(), 1);
+
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+
+    #[test]
+    fn every_file_is_rewritten() {
+        let dir = scratch_dir("files");
+        let other = dir.join("work_history");
+        let active = dir.join("fish_history");
+        let missing = dir.join("gone_history");
+        fs::write(
+            &other,
+            "- cmd: make\n  when: 1700000000\n- cmd: ls\n  when: 1700000001\n",
+        )
+        .expect("write history");
+        fs::write(&active, "- cmd: make\n  when: 1700000002\n").expect("write history");
+
+        let paths = [other.clone(), missing, active.clone()];
+        let removal = remove_from_files(&paths, HistoryFormat::Fish, &|command| command == "make")
+            .expect("remove");
+        assert_eq!(removal.removed, 2);
+        assert_eq!(removal.backups.len(), 2);
+        assert_eq!(
+            fs::read_to_string(&other).expect("read history"),
+            "- cmd: ls\n  when: 1700000001\n"
+        );
+        assert_eq!(fs::read_to_string(&active).expect("read history"), "");
+
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
@@ -0,0 +1,502 @@
+// search.rs
+//! Matching of history entries against the search query.
+
+use std::collections::HashMap;
+use std::fmt;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use crate::bookmark::Bookmark;
+use crate::stats::program_name;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_BOUNDARY: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// Weight of the frecency bonus relative to match quality
+const FRECENCY_WEIGHT: f64 = 8.0;
+/// Entries this many positions back count half as recent
+const RECENCY_HALF_LIFE: f64 = 200.0;
+
+/// How often a command occurs in history and where its newest occurrence is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub count: usize,
+    /// Index of the newest occurrence in the newest-first history
+    pub newest: usize,
+}
+
+impl Usage {
+    /// Count occurrences over a newest-first history
+    pub fn collect(history: &[String]) -> HashMap<String, Usage> {
+        let mut usage: HashMap<String, Usage> = HashMap::new();
+        for (index, cmd) in history.iter().enumerate() {
+            usage
+                .entry(cmd.clone())
+                .or_insert(Usage {
+                    count: 0,
+                    newest: index,
+                })
+                .count += 1;
+        }
+        usage
+    }
+
+    /// Ranking bonus combining frequency with how recently the command was last run
+    pub fn frecency_bonus(&self) -> i64 {
+        let recency = RECENCY_HALF_LIFE / (RECENCY_HALF_LIFE + self.newest as f64);
+        (FRECENCY_WEIGHT * (1.0 + self.count as f64 * recency).ln()).round() as i64
+    }
+}
+
+/// Which query language the search bar uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Fuzzy,
+    /// `*` and `?` wildcards, matched anywhere in the command
+    Glob,
+    Regex,
+}
+
+impl MatchMode {
+    /// The mode Ctrl+R switches to
+    pub fn next(self) -> Self {
+        match self {
+            MatchMode::Fuzzy => MatchMode::Glob,
+            MatchMode::Glob => MatchMode::Regex,
+            MatchMode::Regex => MatchMode::Fuzzy,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MatchMode::Fuzzy => "fuzzy",
+            MatchMode::Glob => "glob",
+            MatchMode::Regex => "regex",
+        }
+    }
+}
+
+/// Whether the query narrows the list or only marks matches in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchDisplay {
+    #[default]
+    Filter,
+    /// Keep every entry and jump between matches with n/N, like `less`
+    Highlight,
+}
+
+impl SearchDisplay {
+    /// The display Ctrl+F switches to
+    pub fn toggled(self) -> Self {
+        match self {
+            SearchDisplay::Filter => SearchDisplay::Highlight,
+            SearchDisplay::Highlight => SearchDisplay::Filter,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SearchDisplay::Filter => "filter",
+            SearchDisplay::Highlight => "highlight",
+        }
+    }
+}
+
+/// Why a query could not be compiled
+#[derive(Debug)]
+pub enum QueryError {
+    InvalidRegex,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::InvalidRegex => write!(f, "Invalid regex"),
+        }
+    }
+}
+
+impl From<regex::Error> for QueryError {
+    fn from(_: regex::Error) -> Self {
+        QueryError::InvalidRegex
+    }
+}
+
+/// Qualifiers recognized in fuzzy queries, e.g. `cmd:rsync`. Other words with
+/// a colon, like `host:22` or URLs, are searched for literally.
+const FIELDS: [&str; 3] = ["cmd", "tag", "note"];
+
+/// A query compiled for its match mode
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// Every `include` token must fuzzy-match, in any order, no `exclude`
+    /// token (written `!token`) may appear as a substring, and the program
+    /// must be each of `programs` (written `cmd:name`), and the command must
+    /// be bookmarked with each of `tags` (written `tag:name`) and a note
+    /// containing each of `notes` (written `note:text`)
+    Fuzzy {
+        include: Vec<String>,
+        exclude: Vec<String>,
+        programs: Vec<String>,
+        tags: Vec<String>,
+        notes: Vec<String>,
+        case_sensitive: bool,
+    },
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Compile `query`; fails only for invalid regex or glob patterns, since
+    /// unknown `field:value` words are searched for literally (see `FIELDS`)
+    pub fn new(mode: MatchMode, query: &str, case_sensitive: bool) -> Result<Self, QueryError> {
+        Ok(match mode {
+            MatchMode::Fuzzy => {
+                let mut include = Vec::new();
+                let mut exclude = Vec::new();
+                let mut programs = Vec::new();
+                let mut tags = Vec::new();
+                let mut notes = Vec::new();
+                for token in tokenize(query) {
+                    if let Some((field, value)) = split_field(&token) {
+                        match field {
+                            "cmd" => programs.push(fold_case(value, case_sensitive)),
+                            // Tags are stored lowercase, whatever the case setting
+                            "tag" => tags.push(value.trim_start_matches('#').to_lowercase()),
+                            _ => notes.push(fold_case(value, case_sensitive)),
+                        }
+                    } else if token.len() > 1 && token.starts_with('!') {
+                        exclude.push(fold_case(&token[1..], case_sensitive));
+                    } else {
+                        include.push(token);
+                    }
+                }
+                Matcher::Fuzzy {
+                    include,
+                    exclude,
+                    programs,
+                    tags,
+                    notes,
+                    case_sensitive,
+                }
+            }
+            MatchMode::Glob => Matcher::Regex(
+                RegexBuilder::new(&glob_to_regex(query))
+                    .case_insensitive(!case_sensitive)
+                    .build()?,
+            ),
+            MatchMode::Regex => Matcher::Regex(
+                RegexBuilder::new(query)
+                    .case_insensitive(!case_sensitive)
+                    .build()?,
+            ),
+        })
+    }
+
+    /// Relevance of `candidate`, saved as `bookmark` if it is one, or `None` when
+    /// it does not match. Regex and glob matches all score the same, keeping
+    /// history order.
+    pub fn score(&self, candidate: &str, bookmark: Option<&Bookmark>) -> Option<i64> {
+        match self {
+            Matcher::Fuzzy {
+                include,
+                exclude,
+                programs,
+                tags,
+                notes,
+                case_sensitive,
+            } => {
+                if !tags.is_empty() || !notes.is_empty() {
+                    let bookmark = bookmark?;
+                    if tags.iter().any(|tag| !bookmark.tags.contains(tag)) {
+                        return None;
+                    }
+                    let note = fold_case(
+                        bookmark.note.as_deref().unwrap_or_default(),
+                        *case_sensitive,
+                    );
+                    if notes.iter().any(|text| !note.contains(text.as_str())) {
+                        return None;
+                    }
+                }
+                if !programs.is_empty() {
+                    let program = fold_case(program_name(candidate)?, *case_sensitive);
+                    if programs.iter().any(|wanted| *wanted != program) {
+                        return None;
+                    }
+                }
+                if !exclude.is_empty() {
+                    let folded = fold_case(candidate, *case_sensitive);
+                    if exclude.iter().any(|token| folded.contains(token.as_str())) {
+                        return None;
+                    }
+                }
+                include
+                    .iter()
+                    .map(|token| fuzzy_score(token, candidate, *case_sensitive))
+                    .sum()
+            }
+            Matcher::Regex(re) => re.is_match(candidate).then_some(0),
+        }
+    }
+}
+
+/// Split `field:value` for one of the known `FIELDS`
+fn split_field(token: &str) -> Option<(&str, &str)> {
+    let (field, value) = token.split_once(':')?;
+    (FIELDS.contains(&field) && !value.is_empty()).then_some((field, value))
+}
+
+/// Translate a glob (`*` any run, `?` any one character) into an unanchored regex
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::new();
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            _ => pattern.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    pattern
+}
+
+fn fold_case(text: &str, case_sensitive: bool) -> String {
+    if case_sensitive {
+        text.to_string()
+    } else {
+        text.to_lowercase()
+    }
+}
+
+/// Split a query on whitespace, keeping `"quoted phrases"` together
+pub fn tokenize(query: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// How letter case is treated when matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseMode {
+    /// Case-insensitive unless the query contains an uppercase letter
+    #[default]
+    Smart,
+    Insensitive,
+    Sensitive,
+}
+
+impl CaseMode {
+    pub fn is_sensitive(self, query: &str) -> bool {
+        match self {
+            CaseMode::Smart => query.chars().any(char::is_uppercase),
+            CaseMode::Insensitive => false,
+            CaseMode::Sensitive => true,
+        }
+    }
+}
+
+/// Score `candidate` as a fuzzy subsequence match of `query`; higher is better.
+///
+/// Finds the leftmost match first, then walks back from its end to find the
+/// tightest window (like fzf's v1 algorithm), so `gcmsg` matches
+/// `git commit -m "msg"` with its characters anchored on word starts.
+pub fn fuzzy_score(query: &str, candidate: &str, case_sensitive: bool) -> Option<i64> {
+    let fold = |c: char| {
+        if case_sensitive {
+            c
+        } else {
+            c.to_lowercase().next().unwrap_or(c)
+        }
+    };
+
+    let query: Vec<char> = query.chars().map(fold).collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let chars: Vec<char> = candidate.chars().map(fold).collect();
+
+    // Forward pass: where does the leftmost full match end?
+    let mut qi = 0;
+    let mut end = None;
+    for (i, c) in chars.iter().enumerate() {
+        if *c == query[qi] {
+            qi += 1;
+            if qi == query.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward pass: tighten the start of the window
+    let mut qi = query.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if chars[i] == query[qi - 1] {
+            qi -= 1;
+            if qi == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    // Score the greedy match inside the window
+    let mut score = 0;
+    let mut qi = 0;
+    let mut previous: Option<usize> = None;
+    for i in start..=end {
+        if qi == query.len() {
+            break;
+        }
+        if chars[i] != query[qi] {
+            continue;
+        }
+
+        score += SCORE_MATCH;
+        if i == 0 || is_boundary(chars[i - 1]) {
+            score += BONUS_BOUNDARY;
+        }
+        match previous {
+            Some(p) if p + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(p) => {
+                score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (i - p - 2) as i64;
+            }
+            None => {}
+        }
+        previous = Some(i);
+        qi += 1;
+    }
+
+    Some(score)
+}
+
+fn is_boundary(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '-' | '_' | '.' | '/' | '=' | ':' | '"' | '\'' | '|' | ';'
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fuzzy(query: &str) -> Matcher {
+        Matcher::new(MatchMode::Fuzzy, query, false).expect("fuzzy queries compile")
+    }
+
+    fn bookmark(tags: &[&str], note: Option<&str>) -> Bookmark {
+        Bookmark {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            note: note.map(str::to_string),
+            ..Bookmark::new("git push")
+        }
+    }
+
+    #[test]
+    fn fuzzy_matches_initials() {
+        let score = fuzzy_score("gcmsg", "git commit -m \"msg\"", false).expect("match");
+        let scattered = fuzzy_score("gcmsg", "xgxcxmxsxg", false).expect("match");
+        assert!(score > scattered, "{} <= {}", score, scattered);
+        assert_eq!(fuzzy_score("gcmsg", "git status", false), None);
+        assert_eq!(fuzzy_score("", "anything", false), Some(0));
+    }
+
+    #[test]
+    fn fuzzy_prefers_word_boundaries() {
+        let boundary = fuzzy_score("b", "a-b", false).expect("match");
+        let inner = fuzzy_score("b", "ab", false).expect("match");
+        assert!(boundary > inner, "{} <= {}", boundary, inner);
+    }
+
+    #[test]
+    fn fuzzy_prefers_consecutive_matches() {
+        let consecutive = fuzzy_score("ab", "abxx", false).expect("match");
+        let gapped = fuzzy_score("ab", "axxb", false).expect("match");
+        let wider = fuzzy_score("ab", "axxxxb", false).expect("match");
+        assert!(consecutive > gapped, "{} <= {}", consecutive, gapped);
+        assert!(gapped > wider, "{} <= {}", gapped, wider);
+    }
+
+    #[test]
+    fn fuzzy_case_folding() {
+        assert_eq!(
+            fuzzy_score("GS", "git status", false),
+            fuzzy_score("gs", "git status", false)
+        );
+        assert_eq!(fuzzy_score("GS", "git status", true), None);
+        assert!(fuzzy_score("GS", "Git Status", true).is_some());
+    }
+
+    #[test]
+    fn exclude_tokens() {
+        let matcher = fuzzy("git !push");
+        assert!(matcher.score("git status", None).is_some());
+        assert_eq!(matcher.score("git push origin", None), None);
+        assert_eq!(matcher.score("git PUSH", None), None);
+    }
+
+    #[test]
+    fn program_qualifier() {
+        let matcher = fuzzy("cmd:git st");
+        assert!(matcher.score("git status", None).is_some());
+        assert_eq!(matcher.score("echo git status", None), None);
+        assert_eq!(fuzzy("cmd:git cmd:docker").score("git status", None), None);
+    }
+
+    #[test]
+    fn tag_qualifier() {
+        let matcher = fuzzy("tag:#Deploy");
+        assert!(matcher
+            .score("git push", Some(&bookmark(&["deploy", "prod"], None)))
+            .is_some());
+        assert_eq!(
+            matcher.score("git push", Some(&bookmark(&["prod"], None))),
+            None
+        );
+        assert_eq!(matcher.score("git push", None), None);
+    }
+
+    #[test]
+    fn note_qualifier() {
+        let matcher = fuzzy("note:vpn push");
+        assert!(matcher
+            .score("git push", Some(&bookmark(&[], Some("Needs VPN"))))
+            .is_some());
+        assert_eq!(
+            matcher.score("git push", Some(&bookmark(&[], Some("run first")))),
+            None
+        );
+        assert_eq!(matcher.score("git push", Some(&bookmark(&[], None))), None);
+    }
+
+    #[test]
+    fn unknown_fields_are_literal() {
+        let matcher = fuzzy("host:22");
+        assert!(matcher.score("ssh host:22", None).is_some());
+        assert_eq!(matcher.score("ssh host", None), None);
+    }
+}
@@ -0,0 +1,555 @@
+// sources.rs
+//! Where the history list comes from: the shell's own history file or an
+//! external history tool's database.
+
+use std::cmp::Reverse;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use serde::{Deserialize, Serialize};
+use crate::app::ShellType;
+use crate::config::Config;
+use crate::entry::{HistoryEntry, HistoryLine, HistoryRecord};
+use crate::profile::{Phase, StartupProfile};
+use crate::rewrite::{self, Removal};
+use crate::sqlite;
+use crate::wsl;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistorySource {
+    /// The detected shell's history file
+    #[default]
+    Shell,
+    /// Atuin's SQLite database
+    Atuin,
+    /// McFly's SQLite database
+    McFly,
+    /// Every shell with a history file, interleaved by time
+    #[serde(rename = "all")]
+    AllShells,
+    /// The config's `history_file`, in `history_format`
+    File,
+    /// Lines printed by the config's `history_command`
+    Command,
+    /// The login shell's history on `remote_host`, fetched over SSH
+    Remote,
+}
+
+/// Layout of a `history_file`, named after the shell that writes it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryFormat {
+    /// One command per line, oldest first
+    #[default]
+    Plain,
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl HistoryFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "plain" => Some(HistoryFormat::Plain),
+            "bash" => Some(HistoryFormat::Bash),
+            "zsh" => Some(HistoryFormat::Zsh),
+            "fish" => Some(HistoryFormat::Fish),
+            _ => None,
+        }
+    }
+
+    /// The format of a shell's history file, for the shells whose files can be edited
+    pub fn of_shell(shell: &ShellType) -> Option<Self> {
+        match shell {
+            ShellType::Bash => Some(HistoryFormat::Bash),
+            ShellType::Zsh => Some(HistoryFormat::Zsh),
+            ShellType::Fish => Some(HistoryFormat::Fish),
+            _ => None,
+        }
+    }
+
+    /// Entries with the lines they span, oldest first
+    pub fn records(self, lines: &[HistoryLine]) -> Vec<HistoryRecord> {
+        match self {
+            HistoryFormat::Plain => lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| !line.text.trim().is_empty())
+                .map(|(i, line)| HistoryRecord {
+                    lines: i..i + 1,
+                    entry: HistoryEntry::new(&*line.text),
+                })
+                .collect(),
+            HistoryFormat::Bash => ShellType::bash_records(lines),
+            HistoryFormat::Zsh => ShellType::zsh_records(lines),
+            HistoryFormat::Fish => ShellType::fish_records(lines),
+        }
+    }
+
+    fn parse(self, content: Vec<u8>) -> Vec<HistoryEntry> {
+        match self {
+            HistoryFormat::Plain => String::from_utf8_lossy(&content)
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(HistoryEntry::new)
+                .rev()
+                .collect(),
+            HistoryFormat::Bash => ShellType::Bash.parse_history(content),
+            HistoryFormat::Zsh => ShellType::Zsh.parse_history(content),
+            HistoryFormat::Fish => ShellType::Fish.parse_history(content),
+        }
+    }
+}
+
+impl HistorySource {
+    /// Parse the name used in the config file and on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "shell" => Some(HistorySource::Shell),
+            "atuin" => Some(HistorySource::Atuin),
+            "mcfly" => Some(HistorySource::McFly),
+            "all" => Some(HistorySource::AllShells),
+            "file" => Some(HistorySource::File),
+            "command" => Some(HistorySource::Command),
+            "remote" => Some(HistorySource::Remote),
+            _ => None,
+        }
+    }
+
+    /// The provider reading this source for `shell`
+    pub fn provider(&self, shell: &ShellType, config: &Config) -> Box<dyn HistoryProvider> {
+        match self {
+            HistorySource::Shell => Box::new(ShellHistory(shell.clone())),
+            HistorySource::Atuin => Box::new(SqliteHistory {
+                name: "Atuin",
+                path: data_dir().join("atuin").join("history.db"),
+                // Atuin stores nanoseconds, and a duration of -1 when unknown
+                query: "SELECT hex(json_object('command', command, 'timestamp', timestamp / 1000000000, \
+                        'duration', CASE WHEN duration >= 0 THEN duration / 1000000000 END, \
+                        'exit', exit, 'cwd', cwd)) \
+                        FROM history WHERE deleted_at IS NULL ORDER BY timestamp DESC",
+            }),
+            HistorySource::McFly => Box::new(SqliteHistory {
+                name: "McFly",
+                path: mcfly_dir().join("history.db"),
+                query: "SELECT hex(json_object('command', cmd, 'timestamp', when_run, 'exit', exit_code, 'cwd', dir)) \
+                        FROM commands ORDER BY when_run DESC, id DESC",
+            }),
+            HistorySource::AllShells => Box::new(AllShells {
+                current: shell.clone(),
+                wsl: config.wsl_history,
+            }),
+            HistorySource::File => Box::new(FileHistory {
+                path: config.history_file.as_deref().map(expand_home),
+                format: config.history_format,
+            }),
+            HistorySource::Command => Box::new(CommandHistory(config.history_command.clone())),
+            HistorySource::Remote => Box::new(RemoteHistory(config.remote_host.clone())),
+        }
+    }
+}
+
+/// One way of getting a history list. Implement this to add a source; it only
+/// has to be added to `HistorySource::provider` to become selectable.
+pub trait HistoryProvider: Send {
+    /// Name shown in the empty state, e.g. "Bash" or "Atuin"
+    fn label(&self) -> String;
+
+    /// Where the history is read from, shown in the empty state
+    fn location(&self) -> PathBuf;
+
+    /// Read every entry, newest first, recording phases in `timings`
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>>;
+
+    /// Whether entries can carry timestamps, given the right shell settings
+    fn records_timestamps(&self) -> bool {
+        false
+    }
+
+    /// Files whose changes mean there are new entries; empty when the source
+    /// can't be watched and has to be reloaded by hand
+    fn watched_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Delete every entry whose command `matches` from where the history is
+    /// stored, backing it up first
+    fn remove_entries(&self, _matches: &dyn Fn(&str) -> bool) -> io::Result<Removal> {
+        Err(not_editable(&self.label()))
+    }
+}
+
+fn not_editable(label: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("deleting from {} history is not supported", label),
+    )
+}
+
+/// A shell's own history file
+pub struct ShellHistory(pub ShellType);
+
+impl HistoryProvider for ShellHistory {
+    fn label(&self) -> String {
+        format!("{:?}", self.0)
+    }
+
+    fn location(&self) -> PathBuf {
+        self.0.history_path()
+    }
+
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let content = StartupProfile::time(timings, "read history", || self.0.read_history())?;
+        Ok(StartupProfile::time(timings, "parse history", || {
+            self.0.parse_history(content)
+        }))
+    }
+
+    fn records_timestamps(&self) -> bool {
+        matches!(
+            self.0,
+            ShellType::Zsh | ShellType::Bash | ShellType::Fish | ShellType::Tcsh | ShellType::Xonsh
+        )
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        vec![self.location()]
+    }
+
+    fn remove_entries(&self, matches: &dyn Fn(&str) -> bool) -> io::Result<Removal> {
+        match HistoryFormat::of_shell(&self.0) {
+            // Entries of fish's other sessions are listed too, so they go as well
+            Some(HistoryFormat::Fish) => rewrite::remove_from_files(
+                &ShellType::fish_session_files(&self.location()),
+                HistoryFormat::Fish,
+                matches,
+            ),
+            Some(format) => rewrite::remove_entries(&self.location(), format, matches),
+            None => Err(not_editable(&self.label())),
+        }
+    }
+}
+
+/// A history tool's SQLite database, queried for the commands and what was
+/// recorded about each run
+struct SqliteHistory {
+    name: &'static str,
+    path: PathBuf,
+    /// Selects rows for `sqlite::query_history`, newest first
+    query: &'static str,
+}
+
+impl HistoryProvider for SqliteHistory {
+    fn label(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn location(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let what = format!("{} database", self.name);
+        StartupProfile::time(timings, "read history", || {
+            sqlite::query_history(&self.path, self.query, &what)
+        })
+    }
+
+    /// SQLite writes new rows to the write-ahead log first
+    fn watched_files(&self) -> Vec<PathBuf> {
+        let mut wal = self.path.clone().into_os_string();
+        wal.push("-wal");
+        vec![self.path.clone(), PathBuf::from(wal)]
+    }
+}
+
+/// Every shell with a history file merged, see `load`
+struct AllShells {
+    current: ShellType,
+    /// Also read the other side of WSL
+    wsl: bool,
+}
+
+impl HistoryProvider for AllShells {
+    fn label(&self) -> String {
+        "all shells".to_string()
+    }
+
+    fn location(&self) -> PathBuf {
+        self.current.history_path()
+    }
+
+    /// Merge the history of the current shell and every other shell that has
+    /// a history file, labelling each entry with its shell. Entries without a
+    /// timestamp go after the timed ones, keeping their shell's order.
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let mut entries = Vec::new();
+        let mut current_error = None;
+        for shell in ShellType::known() {
+            let is_current = shell == self.current;
+            let provider = ShellHistory(shell);
+            if !is_current && !provider.location().exists() {
+                continue;
+            }
+            match provider.load(timings) {
+                Ok(found) => entries.extend(found.into_iter().map(|entry| HistoryEntry {
+                    origin: Some(provider.0.name().to_string()),
+                    ..entry
+                })),
+                Err(e) if is_current => current_error = Some(e),
+                Err(_) => {}
+            }
+        }
+
+        if self.wsl {
+            StartupProfile::time(timings, "read WSL history", || {
+                for history in wsl::other_side() {
+                    let Ok(found) = history.load(&mut Vec::new()) else {
+                        continue;
+                    };
+                    entries.extend(found.into_iter().map(|entry| HistoryEntry {
+                        origin: Some(history.label.clone()),
+                        ..entry
+                    }));
+                }
+            });
+        }
+
+        match current_error {
+            Some(e) if entries.is_empty() => Err(e),
+            _ => {
+                entries.sort_by_key(|entry| Reverse(entry.timestamp));
+                Ok(entries)
+            }
+        }
+    }
+
+    fn records_timestamps(&self) -> bool {
+        ShellHistory(self.current.clone()).records_timestamps()
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = ShellType::known()
+            .into_iter()
+            .map(|shell| shell.history_path())
+            .collect();
+        files.push(self.location());
+        files
+    }
+}
+
+/// The config's `history_file`
+struct FileHistory {
+    path: Option<PathBuf>,
+    format: HistoryFormat,
+}
+
+impl HistoryProvider for FileHistory {
+    fn label(&self) -> String {
+        "history file".to_string()
+    }
+
+    fn location(&self) -> PathBuf {
+        self.path.clone().unwrap_or_default()
+    }
+
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let Some(path) = &self.path else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "history_source is \"file\" but no history_file is set",
+            ));
+        };
+        let content = StartupProfile::time(timings, "read history", || fs::read(path))?;
+        Ok(StartupProfile::time(timings, "parse history", || {
+            self.format.parse(content)
+        }))
+    }
+
+    fn records_timestamps(&self) -> bool {
+        self.format != HistoryFormat::Plain
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        self.path.iter().cloned().collect()
+    }
+
+    fn remove_entries(&self, matches: &dyn Fn(&str) -> bool) -> io::Result<Removal> {
+        match &self.path {
+            Some(path) => rewrite::remove_entries(path, self.format, matches),
+            None => Err(not_editable(&self.label())),
+        }
+    }
+}
+
+/// Any command printing one history entry per line, oldest first like a
+/// history file, e.g. `atuin history list --cmd-only`
+struct CommandHistory(Option<String>);
+
+impl HistoryProvider for CommandHistory {
+    fn label(&self) -> String {
+        "history command".to_string()
+    }
+
+    /// The command itself, as there is no file
+    fn location(&self) -> PathBuf {
+        self.0.as_deref().map(PathBuf::from).unwrap_or_default()
+    }
+
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let Some(command) = &self.0 else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "history_source is \"command\" but no history_command is set",
+            ));
+        };
+
+        let (shell, flag) = if cfg!(target_os = "windows") {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let output = StartupProfile::time(timings, "read history", || {
+            process::Command::new(shell)
+                .args([flag, command])
+                .stdin(process::Stdio::null())
+                .output()
+        })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = match stderr.lines().next() {
+                Some(line) => format!(": {}", line),
+                None => String::new(),
+            };
+            return Err(io::Error::other(format!(
+                "`{}` failed ({}){}",
+                command, output.status, reason
+            )));
+        }
+
+        Ok(StartupProfile::time(timings, "parse history", || {
+            HistoryFormat::Plain.parse(output.stdout)
+        }))
+    }
+}
+
+/// Prints the remote login shell's name, then its history file. It is run by
+/// that login shell, so it must parse the same in sh, zsh and fish: no `'`,
+/// `\` or `$(...)` outside the single-quoted script.
+const REMOTE_SCRIPT: &str = "sh -c '\
+    s=${SHELL##*/}; echo \"$s\"; \
+    case $s in \
+        zsh) f=${HISTFILE:-$HOME/.zsh_history} ;; \
+        fish) f=${XDG_DATA_HOME:-$HOME/.local/share}/fish/fish_history ;; \
+        *) f=${HISTFILE:-$HOME/.bash_history} ;; \
+    esac; \
+    cat \"$f\"'";
+
+/// History of the login shell on an SSH host, e.g. `user@server`
+struct RemoteHistory(Option<String>);
+
+impl HistoryProvider for RemoteHistory {
+    fn label(&self) -> String {
+        "remote history".to_string()
+    }
+
+    fn location(&self) -> PathBuf {
+        self.0.as_deref().map(PathBuf::from).unwrap_or_default()
+    }
+
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let Some(host) = &self.0 else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "history_source is \"remote\" but no remote_host is set",
+            ));
+        };
+
+        // The TUI owns the terminal, so ssh must not prompt for a password
+        let output = StartupProfile::time(timings, "read history", || {
+            process::Command::new("ssh")
+                .args([
+                    "-o",
+                    "BatchMode=yes",
+                    "-o",
+                    "ConnectTimeout=10",
+                    "--",
+                    host,
+                    REMOTE_SCRIPT,
+                ])
+                .stdin(process::Stdio::null())
+                .output()
+        })
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => {
+                io::Error::new(e.kind(), "browsing remote history needs the ssh command")
+            }
+            _ => e,
+        })?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .find(|line| !line.trim().is_empty())
+                .unwrap_or("ssh failed");
+            return Err(io::Error::other(format!(
+                "{} (key-based login is needed, as there is no password prompt)",
+                reason.trim()
+            )));
+        }
+
+        let mut content = output.stdout;
+        let newline = content
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .unwrap_or(content.len());
+        let name = String::from_utf8_lossy(&content[..newline])
+            .trim()
+            .to_string();
+        content.drain(..(newline + 1).min(content.len()));
+        let shell = ShellType::from_name(&name)
+            .filter(|shell| matches!(shell, ShellType::Zsh | ShellType::Fish));
+        Ok(StartupProfile::time(timings, "parse history", || {
+            shell.unwrap_or(ShellType::Bash).parse_history(content)
+        }))
+    }
+
+    fn records_timestamps(&self) -> bool {
+        true
+    }
+}
+
+/// `~/synced/.zsh_history` relative to the home directory, like the shell would
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), directories::BaseDirs::new()) {
+        (Ok(rest), Some(dirs)) => dirs.home_dir().join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// $XDG_DATA_HOME, or ~/.local/share, which Atuin also uses on macOS
+fn data_dir() -> PathBuf {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| directories::BaseDirs::new().map(|dirs| dirs.home_dir().join(".local/share")))
+        .unwrap_or_default()
+}
+
+/// McFly keeps using ~/.mcfly when it exists from older versions
+fn mcfly_dir() -> PathBuf {
+    let Some(dirs) = directories::BaseDirs::new() else {
+        return PathBuf::new();
+    };
+    let legacy = dirs.home_dir().join(".mcfly");
+    if legacy.is_dir() {
+        legacy
+    } else if cfg!(target_os = "macos") {
+        dirs.data_dir().join("McFly")
+    } else {
+        data_dir().join("mcfly")
+    }
+}
@@ -0,0 +1,125 @@
+// sqlite.rs
+//! Reads text columns from SQLite history databases (Nushell, Atuin), and
+//! writes the SQLite bookmark store, through the sqlite3 CLI, so no SQLite
+//! library has to be linked in.
+
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use serde::Deserialize;
+use crate::entry::HistoryEntry;
+
+/// A history row as selected by `query_history`, with times in seconds
+#[derive(Deserialize)]
+pub struct HistoryRow {
+    command: String,
+    #[serde(default)]
+    timestamp: Option<i64>,
+    #[serde(default)]
+    duration: Option<i64>,
+    #[serde(default)]
+    exit: Option<i64>,
+    #[serde(default)]
+    cwd: Option<String>,
+}
+
+impl From<HistoryRow> for HistoryEntry {
+    fn from(row: HistoryRow) -> Self {
+        HistoryEntry {
+            timestamp: row.timestamp.and_then(|t| u64::try_from(t).ok()),
+            duration: row.duration.and_then(|d| u64::try_from(d).ok()),
+            exit_status: row.exit,
+            cwd: row.cwd.filter(|cwd| !cwd.is_empty()),
+            ..HistoryEntry::new(row.command)
+        }
+    }
+}
+
+/// Run `query`, which must select `hex(json_object('command', ...,
+/// 'timestamp', ..., 'duration', ..., 'exit', ..., 'cwd', ...))` with the
+/// times in seconds; any but `command` may be left out or NULL
+pub fn query_history(path: &Path, query: &str, what: &str) -> io::Result<Vec<HistoryEntry>> {
+    Ok(query_hex_column(path, query, what)?
+        .iter()
+        .filter_map(|row| serde_json::from_str::<HistoryRow>(row).ok())
+        .map(HistoryEntry::from)
+        .collect())
+}
+
+/// Run `query` read-only against the database at `path`. The query must select
+/// a single `hex(...)` column so values with newlines come back one per line.
+pub fn query_hex_column(path: &Path, query: &str, what: &str) -> io::Result<Vec<String>> {
+    let output = Command::new("sqlite3")
+        .arg("-readonly")
+        .arg(path)
+        .arg(query)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| missing_sqlite3(e, "reading", what))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "sqlite3 could not read the {}: {}",
+            what,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| decode_hex(line.trim()))
+        .map(|value| String::from_utf8_lossy(&value).into_owned())
+        .collect())
+}
+
+/// Run `script` against the database at `path`, creating it if needed. It
+/// stops at the first failing statement, so a script wrapped in a transaction
+/// changes all or nothing.
+pub fn execute(path: &Path, script: &str, what: &str) -> io::Result<()> {
+    let mut child = Command::new("sqlite3")
+        .arg("-bail")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| missing_sqlite3(e, "writing", what))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(script.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "sqlite3 could not write the {}: {}",
+            what,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+fn missing_sqlite3(e: io::Error, doing: &str, what: &str) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            e.kind(),
+            format!("{} the {} needs the sqlite3 command", doing, what),
+        ),
+        _ => e,
+    }
+}
+
+/// `bytes` as hex digits, e.g. for an `X'...'` literal
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,56 @@
+// stats.rs
+//! Usage statistics over a loaded history list.
+
+use std::collections::HashMap;
+
+/// How many rows the "top" tables keep
+const TOP_N: usize = 10;
+
+#[derive(Debug, Clone, Default)]
+pub struct HistoryStats {
+    pub total: usize,
+    pub unique: usize,
+    pub top_commands: Vec<(String, usize)>,
+    pub top_programs: Vec<(String, usize)>,
+}
+
+impl HistoryStats {
+    pub fn compute(history: &[String]) -> Self {
+        let mut commands: HashMap<&str, usize> = HashMap::new();
+        let mut programs: HashMap<&str, usize> = HashMap::new();
+
+        for cmd in history
+            .iter()
+            .map(|cmd| cmd.trim())
+            .filter(|cmd| !cmd.is_empty())
+        {
+            *commands.entry(cmd).or_default() += 1;
+            if let Some(program) = program_name(cmd) {
+                *programs.entry(program).or_default() += 1;
+            }
+        }
+
+        Self {
+            total: history.len(),
+            unique: commands.len(),
+            top_commands: top(commands),
+            top_programs: top(programs),
+        }
+    }
+}
+
+/// The executable a command line runs, skipping leading `VAR=value` assignments
+pub fn program_name(cmd: &str) -> Option<&str> {
+    cmd.split_whitespace().find(|token| !token.contains('='))
+}
+
+fn top(counts: HashMap<&str, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(key, count)| (key.to_string(), count))
+        .collect();
+    // Ties are broken alphabetically so reports are stable between runs
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(TOP_N);
+    counts
+}
@@ -0,0 +1,612 @@
+// storage.rs
+//! Where bookmarks are kept: one file in term-kit's data directory, in the
+//! format picked with `bookmark_format`. Bookmarks saved in another format,
+//! or by older versions in `~/.term_kit_bookmarks`, move over at startup,
+//! as do the presets and recent searches older versions kept in the home
+//! directory.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::bookmark::{self, Bookmark};
+use crate::sqlite;
+use crate::toml;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookmarkFormat {
+    #[default]
+    Json,
+    /// Diff-friendly and easy to edit by hand, e.g. in a dotfiles repo
+    Toml,
+    /// A database updated row by row, for large collections or querying the
+    /// bookmarks with other tools; needs the sqlite3 command
+    Sqlite,
+}
+
+impl BookmarkFormat {
+    const ALL: [BookmarkFormat; 3] = [
+        BookmarkFormat::Json,
+        BookmarkFormat::Toml,
+        BookmarkFormat::Sqlite,
+    ];
+
+    fn file_name(self) -> &'static str {
+        match self {
+            BookmarkFormat::Json => "bookmarks.json",
+            BookmarkFormat::Toml => "bookmarks.toml",
+            BookmarkFormat::Sqlite => "bookmarks.db",
+        }
+    }
+
+    fn open(self, path: PathBuf) -> Box<dyn BookmarkStore> {
+        match self {
+            BookmarkFormat::Json => Box::new(JsonStore(path)),
+            BookmarkFormat::Toml => Box::new(TomlStore(path)),
+            BookmarkFormat::Sqlite => Box::new(SqliteStore(path)),
+        }
+    }
+
+    /// The store for this format, in `data_dir()`. Until `migrate` moved them
+    /// there, bookmarks kept in another format or by older versions are used
+    /// where they are.
+    pub fn store(self) -> Box<dyn BookmarkStore> {
+        let Some(dir) = data_dir() else {
+            return Box::new(JsonStore(legacy_path(".term_kit_bookmarks")));
+        };
+        let store = self.open(dir.join(self.file_name()));
+        if store.location().exists() {
+            return store;
+        }
+        self.old_store(&dir).unwrap_or(store)
+    }
+
+    /// Move bookmarks kept in another format, or by older versions, into this
+    /// format's file. Done once at startup: `store` keeps using the old file
+    /// if this fails.
+    pub fn migrate(self) -> io::Result<()> {
+        let Some(dir) = data_dir() else {
+            return Ok(());
+        };
+        let store = self.open(dir.join(self.file_name()));
+        if store.location().exists() {
+            return Ok(());
+        }
+        let Some(old) = self.old_store(&dir) else {
+            return Ok(());
+        };
+        migrate(old.as_ref(), store.as_ref()).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to move {} to {}: {}",
+                    old.location().display(),
+                    store.location().display(),
+                    e
+                ),
+            )
+        })
+    }
+
+    /// The first existing file of another format, or of older versions
+    fn old_store(self, dir: &Path) -> Option<Box<dyn BookmarkStore>> {
+        Self::ALL
+            .into_iter()
+            .filter(|format| *format != self)
+            .map(|format| format.open(dir.join(format.file_name())))
+            .chain([
+                Box::new(JsonStore(legacy_path(".term_kit_bookmarks"))) as Box<dyn BookmarkStore>
+            ])
+            .find(|old| old.location().exists())
+    }
+}
+
+/// $XDG_DATA_HOME/term-kit, ~/Library/Application Support/term-kit or
+/// %APPDATA%\term-kit\data
+fn data_dir() -> Option<PathBuf> {
+    directories::ProjectDirs::from("", "", "term-kit").map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Where older versions kept `name`, in the home directory
+fn legacy_path(name: &str) -> PathBuf {
+    directories::BaseDirs::new()
+        .expect("Failed to determine user home directory")
+        .home_dir()
+        .join(name)
+}
+
+/// A file kept in `data_dir()`, which older versions kept in the home
+/// directory as `legacy`
+pub struct DataFile {
+    pub name: &'static str,
+    pub legacy: &'static str,
+}
+
+impl DataFile {
+    /// Where the file is: in the home directory until `migrate` moved it
+    pub fn path(&self) -> PathBuf {
+        let legacy = legacy_path(self.legacy);
+        let Some(dir) = data_dir() else {
+            return legacy;
+        };
+        let path = dir.join(self.name);
+        if !path.exists() && legacy.exists() {
+            legacy
+        } else {
+            path
+        }
+    }
+
+    /// Move the file into `data_dir()`, which is created so the file can be
+    /// written right away. Done once at startup: `path` keeps pointing to the
+    /// home directory if this fails.
+    pub fn migrate(&self) -> io::Result<()> {
+        let legacy = legacy_path(self.legacy);
+        let Some(dir) = data_dir() else {
+            return Ok(());
+        };
+        let path = dir.join(self.name);
+        if path.exists() || !legacy.exists() {
+            return fs::create_dir_all(&dir).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Failed to create {}: {}", dir.display(), e),
+                )
+            });
+        }
+        fs::create_dir_all(&dir)
+            .and_then(|()| fs::copy(&legacy, &path))
+            .and_then(|_| fs::remove_file(&legacy))
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "Failed to move {} to {}: {}",
+                        legacy.display(),
+                        path.display(),
+                        e
+                    ),
+                )
+            })
+    }
+}
+
+/// Copy the bookmarks of `from` into `to`, then remove `from`
+fn migrate(from: &dyn BookmarkStore, to: &dyn BookmarkStore) -> io::Result<()> {
+    let (version, bookmarks) = from.load()?;
+    if version > bookmark::FILE_VERSION {
+        return Err(io::Error::other(format!(
+            "it was saved by a newer term-kit (format {})",
+            version
+        )));
+    }
+    to.save(&bookmarks)?;
+    fs::remove_file(from.location())
+}
+
+/// One way of keeping bookmarks. Implement this to add a format; it only has
+/// to be added to `BookmarkFormat::open` to become selectable.
+pub trait BookmarkStore {
+    /// Shown in messages and bundled by `term-kit backup`
+    fn location(&self) -> &Path;
+
+    /// The format version and bookmarks; nothing saved yet has none. Files
+    /// that can't be read are an error rather than empty, so they are never
+    /// saved over.
+    fn load(&self) -> io::Result<(u32, Vec<Bookmark>)>;
+
+    fn save(&self, bookmarks: &[Bookmark]) -> io::Result<()>;
+
+    /// `save` over `saved`, the bookmarks the store holds now, for stores that
+    /// only write what changed and would otherwise load them again first
+    fn save_over(&self, bookmarks: &[Bookmark], _saved: &[Bookmark]) -> io::Result<()> {
+        self.save(bookmarks)
+    }
+}
+
+/// Contents of `path`, or `None` when there is no such file
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_creating_dir(path: &Path, content: String) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)
+}
+
+fn invalid_data(e: impl ToString) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+struct JsonStore(PathBuf);
+
+impl BookmarkStore for JsonStore {
+    fn location(&self) -> &Path {
+        &self.0
+    }
+
+    fn load(&self) -> io::Result<(u32, Vec<Bookmark>)> {
+        match read_optional(&self.0)? {
+            Some(content) => bookmark::parse_file(&content).map_err(invalid_data),
+            None => Ok((bookmark::FILE_VERSION, Vec::new())),
+        }
+    }
+
+    fn save(&self, bookmarks: &[Bookmark]) -> io::Result<()> {
+        write_creating_dir(
+            &self.0,
+            bookmark::write_file(bookmarks).map_err(invalid_data)?,
+        )
+    }
+}
+
+/// `version = 2` followed by a `[[bookmarks]]` table per bookmark
+struct TomlStore(PathBuf);
+
+impl BookmarkStore for TomlStore {
+    fn location(&self) -> &Path {
+        &self.0
+    }
+
+    fn load(&self) -> io::Result<(u32, Vec<Bookmark>)> {
+        let Some(content) = read_optional(&self.0)? else {
+            return Ok((bookmark::FILE_VERSION, Vec::new()));
+        };
+        let mut document = toml::from_str(&content).map_err(invalid_data)?;
+        // A file without bookmarks has no [[bookmarks]] tables at all
+        if let Some(root) = document.as_object_mut() {
+            root.entry("bookmarks")
+                .or_insert_with(|| Value::Array(Vec::new()));
+        }
+        bookmark::parse_value(document).map_err(invalid_data)
+    }
+
+    fn save(&self, bookmarks: &[Bookmark]) -> io::Result<()> {
+        let tables = bookmarks
+            .iter()
+            .map(|bookmark| match serde_json::to_value(bookmark)? {
+                // Bare commands get a table of their own like any other bookmark
+                Value::String(command) => Ok(Value::Object(Map::from_iter([(
+                    "command".to_string(),
+                    Value::String(command),
+                )]))),
+                table => Ok(table),
+            })
+            .collect::<serde_json::Result<Vec<Value>>>()
+            .map_err(invalid_data)?;
+        let root = Map::from_iter([
+            ("version".to_string(), Value::from(bookmark::FILE_VERSION)),
+            ("bookmarks".to_string(), Value::Array(tables)),
+        ]);
+        write_creating_dir(&self.0, toml::to_string(&root))
+    }
+}
+
+const SQLITE_SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE IF NOT EXISTS bookmarks (
+    command TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    title TEXT,
+    note TEXT,
+    group_name TEXT,
+    pinned INTEGER NOT NULL DEFAULT 0,
+    trashed_at INTEGER,
+    uses INTEGER NOT NULL DEFAULT 0,
+    last_used INTEGER,
+    extra TEXT
+);
+CREATE TABLE IF NOT EXISTS bookmark_tags (
+    command TEXT NOT NULL,
+    tag TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (command, tag)
+);
+CREATE INDEX IF NOT EXISTS bookmark_tags_by_tag ON bookmark_tags (tag);
+";
+
+/// The format version (a blank row if missing), then each bookmark as the JSON
+/// of the other formats, so loading shares their parsing
+const SQLITE_LOAD: &str =
+    "SELECT hex(coalesce((SELECT value FROM meta WHERE key = 'version'), ''));\
+    SELECT hex(json_patch(json_object(\
+    'command', command, 'title', title, 'note', note, 'group', group_name, \
+    'pinned', json(CASE WHEN pinned THEN 'true' ELSE 'false' END), \
+    'trashed_at', trashed_at, 'uses', uses, 'last_used', last_used, \
+    'tags', json((SELECT json_group_array(tag) FROM \
+        (SELECT tag FROM bookmark_tags t WHERE t.command = b.command ORDER BY position)))\
+    ), coalesce(extra, '{}'))) FROM bookmarks b ORDER BY position";
+
+/// A `bookmarks` row per bookmark, its tags in `bookmark_tags` and the format
+/// version in `meta`. Saving only touches the rows that changed.
+struct SqliteStore(PathBuf);
+
+impl SqliteStore {
+    const WHAT: &'static str = "bookmark database";
+}
+
+impl BookmarkStore for SqliteStore {
+    fn location(&self) -> &Path {
+        &self.0
+    }
+
+    fn load(&self) -> io::Result<(u32, Vec<Bookmark>)> {
+        if !self.0.exists() {
+            return Ok((bookmark::FILE_VERSION, Vec::new()));
+        }
+        let mut rows = sqlite::query_hex_column(&self.0, SQLITE_LOAD, Self::WHAT)?.into_iter();
+        let version = rows
+            .next()
+            .and_then(|version| version.parse().ok())
+            .ok_or_else(|| invalid_data("the bookmark database has no version"))?;
+        let bookmarks = rows
+            .map(|row| serde_json::from_str(&row))
+            .collect::<serde_json::Result<Vec<Bookmark>>>()
+            .map_err(invalid_data)?;
+        Ok((version, bookmarks))
+    }
+
+    fn save(&self, bookmarks: &[Bookmark]) -> io::Result<()> {
+        let (_, saved) = self.load()?;
+        self.save_over(bookmarks, &saved)
+    }
+
+    fn save_over(&self, bookmarks: &[Bookmark], saved: &[Bookmark]) -> io::Result<()> {
+        let saved: HashMap<&str, (usize, &Bookmark)> = saved
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (b.command.as_str(), (i, b)))
+            .collect();
+
+        let mut changes = String::new();
+        for command in saved
+            .keys()
+            .filter(|command| !bookmarks.iter().any(|b| b.command == **command))
+        {
+            let command = sql_text(command);
+            let _ = writeln!(
+                changes,
+                "DELETE FROM bookmarks WHERE command = {};",
+                command
+            );
+            let _ = writeln!(
+                changes,
+                "DELETE FROM bookmark_tags WHERE command = {};",
+                command
+            );
+        }
+        for (position, bookmark) in bookmarks.iter().enumerate() {
+            let before = saved.get(bookmark.command.as_str());
+            if before == Some(&(position, bookmark)) {
+                continue;
+            }
+            let command = sql_text(&bookmark.command);
+            let extra = if bookmark.extra.is_empty() {
+                "NULL".to_string()
+            } else {
+                sql_text(&serde_json::to_string(&bookmark.extra).map_err(invalid_data)?)
+            };
+            let _ = writeln!(
+                changes,
+                "INSERT INTO bookmarks (command, position, title, note, group_name, pinned, trashed_at, uses, last_used, extra) \
+                 VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {}) \
+                 ON CONFLICT (command) DO UPDATE SET position = excluded.position, title = excluded.title, \
+                 note = excluded.note, group_name = excluded.group_name, pinned = excluded.pinned, \
+                 trashed_at = excluded.trashed_at, uses = excluded.uses, last_used = excluded.last_used, extra = excluded.extra;",
+                command,
+                position,
+                sql_optional(bookmark.title.as_deref()),
+                sql_optional(bookmark.note.as_deref()),
+                sql_optional(bookmark.group.as_deref()),
+                u8::from(bookmark.pinned),
+                sql_number(bookmark.trashed_at),
+                bookmark.uses,
+                sql_number(bookmark.last_used),
+                extra,
+            );
+            if before.is_some_and(|(_, before)| before.tags == bookmark.tags) {
+                continue;
+            }
+            let _ = writeln!(
+                changes,
+                "DELETE FROM bookmark_tags WHERE command = {};",
+                command
+            );
+            for (i, tag) in bookmark.tags.iter().enumerate() {
+                let _ = writeln!(
+                    changes,
+                    "INSERT INTO bookmark_tags VALUES ({}, {}, {});",
+                    command,
+                    sql_text(tag),
+                    i
+                );
+            }
+        }
+
+        if changes.is_empty() && self.0.exists() {
+            return Ok(());
+        }
+        if let Some(parent) = self.0.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let script = format!(
+            "BEGIN;\n{}INSERT OR IGNORE INTO meta VALUES ('version', '{}');\n{}COMMIT;\n",
+            SQLITE_SCHEMA,
+            bookmark::FILE_VERSION,
+            changes
+        );
+        sqlite::execute(&self.0, &script, Self::WHAT)
+    }
+}
+
+/// `text` as an SQL literal; written as hex so quotes and newlines need no escaping
+fn sql_text(text: &str) -> String {
+    format!("CAST(X'{}' AS TEXT)", sqlite::encode_hex(text.as_bytes()))
+}
+
+fn sql_optional(text: Option<&str>) -> String {
+    text.map_or_else(|| "NULL".to_string(), sql_text)
+}
+
+fn sql_number(number: Option<u64>) -> String {
+    number.map_or_else(|| "NULL".to_string(), |n| n.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// A store in a fresh temp directory, or `None` without the sqlite3
+    /// command to run it against
+    fn sqlite_store(name: &str) -> Option<SqliteStore> {
+        if Command::new("sqlite3").arg("-version").output().is_err() {
+            eprintln!("skipping, sqlite3 is not installed");
+            return None;
+        }
+        let dir =
+            std::env::temp_dir().join(format!("term-kit-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Some(SqliteStore(dir.join("bookmarks.db")))
+    }
+
+    fn clean_up(store: SqliteStore) {
+        if let Some(dir) = store.0.parent() {
+            fs::remove_dir_all(dir).expect("clean up");
+        }
+    }
+
+    /// `position` of each tag row of `command`, in tag order
+    fn tag_positions(store: &SqliteStore, command: &str) -> Vec<String> {
+        let query = format!(
+            "SELECT hex(position) FROM bookmark_tags WHERE command = {} ORDER BY position",
+            sql_text(command)
+        );
+        sqlite::query_hex_column(&store.0, &query, SqliteStore::WHAT).expect("query tags")
+    }
+
+    fn tagged(command: &str, tags: &[&str]) -> Bookmark {
+        Bookmark {
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Bookmark::new(command)
+        }
+    }
+
+    #[test]
+    fn sqlite_round_trip() {
+        let Some(store) = sqlite_store("round-trip") else {
+            return;
+        };
+        let bookmarks = vec![
+            Bookmark {
+                title: Some("it's \"quoted\"".to_string()),
+                note: Some("two\nlines".to_string()),
+                group: Some("homelab".to_string()),
+                pinned: true,
+                trashed_at: Some(1700000000),
+                uses: 7,
+                last_used: Some(1700000001),
+                extra: [("color".to_string(), Value::from("red"))].into(),
+                ..tagged("echo 'a'; echo \"b\"\necho c", &["zeta", "alpha", "mid"])
+            },
+            Bookmark::new("ls -la"),
+        ];
+
+        assert_eq!(
+            store.load().expect("load missing"),
+            (bookmark::FILE_VERSION, Vec::new())
+        );
+        store.save(&bookmarks).expect("save");
+        assert_eq!(
+            store.load().expect("load"),
+            (bookmark::FILE_VERSION, bookmarks)
+        );
+        store.save(&[]).expect("save empty");
+        assert_eq!(
+            store.load().expect("load empty"),
+            (bookmark::FILE_VERSION, Vec::new())
+        );
+        clean_up(store);
+    }
+
+    #[test]
+    fn sqlite_saves_only_changes() {
+        let Some(store) = sqlite_store("diff") else {
+            return;
+        };
+        store
+            .save(&[
+                tagged("kept", &["b", "a"]),
+                tagged("edited", &["x"]),
+                Bookmark::new("deleted"),
+            ])
+            .expect("first save");
+        // Positions a full rewrite would reset, to tell which rows were written
+        sqlite::execute(
+            &store.0,
+            "UPDATE bookmark_tags SET position = position + 10;",
+            SqliteStore::WHAT,
+        )
+        .expect("mark tags");
+
+        let bookmarks = vec![
+            Bookmark::new("inserted"),
+            Bookmark {
+                title: Some("now titled".to_string()),
+                ..tagged("edited", &["x"])
+            },
+            tagged("kept", &["b", "a"]),
+        ];
+        store.save(&bookmarks).expect("second save");
+        assert_eq!(store.load().expect("load").1, bookmarks);
+        assert_eq!(tag_positions(&store, "kept"), ["10", "11"]);
+        assert_eq!(tag_positions(&store, "edited"), ["10"]);
+        assert!(tag_positions(&store, "deleted").is_empty());
+
+        let retagged = vec![tagged("kept", &["a", "c", "b"])];
+        store.save(&retagged).expect("third save");
+        assert_eq!(store.load().expect("load retagged").1, retagged);
+        assert_eq!(tag_positions(&store, "kept"), ["0", "1", "2"]);
+        assert!(tag_positions(&store, "edited").is_empty());
+        clean_up(store);
+    }
+
+    #[test]
+    fn sqlite_errors_say_why() {
+        let Some(store) = sqlite_store("corrupt") else {
+            return;
+        };
+        write_creating_dir(&store.0, "not a database".repeat(100)).expect("write");
+        let error = store.load().expect_err("load garbage").to_string();
+        assert!(error.starts_with("sqlite3 could not read the bookmark database: "));
+        assert!(error.contains("not a database"), "{}", error);
+        clean_up(store);
+    }
+
+    #[test]
+    fn sqlite_save_over_trusts_the_given_rows() {
+        let Some(store) = sqlite_store("save-over") else {
+            return;
+        };
+        store.save(&[Bookmark::new("a")]).expect("save");
+        // Rows said to be saved already aren't written again
+        let bookmarks = [Bookmark::new("a"), Bookmark::new("b")];
+        store.save_over(&bookmarks, &bookmarks).expect("save over");
+        assert_eq!(store.load().expect("load").1, [Bookmark::new("a")]);
+        store
+            .save_over(&bookmarks, &[Bookmark::new("a")])
+            .expect("save over");
+        assert_eq!(store.load().expect("load").1, bookmarks);
+        clean_up(store);
+    }
+}
@@ -0,0 +1,402 @@
+// timefmt.rs
+//! Local calendar times for the time column, rendered with a small
+//! strftime-style formatter since only a handful of fields are ever shown.
+
+use crate::config::{DateLocale, TimeStyle, TimestampConfig};
+use crate::entry;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+const AM_PM: [&str; 2] = ["AM", "PM"];
+
+/// Commands older than this show a date instead of their age
+const RELATIVE_SPAN: u64 = 7 * 86_400;
+
+/// A timestamp broken down in the local time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub year: i64,
+    /// 1-12
+    pub month: u32,
+    /// 1-31
+    pub day: u32,
+    /// 0 is Sunday
+    pub weekday: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl LocalTime {
+    pub fn from_timestamp(timestamp: u64) -> Self {
+        Self::from_local_seconds(timestamp as i64 + utc_offset(timestamp))
+    }
+
+    /// The calendar time `local` seconds after 1970-01-01 00:00 local time
+    fn from_local_seconds(local: i64) -> Self {
+        let days = local.div_euclid(86_400);
+        let seconds = local.rem_euclid(86_400) as u32;
+
+        // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            // 1970-01-01 was a Thursday
+            weekday: (days + 4).rem_euclid(7) as u32,
+            hour: seconds / 3_600,
+            minute: seconds % 3_600 / 60,
+            second: seconds % 60,
+        }
+    }
+
+    /// Render with strftime-style directives: %Y %y %m %d %e %H %I %M %S %p
+    /// %b %B %a %A and %%; `%-d` and friends drop the zero padding. Anything
+    /// else is copied as is. Names are taken from `locale` where it has them.
+    pub fn format(&self, format: &str, locale: &DateLocale) -> String {
+        let month = self.month as usize - 1;
+        let weekday = self.weekday as usize;
+        let mut out = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            let mut directive = chars.next();
+            let padded = directive != Some('-');
+            if !padded {
+                directive = chars.next();
+            }
+            let number = |value: u32| {
+                if padded {
+                    format!("{:02}", value)
+                } else {
+                    value.to_string()
+                }
+            };
+            let hour12 = match self.hour % 12 {
+                0 => 12,
+                hour => hour,
+            };
+            match directive {
+                Some('Y') => out.push_str(&self.year.to_string()),
+                Some('y') => out.push_str(&number(self.year.rem_euclid(100) as u32)),
+                Some('m') => out.push_str(&number(self.month)),
+                Some('d') => out.push_str(&number(self.day)),
+                Some('e') => out.push_str(&format!("{:>2}", self.day)),
+                Some('H') => out.push_str(&number(self.hour)),
+                Some('I') => out.push_str(&number(hour12)),
+                Some('M') => out.push_str(&number(self.minute)),
+                Some('S') => out.push_str(&number(self.second)),
+                Some('p') => {
+                    out.push_str(name(&locale.am_pm, &AM_PM, usize::from(self.hour >= 12)))
+                }
+                Some('B') => out.push_str(name(&locale.months, &MONTHS, month)),
+                Some('b') => out.push_str(&short_name(
+                    &locale.short_months,
+                    name(&locale.months, &MONTHS, month),
+                    MONTHS.len(),
+                    month,
+                )),
+                Some('A') => out.push_str(name(&locale.weekdays, &WEEKDAYS, weekday)),
+                Some('a') => out.push_str(&short_name(
+                    &locale.short_weekdays,
+                    name(&locale.weekdays, &WEEKDAYS, weekday),
+                    WEEKDAYS.len(),
+                    weekday,
+                )),
+                Some('%') => out.push('%'),
+                Some(other) => {
+                    out.push('%');
+                    if !padded {
+                        out.push('-');
+                    }
+                    out.push(other);
+                }
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+}
+
+/// Entry `index` of the configured `names`, or of `english` unless there are
+/// as many of them
+fn name<'a>(names: &'a [String], english: &[&'a str], index: usize) -> &'a str {
+    match names.get(index) {
+        Some(name) if names.len() == english.len() => name,
+        _ => english[index],
+    }
+}
+
+/// Entry `index` of the configured abbreviations, or the first three letters
+/// of `full` unless there are `len` of them
+fn short_name(short: &[String], full: &str, len: usize, index: usize) -> String {
+    match short.get(index) {
+        Some(name) if short.len() == len => name.clone(),
+        _ => full.chars().take(3).collect(),
+    }
+}
+
+/// When a command ran, as configured: "2h ago" or "Mar 3" for the relative
+/// style, the configured format for the absolute one
+pub fn format_timestamp(timestamp: u64, config: &TimestampConfig) -> String {
+    let now = entry::now();
+    match config.style {
+        TimeStyle::Relative if now.saturating_sub(timestamp) < RELATIVE_SPAN => {
+            entry::format_age(timestamp, now)
+        }
+        TimeStyle::Relative => {
+            let time = LocalTime::from_timestamp(timestamp);
+            if time.year == LocalTime::from_timestamp(now).year {
+                time.format("%b %-d", &config.locale)
+            } else {
+                time.format("%b %-d %Y", &config.locale)
+            }
+        }
+        TimeStyle::Absolute => {
+            let default = if config.clock_12h {
+                "%Y-%m-%d %-I:%M %p"
+            } else {
+                "%Y-%m-%d %H:%M"
+            };
+            LocalTime::from_timestamp(timestamp)
+                .format(config.format.as_deref().unwrap_or(default), &config.locale)
+        }
+    }
+}
+
+/// Seconds the local time zone is ahead of UTC at `timestamp`
+#[cfg(unix)]
+fn utc_offset(timestamp: u64) -> i64 {
+    let Ok(time) = libc::time_t::try_from(timestamp) else {
+        return 0;
+    };
+    // SAFETY: localtime_r only writes to the `tm` passed in, which is a
+    // zeroed, correctly sized struct; it returns null on failure
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&time, &mut tm).is_null() {
+            return 0;
+        }
+        tm.tm_gmtoff as i64
+    }
+}
+
+/// Seconds the local time zone is currently ahead of UTC; Windows has no
+/// cheap per-date lookup, so dates across a DST change are off by an hour
+#[cfg(windows)]
+fn utc_offset(_timestamp: u64) -> i64 {
+    const TIME_ZONE_ID_DAYLIGHT: u32 = 2;
+
+    #[repr(C)]
+    struct TimeZoneInformation {
+        bias: i32,
+        standard_name: [u16; 32],
+        standard_date: [u16; 8],
+        standard_bias: i32,
+        daylight_name: [u16; 32],
+        daylight_date: [u16; 8],
+        daylight_bias: i32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTimeZoneInformation(info: *mut TimeZoneInformation) -> u32;
+    }
+
+    let mut info = TimeZoneInformation {
+        bias: 0,
+        standard_name: [0; 32],
+        standard_date: [0; 8],
+        standard_bias: 0,
+        daylight_name: [0; 32],
+        daylight_date: [0; 8],
+        daylight_bias: 0,
+    };
+    // SAFETY: `info` is a correctly laid out TIME_ZONE_INFORMATION that the
+    // call only writes to
+    let zone = unsafe { GetTimeZoneInformation(&mut info) };
+    if zone == u32::MAX {
+        return 0;
+    }
+    let bias = info.bias
+        + if zone == TIME_ZONE_ID_DAYLIGHT {
+            info.daylight_bias
+        } else {
+            info.standard_bias
+        };
+    -i64::from(bias) * 60
+}
+
+#[cfg(not(any(unix, windows)))]
+fn utc_offset(_timestamp: u64) -> i64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(
+        year: i64,
+        month: u32,
+        day: u32,
+        weekday: u32,
+        hour: u32,
+        minute: u32,
+        second: u32,
+    ) -> LocalTime {
+        LocalTime {
+            year,
+            month,
+            day,
+            weekday,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    fn format(time: LocalTime, format: &str) -> String {
+        time.format(format, &DateLocale::default())
+    }
+
+    #[test]
+    fn epoch() {
+        assert_eq!(
+            LocalTime::from_local_seconds(0),
+            time(1970, 1, 1, 4, 0, 0, 0)
+        );
+        assert_eq!(
+            LocalTime::from_local_seconds(-1),
+            time(1969, 12, 31, 3, 23, 59, 59)
+        );
+    }
+
+    #[test]
+    fn leap_days() {
+        assert_eq!(
+            LocalTime::from_local_seconds(1709210096),
+            time(2024, 2, 29, 4, 12, 34, 56)
+        );
+        // 2100 is divisible by 100 but not 400, so has no 29th
+        assert_eq!(
+            LocalTime::from_local_seconds(4107456000 + 86_400),
+            time(2100, 3, 1, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn year_boundary() {
+        assert_eq!(
+            LocalTime::from_local_seconds(946684799),
+            time(1999, 12, 31, 5, 23, 59, 59)
+        );
+        assert_eq!(
+            LocalTime::from_local_seconds(946684800),
+            time(2000, 1, 1, 6, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn padded_and_unpadded_fields() {
+        let t = time(2024, 3, 5, 2, 9, 7, 3);
+        assert_eq!(format(t, "%Y-%m-%d %H:%M:%S"), "2024-03-05 09:07:03");
+        assert_eq!(format(t, "%-d/%-m/%y %-H:%M"), "5/3/24 9:07");
+        assert_eq!(format(t, "[%e]"), "[ 5]");
+        assert_eq!(format(t, "%a %A, %b %B"), "Tue Tuesday, Mar March");
+    }
+
+    #[test]
+    fn twelve_hour_clock() {
+        assert_eq!(format(time(2024, 3, 5, 2, 0, 5, 0), "%I:%M %p"), "12:05 AM");
+        assert_eq!(format(time(2024, 3, 5, 2, 9, 5, 0), "%-I:%M %p"), "9:05 AM");
+        assert_eq!(format(time(2024, 3, 5, 2, 12, 0, 0), "%-I %p"), "12 PM");
+        assert_eq!(format(time(2024, 3, 5, 2, 23, 59, 0), "%I %p"), "11 PM");
+    }
+
+    #[test]
+    fn unknown_directives_are_copied() {
+        let t = time(2024, 3, 5, 2, 9, 7, 3);
+        assert_eq!(format(t, "%Q %-Q 100%% %"), "%Q %-Q 100% %");
+    }
+
+    #[test]
+    fn configured_names() {
+        let words = |words: &[&str]| words.iter().map(|word| word.to_string()).collect();
+        let locale = DateLocale {
+            months: words(&[
+                "Januar",
+                "Februar",
+                "März",
+                "April",
+                "Mai",
+                "Juni",
+                "Juli",
+                "August",
+                "September",
+                "Oktober",
+                "November",
+                "Dezember",
+            ]),
+            short_months: Vec::new(),
+            weekdays: words(&[
+                "Sonntag",
+                "Montag",
+                "Dienstag",
+                "Mittwoch",
+                "Donnerstag",
+                "Freitag",
+                "Samstag",
+            ]),
+            short_weekdays: words(&["So.", "Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa."]),
+            am_pm: words(&["vorm.", "nachm."]),
+        };
+        let t = time(2024, 3, 5, 2, 15, 7, 3);
+        assert_eq!(
+            t.format("%A, %-d. %B (%a %b)", &locale),
+            "Dienstag, 5. März (Di. Mär)"
+        );
+        assert_eq!(t.format("%-I %p", &locale), "3 nachm.");
+
+        // Lists of the wrong length fall back to English
+        let partial = DateLocale {
+            months: words(&["Januar"]),
+            ..DateLocale::default()
+        };
+        assert_eq!(t.format("%B %b", &partial), "March Mar");
+    }
+}
@@ -0,0 +1,672 @@
+// toml.rs
+//! The part of TOML that bookmark files need: tables, arrays of tables, and
+//! string, integer, float, boolean, array and inline table values. Documents
+//! are read into and written from `serde_json::Value`, so bookmarks reuse
+//! their JSON (de)serialization.
+
+use serde_json::{Map, Number, Value};
+
+/// A TOML document from `root`. Keys holding objects become `[tables]` and
+/// keys holding arrays of objects `[[tables]]`; nulls, which TOML has no
+/// form for, are left out.
+pub fn to_string(root: &Map<String, Value>) -> String {
+    let mut out = String::new();
+    // Plain keys have to come before the first table header
+    for (key, value) in root
+        .iter()
+        .filter(|(_, value)| !is_table(value) && !is_table_array(value))
+    {
+        write_key_value(&mut out, key, value);
+    }
+    for (key, value) in root {
+        match value {
+            Value::Object(table) => {
+                out.push_str(&format!("\n[{}]\n", format_key(key)));
+                write_table(&mut out, table);
+            }
+            Value::Array(items) if is_table_array(value) => {
+                for item in items.iter().filter_map(Value::as_object) {
+                    out.push_str(&format!("\n[[{}]]\n", format_key(key)));
+                    write_table(&mut out, item);
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+fn is_table(value: &Value) -> bool {
+    value.is_object()
+}
+
+fn is_table_array(value: &Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| !items.is_empty() && items.iter().all(Value::is_object))
+}
+
+fn write_table(out: &mut String, table: &Map<String, Value>) {
+    for (key, value) in table {
+        write_key_value(out, key, value);
+    }
+}
+
+fn write_key_value(out: &mut String, key: &str, value: &Value) {
+    if !value.is_null() {
+        out.push_str(&format!("{} = {}\n", format_key(key), format_value(value)));
+    }
+}
+
+fn format_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        format_basic_string(key)
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => format_string(s),
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .filter(|v| !v.is_null())
+                .map(format_value)
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(table) => {
+            let pairs: Vec<String> = table
+                .iter()
+                .filter(|(_, v)| !v.is_null())
+                .map(|(k, v)| format!("{} = {}", format_key(k), format_value(v)))
+                .collect();
+            if pairs.is_empty() {
+                "{}".to_string()
+            } else {
+                format!("{{ {} }}", pairs.join(", "))
+            }
+        }
+    }
+}
+
+/// Multi-line commands as literal blocks, which read like the command itself
+fn format_string(s: &str) -> String {
+    let literal_block = s.contains('\n')
+        && !s.contains("'''")
+        && !s.ends_with('\'')
+        && !s.chars().any(|c| c.is_control() && c != '\n' && c != '\t');
+    if literal_block {
+        format!("'''\n{}'''", s)
+    } else {
+        format_basic_string(s)
+    }
+}
+
+fn format_basic_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The document in `text` as an object, or what is wrong with it and where
+pub fn from_str(text: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    parser
+        .document()
+        .map_err(|message| format!("line {}: {}", parser.line(), message))
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+/// The table key-value pairs currently go into
+enum Target {
+    Root,
+    Table(String),
+    /// The last table of an array of tables
+    ArrayItem(String),
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count()
+            + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", c))
+        }
+    }
+
+    /// Spaces and tabs
+    fn skip_blank(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Blanks, newlines and comments
+    fn skip_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\n' | '\r') => self.pos += 1,
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.pos += 1;
+        }
+    }
+
+    /// Nothing but a comment may follow a value or header on its line
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_blank();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+        if self.starts_with("\r\n") {
+            self.pos += 1;
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some('\n') => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(format!("unexpected '{}' after the value", c)),
+        }
+    }
+
+    fn document(&mut self) -> Result<Value, String> {
+        let mut root = Map::new();
+        let mut target = Target::Root;
+        loop {
+            self.skip_space();
+            let Some(c) = self.peek() else {
+                return Ok(Value::Object(root));
+            };
+
+            if c == '[' {
+                self.pos += 1;
+                let array = self.peek() == Some('[');
+                if array {
+                    self.pos += 1;
+                }
+                self.skip_blank();
+                let name = self.key()?;
+                self.skip_blank();
+                self.expect(']')?;
+                if array {
+                    self.expect(']')?;
+                }
+                self.end_of_line()?;
+
+                let slot = root.entry(name.clone()).or_insert_with(|| {
+                    if array {
+                        Value::Array(Vec::new())
+                    } else {
+                        Value::Null
+                    }
+                });
+                target = match slot {
+                    Value::Array(items) if array => {
+                        items.push(Value::Object(Map::new()));
+                        Target::ArrayItem(name)
+                    }
+                    Value::Null => {
+                        *slot = Value::Object(Map::new());
+                        Target::Table(name)
+                    }
+                    _ => return Err(format!("'{}' is defined twice", name)),
+                };
+                continue;
+            }
+
+            let key = self.key()?;
+            self.skip_blank();
+            self.expect('=')?;
+            self.skip_blank();
+            let value = self.value()?;
+            self.end_of_line()?;
+
+            let table = match &target {
+                Target::Root => Some(&mut root),
+                Target::Table(name) => root.get_mut(name).and_then(Value::as_object_mut),
+                Target::ArrayItem(name) => root
+                    .get_mut(name)
+                    .and_then(Value::as_array_mut)
+                    .and_then(|items| items.last_mut())
+                    .and_then(Value::as_object_mut),
+            };
+            let Some(table) = table else {
+                return Err("key outside of any table".to_string());
+            };
+            if table.insert(key.clone(), value).is_some() {
+                return Err(format!("'{}' is defined twice", key));
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        let key = match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                self.basic_string()?
+            }
+            Some('\'') => {
+                self.pos += 1;
+                self.literal_string()?
+            }
+            _ => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err("expected a key".to_string());
+                }
+                self.chars[start..self.pos].iter().collect()
+            }
+        };
+        self.skip_blank();
+        if self.peek() == Some('.') {
+            return Err("dotted keys are not supported".to_string());
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.pos += 3;
+                self.multiline_string('"').map(Value::String)
+            }
+            Some('\'') if self.starts_with("'''") => {
+                self.pos += 3;
+                self.multiline_string('\'').map(Value::String)
+            }
+            Some('"') => {
+                self.pos += 1;
+                self.basic_string().map(Value::String)
+            }
+            Some('\'') => {
+                self.pos += 1;
+                self.literal_string().map(Value::String)
+            }
+            Some('[') => {
+                self.pos += 1;
+                self.array()
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.inline_table()
+            }
+            Some(_) if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(_) if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.number(),
+            None => Err("expected a value".to_string()),
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, String> {
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    s.push(self.escape()?);
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    s.push(c);
+                }
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                None | Some('\n') => return Err("unterminated string".to_string()),
+                Some('\'') => {
+                    let s = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(_) => self.pos += 1,
+            }
+        }
+    }
+
+    /// The rest of a `"""` or `'''` string, whose first newline is dropped
+    fn multiline_string(&mut self, quote: char) -> Result<String, String> {
+        if self.starts_with("\r\n") {
+            self.pos += 2;
+        } else if self.peek() == Some('\n') {
+            self.pos += 1;
+        }
+
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return Err("unterminated string".to_string()),
+                Some(c)
+                    if c == quote && self.chars[self.pos..].iter().take(3).all(|q| *q == quote) =>
+                {
+                    // Up to two more quotes right before the closing ones are content
+                    let run = self.chars[self.pos..]
+                        .iter()
+                        .take_while(|q| **q == quote)
+                        .count()
+                        .min(5);
+                    s.extend(std::iter::repeat_n(quote, run - 3));
+                    self.pos += run;
+                    return Ok(s);
+                }
+                Some('\\') if quote == '"' => {
+                    self.pos += 1;
+                    // A backslash ending a line joins it with the next non-blank text
+                    let rest = self.pos;
+                    self.skip_blank();
+                    if matches!(self.peek(), Some('\n' | '\r')) {
+                        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+                            self.pos += 1;
+                        }
+                    } else {
+                        self.pos = rest;
+                        s.push(self.escape()?);
+                    }
+                }
+                Some(c) => {
+                    self.pos += 1;
+                    s.push(c);
+                }
+            }
+        }
+    }
+
+    /// The character after a backslash
+    fn escape(&mut self) -> Result<char, String> {
+        let c = self.peek().ok_or("unterminated string")?;
+        self.pos += 1;
+        let digits = match c {
+            'b' => return Ok('\u{8}'),
+            't' => return Ok('\t'),
+            'n' => return Ok('\n'),
+            'f' => return Ok('\u{c}'),
+            'r' => return Ok('\r'),
+            'e' => return Ok('\u{1b}'),
+            '"' => return Ok('"'),
+            '\\' => return Ok('\\'),
+            'u' => 4,
+            'U' => 8,
+            other => return Err(format!("unknown escape '\\{}'", other)),
+        };
+        let hex: String = self.chars.iter().skip(self.pos).take(digits).collect();
+        self.pos += digits;
+        u32::from_str_radix(&hex, 16)
+            .ok()
+            .filter(|_| hex.len() == digits)
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid unicode escape '{}'", hex))
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_space();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {}
+                _ => return Err("expected ',' or ']' in array".to_string()),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, String> {
+        let mut table = Map::new();
+        self.skip_blank();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::Object(table));
+        }
+        loop {
+            self.skip_blank();
+            let key = self.key()?;
+            self.skip_blank();
+            self.expect('=')?;
+            self.skip_blank();
+            let value = self.value()?;
+            if table.insert(key.clone(), value).is_some() {
+                return Err(format!("'{}' is defined twice", key));
+            }
+            self.skip_blank();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(table));
+                }
+                _ => return Err("expected ',' or '}' in inline table".to_string()),
+            }
+        }
+    }
+
+    /// Integers in any base and floats; dates and times aren't supported
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-' | '.'))
+        {
+            self.pos += 1;
+        }
+        let token: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect();
+        let (sign, digits) = match token.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, token.strip_prefix('+').unwrap_or(&token)),
+        };
+
+        let radix = [("0x", 16), ("0o", 8), ("0b", 2)]
+            .into_iter()
+            .find_map(|(prefix, radix)| digits.strip_prefix(prefix).map(|rest| (rest, radix)));
+        if let Some((rest, radix)) = radix {
+            if let Ok(n) = i64::from_str_radix(rest, radix) {
+                return Ok(Value::Number(Number::from(sign * n)));
+            }
+        } else if let Ok(n) = token.parse::<i64>() {
+            return Ok(Value::Number(Number::from(n)));
+        } else if let Some(n) = token.parse::<f64>().ok().and_then(Number::from_f64) {
+            return Ok(Value::Number(n));
+        }
+        Err(format!("unsupported value '{}'", token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bookmark::{self, Bookmark};
+
+    /// Bookmarks through TOML and back, the way `TomlStore` stores them
+    fn round_trip(bookmarks: &[Bookmark]) -> Vec<Bookmark> {
+        let root = Map::from_iter([
+            ("version".to_string(), Value::from(bookmark::FILE_VERSION)),
+            (
+                "bookmarks".to_string(),
+                serde_json::to_value(bookmarks).expect("serialize"),
+            ),
+        ]);
+        let text = to_string(&root);
+        let document = from_str(&text).unwrap_or_else(|e| panic!("{} in:\n{}", e, text));
+        let (version, loaded) = bookmark::parse_value(document).expect("bookmark file");
+        assert_eq!(version, bookmark::FILE_VERSION);
+        loaded
+    }
+
+    fn string(text: &str) -> Value {
+        let document = from_str(text).unwrap_or_else(|e| panic!("{} in:\n{}", e, text));
+        document["s"].clone()
+    }
+
+    #[test]
+    fn bookmarks_round_trip() {
+        let bookmarks = vec![
+            Bookmark::new("ls -la"),
+            Bookmark {
+                title: Some("say \"hi\"".to_string()),
+                tags: vec!["deploy".to_string(), "prod".to_string()],
+                note: Some("C:\\temp\tand\r\nmore".to_string()),
+                group: Some("homelab".to_string()),
+                pinned: true,
+                trashed_at: Some(1700000000),
+                uses: 3,
+                last_used: Some(1700000001),
+                ..Bookmark::new("for f in *; do\n  echo \"$f\"\ndone")
+            },
+            Bookmark::new("printf '\\e[1m'\u{1b}"),
+            Bookmark::new("cat <<'EOF'\nit's '''quoted'''\nEOF"),
+            Bookmark::new("echo 'ends in a quote'\necho '"),
+        ];
+        assert_eq!(round_trip(&bookmarks), bookmarks);
+    }
+
+    #[test]
+    fn empty_bookmark_list() {
+        assert_eq!(round_trip(&[]), Vec::<Bookmark>::new());
+    }
+
+    #[test]
+    fn unknown_fields_kept_in_extra() {
+        let text = concat!(
+            "version = 2\n\n[[bookmarks]]\ncommand = \"ls\"\n",
+            "color = \"red\"\nstats = { runs = 4, ok = true }\n",
+        );
+        let (_, loaded) =
+            bookmark::parse_value(from_str(text).expect("parse")).expect("bookmark file");
+        assert_eq!(loaded[0].extra["color"], "red");
+        assert_eq!(loaded[0].extra["stats"]["runs"], 4);
+        assert_eq!(round_trip(&loaded), loaded);
+    }
+
+    #[test]
+    fn basic_strings() {
+        assert_eq!(
+            string("s = \"a \\\"b\\\" c\\\\d\\ne\\tf\""),
+            "a \"b\" c\\d\ne\tf"
+        );
+        assert_eq!(string("s = \"\\u00e9\\U0001F600\""), "é😀");
+        assert_eq!(string("s = \"\" # comment"), "");
+    }
+
+    #[test]
+    fn literal_strings() {
+        assert_eq!(string("s = 'C:\\temp\\n'"), "C:\\temp\\n");
+    }
+
+    #[test]
+    fn multi_line_strings() {
+        assert_eq!(
+            string("s = '''\nfirst\n  second\\n'''"),
+            "first\n  second\\n"
+        );
+        assert_eq!(
+            string("s = \"\"\"\none \\\n    two\\tthree\"\"\""),
+            "one two\tthree"
+        );
+        assert_eq!(string("s = '''it's ''quoted'''''"), "it's ''quoted''");
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |text| from_str(text).expect_err("invalid document");
+        assert_eq!(
+            error("a = 1\nb = \"open\nc = 2"),
+            "line 2: unterminated string"
+        );
+        assert_eq!(
+            error("a = 1\n\nb = \"\\q\""),
+            "line 3: unknown escape '\\q'"
+        );
+        assert_eq!(error("a = 1\na = 2"), "line 2: 'a' is defined twice");
+        assert_eq!(
+            error("[t]\nx = \"\\u12\""),
+            "line 2: invalid unicode escape '12\"'"
+        );
+        assert_eq!(
+            error("s = '''\nnever\nclosed"),
+            "line 3: unterminated string"
+        );
+    }
+}
@@ -1,164 +1,226 @@
 // ui.rs
+use std::borrow::Cow;
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{Block, Borders, Clear, Paragraph, Wrap},
     Frame,
 };
 use crate::app::App;
+use crate::expansion::{self, Expansion};
+use crate::views::{display_command, truncate_for_display, view_for};
 
-pub fn draw_ui(f: &mut Frame, app: &mut App) {
-    // Main layout structure
-    let main_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(3), // Header
-            Constraint::Min(1),    // Main content
-            Constraint::Length(3), // Search bar
-            Constraint::Length(1), // Status bar
-        ])
-        .split(f.area());
+/// Below this size nothing useful fits; only a notice is drawn
+const MIN_WIDTH: u16 = 20;
+const MIN_HEIGHT: u16 = 5;
 
-    // Render header
-    let header = Paragraph::new(Line::from(vec![
-        Span::styled("History Finder ", Style::default().fg(Color::Yellow)),
-        Span::styled("v0.1", Style::default().fg(Color::LightBlue)),
-        Span::raw(" | Mode: "),
-        Span::styled(
-            if app.bookmark_mode { 
-                "BOOKMARKS" 
-            } else { 
-                "HISTORY" 
-            },
-            Style::default().fg(Color::Cyan),
-        ),
-        Span::raw(" | [B]Toggle | [/]Search | [h]Help | [q]Quit"),
-    ]))
-    .block(Block::default().borders(Borders::ALL))
-    .alignment(Alignment::Center);
+/// Below this height the header, search bar and status bar collapse into one line
+const COMPACT_HEIGHT: u16 = 16;
 
-    f.render_widget(header, main_layout[0]);
+/// Tallest preview of a multi-line command under the list
+const MAX_PREVIEW_LINES: usize = 8;
 
-    // Main content area
-    let content_title = if app.bookmark_mode {
-        " Bookmarks (Press B to switch) "
-    } else {
-        " Command History (Press B to switch) "
-    };
+/// Tallest bookmark note under the list
+const MAX_NOTE_LINES: usize = 4;
 
-    let content_block = Block::default()
-        .title(content_title)
-        .borders(Borders::ALL)
-        .style(if app.bookmark_mode {
-            Style::default().fg(Color::Yellow)
-        } else {
-            Style::default()
-        });
+pub fn draw_ui(f: &mut Frame, app: &App) {
+    let area = f.area();
+    if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+        draw_too_small(f, area);
+        return;
+    }
+
+    let compact = area.height < COMPACT_HEIGHT;
+    let view = view_for(app.mode);
 
-    // Prepare list items
-    let items = app
-        .current_list()
-        .iter()
-        .enumerate()
-        .skip(app.skipped_items)
-        .map(|(i, cmd)| {
-            let prefix = if app.bookmark_mode {
-                Span::styled("* ", Style::default().fg(Color::Yellow))
-            } else {
-                Span::raw("")
-            };
-
-            let line_style = if i == app.selected {
-                Style::default()
-                    .bg(Color::Rgb(30, 30, 30)).fg(Color::Cyan)
-            } else {
-                Style::default()
-            };
-
-            Line::from(vec![
-                Span::styled(
-                    format!("{:3} ", i + 1),
-                    Style::default().fg(Color::DarkGray),
-                ),
-                prefix,
-                Span::raw(cmd.as_str())
-            ]).style(line_style)
-        })
-        .collect::<Vec<_>>();
-
-    let inner_area = content_block.inner(main_layout[1]);
-    app.set_size(inner_area.height.into());
-    
-    f.render_widget(content_block, main_layout[1]);
-    f.render_widget(Paragraph::new(items), inner_area);
-
-    // Search bar
-    let search_text = if app.search_mode {
-        format!("/{}", app.search_query())
+    let content_area = if compact {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1), // Mode, search and message
+                Constraint::Min(1),    // Main content
+            ])
+            .split(area);
+
+        draw_compact_header(f, layout[0], app);
+        layout[1]
     } else {
-        "Press / to start searching".into()
-    };
+        let chip_rows = if app.program_chips().is_empty() { 0 } else { 1 };
+
+        // Main layout structure
+        let main_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),         // Header
+                Constraint::Length(chip_rows), // Program chips
+                Constraint::Min(1),            // Main content
+                Constraint::Length(3),         // Search bar
+                Constraint::Length(1),         // Status bar
+            ])
+            .split(area);
 
-    let search_bar = Paragraph::new(Text::raw(search_text))
-        .block(Block::default().title(" Search ").borders(Borders::ALL))
-        .alignment(Alignment::Left);
+        // Render header
+        let header = Paragraph::new(Line::from(vec![
+            Span::styled("History Finder ", Style::default().fg(Color::Yellow)),
+            Span::styled("v0.1", Style::default().fg(Color::LightBlue)),
+            Span::raw(" | Mode: "),
+            Span::styled(view.label(), Style::default().fg(Color::Cyan)),
+            Span::raw(" | Sort: "),
+            Span::styled(app.sort_label(), Style::default().fg(Color::Cyan)),
+            Span::raw(" | [B]Toggle | [/]Search | [h]Help | [q]Quit"),
+        ]))
+        .block(Block::default().borders(Borders::ALL))
+        .alignment(Alignment::Center);
 
-    f.render_widget(search_bar, main_layout[2]);
+        f.render_widget(header, main_layout[0]);
 
-    // Status bar
-    let status_actions = if app.bookmark_mode {
-        vec![
-            Span::styled(" B ", Style::default().bg(Color::Yellow).fg(Color::Black)),
-            Span::raw("Switch "),
-            Span::styled(" d ", Style::default().bg(Color::Red).fg(Color::Black)),
-            Span::raw("Delete "),
-        ]
+        // Search bar
+        let search_bar = Paragraph::new(search_line(app))
+            .block(
+                Block::default()
+                    .title(format!(
+                        " Search [{}, {}] (Ctrl+R, Ctrl+F) ",
+                        app.match_mode.label(),
+                        app.search_display.label()
+                    ))
+                    .borders(Borders::ALL),
+            )
+            .alignment(Alignment::Left);
+
+        f.render_widget(search_bar, main_layout[3]);
+        if let Some(offset) = search_cursor_offset(app) {
+            let inner = main_layout[3].inner(Margin::new(1, 1));
+            f.set_cursor_position((inner.x + offset.min(inner.width.saturating_sub(1)), inner.y));
+        }
+
+        draw_chips(f, main_layout[1], app);
+        draw_status_bar(f, main_layout[4], app);
+        main_layout[2]
+    };
+
+    // Variables preview under the list
+    let content_area = if app.show_expansions {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)])
+            .split(content_area);
+        draw_expansions(f, layout[1], app);
+        layout[0]
     } else {
-        vec![
-            Span::styled(" B ", Style::default().bg(Color::Blue).fg(Color::Black)),
-            Span::raw("Switch "),
-            Span::styled(" b ", Style::default().bg(Color::Green).fg(Color::Black)),
-            Span::raw("Bookmark "),
-        ]
+        content_area
     };
 
-    let mut status_line = vec![
-        Span::styled(
-            format!(
-                " {} ", 
-                if app.bookmark_mode { 
-                    "BOOKMARK" 
-                } else { 
-                    "HISTORY" }
-            ),
-            Style::default().fg(Color::Black).bg(if app.bookmark_mode { 
-                Color::Yellow 
-            } else { 
-                Color::Blue 
-            }),
-        ),
-        Span::raw(" "),
-    ];
-    status_line.extend(status_actions);
-    status_line.push(Span::raw(&app.message));
+    // Full text of a multi-line selection, which the list folds onto one row
+    let content_area = match app
+        .selected_command()
+        .filter(|cmd| cmd.contains('\n') && !compact)
+    {
+        Some(cmd) => {
+            let rows = cmd.lines().count().min(MAX_PREVIEW_LINES) as u16 + 2;
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(rows)])
+                .split(content_area);
+            draw_multiline_preview(f, layout[1], &app.shown_command(cmd));
+            layout[0]
+        }
+        None => content_area,
+    };
 
-    f.render_widget(Paragraph::new(Line::from(status_line)), main_layout[3]);
+    // Note of the selected bookmark
+    let note = app
+        .selected_command()
+        .and_then(|cmd| app.bookmark(cmd))
+        .and_then(|bookmark| bookmark.note.as_deref())
+        .filter(|_| !compact);
+    let content_area = match note {
+        Some(note) => {
+            let lines = textwrap::wrap(note, content_area.width.saturating_sub(2).max(1) as usize);
+            let rows = lines.len().min(MAX_NOTE_LINES) as u16 + 2;
+            let layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(3), Constraint::Length(rows)])
+                .split(content_area);
+            draw_note(f, layout[1], lines);
+            layout[0]
+        }
+        None => content_area,
+    };
+
+    // Main content area
+    view.render(f, content_area, app);
+
+    // Context menu for the selected entry
+    if let Some(menu) = &app.menu {
+        let width = menu
+            .items
+            .iter()
+            .map(|item| item.label.chars().count() + item.key.chars().count() + 6)
+            .chain([menu.title.chars().count() + 4])
+            .max()
+            .unwrap_or(0) as u16;
+        let height = menu.items.len() as u16 + 2;
+        let content = content_area;
+        let menu_area = Rect::new(
+            content.x + content.width.saturating_sub(width) / 2,
+            content.y + content.height.saturating_sub(height) / 3,
+            width.min(content.width),
+            height.min(content.height),
+        );
+
+        let lines = menu
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let style = if i == menu.selected {
+                    Style::default().bg(Color::Rgb(30, 30, 30)).fg(Color::Cyan)
+                } else {
+                    Style::default()
+                };
+                let padding = (width as usize)
+                    .saturating_sub(item.label.chars().count() + item.key.chars().count() + 4);
+                Line::from(vec![
+                    Span::raw(format!(" {}{}", item.label, " ".repeat(padding))),
+                    Span::styled(
+                        format!("{} ", item.key),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+                .style(style)
+            })
+            .collect::<Vec<_>>();
+
+        f.render_widget(Clear, menu_area);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::default()
+                    .title(format!(" {} ", menu.title))
+                    .borders(Borders::ALL),
+            ),
+            menu_area,
+        );
+    }
 
     // Help window (rendered last to overlay other components)
     if app.show_help {
         // Create transparent overlay
         f.render_widget(Clear, f.area());
-        
-        // Calculate help window position
-        let help_area = centered_rect(60, 60, f.area());
-        let vertical_offset = (f.area().height.saturating_sub(help_area.height)) / 4;
-        let adjusted_rect = Rect::new(
-            help_area.x,
-            vertical_offset,
-            help_area.width,
-            help_area.height.min(f.area().height - vertical_offset - 2),
-        );
+
+        // Calculate help window position; short terminals get the whole screen
+        let adjusted_rect = if compact {
+            area
+        } else {
+            let help_area = centered_rect(60, 60, area);
+            let vertical_offset = (area.height.saturating_sub(help_area.height)) / 4;
+            Rect::new(
+                help_area.x,
+                vertical_offset,
+                help_area.width,
+                help_area.height.min(area.height - vertical_offset - 2),
+            )
+        };
 
         // Create help content
         let help_block = Block::default()
@@ -173,17 +235,287 @@ pub fn draw_ui(f: &mut Frame, app: &mut App) {
 
         f.render_widget(help_para, adjusted_rect);
     }
+
+    // Visual bell, applied on top of everything
+    if app.is_flashing() {
+        let area = f.area();
+        f.buffer_mut()
+            .set_style(area, Style::default().add_modifier(Modifier::REVERSED));
+    }
+}
+
+/// Search prompt and top-match hint, shared by the full and compact layouts
+fn search_line(app: &App) -> Line<'_> {
+    if let Some(prompt) = &app.prompt {
+        return Line::from(vec![
+            Span::styled(prompt.kind.label(), Style::default().fg(Color::Yellow)),
+            Span::raw(prompt.text.as_str()),
+            Span::styled(prompt.kind.hint(), Style::default().fg(Color::DarkGray)),
+        ]);
+    }
+
+    let mut line = if app.search_mode {
+        Line::from(format!("/{}", app.search_query()))
+    } else if !app.search_query().is_empty() {
+        Line::from(vec![
+            Span::raw(format!("/{}", app.search_query())),
+            Span::styled(
+                "  n/N next/previous match, Esc to clear",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])
+    } else {
+        Line::from("Press / to start searching")
+    };
+    if let Some(top_match) = app.top_match().filter(|_| app.search_mode) {
+        line.push_span(Span::styled(
+            format!("  → {} (Tab)", display_command(app, top_match)),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    line
+}
+
+/// Column of the text cursor within `search_line`, while the user is typing
+fn search_cursor_offset(app: &App) -> Option<u16> {
+    let typed = if let Some(prompt) = &app.prompt {
+        format!("{}{}", prompt.kind.label(), &prompt.text[..prompt.cursor])
+    } else if app.search_mode {
+        format!("/{}", &app.search_query()[..app.search_cursor()])
+    } else {
+        return None;
+    };
+    Some(Span::raw(typed).width() as u16)
+}
+
+/// Variables and substitutions in the selected command; unset variables in red
+fn draw_expansions(f: &mut Frame, area: Rect, app: &App) {
+    let dim = Style::default().fg(Color::DarkGray);
+    let expansions = app
+        .selected_command()
+        .map(expansion::expansions)
+        .unwrap_or_default();
+
+    let mut line = Line::default();
+    for item in &expansions {
+        line.push_span(match item {
+            Expansion::Variable { name, set: true } => {
+                Span::styled(format!("${}", name), Style::default().fg(Color::Green))
+            }
+            Expansion::Variable { name, set: false } => Span::styled(
+                format!("${} (unset)", name),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            ),
+            Expansion::Substitution(text) => {
+                Span::styled(format!("{} (not run)", truncate_for_display(text)), dim)
+            }
+        });
+        line.push_span(Span::raw("  "));
+    }
+    if expansions.is_empty() {
+        line.push_span(Span::styled("No variables or substitutions", dim));
+    }
+
+    f.render_widget(
+        Paragraph::new(line).block(
+            Block::default()
+                .title(" Variables (e to hide) ")
+                .borders(Borders::ALL),
+        ),
+        area,
+    );
+}
+
+/// The selected command line by line, noting how many lines don't fit
+fn draw_multiline_preview(f: &mut Frame, area: Rect, cmd: &str) {
+    let total = cmd.lines().count();
+    let shown = area.height.saturating_sub(2) as usize;
+    let mut lines: Vec<Line> = cmd.lines().take(shown).map(Line::raw).collect();
+    if total > shown {
+        if let Some(last) = lines.last_mut() {
+            *last = Line::styled(
+                format!("… {} more lines", total - shown + 1),
+                Style::default().fg(Color::DarkGray),
+            );
+        }
+    }
+
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(format!(" Command ({} lines) ", total))
+                .borders(Borders::ALL),
+        ),
+        area,
+    );
+}
+
+/// A bookmark's note, wrapped to the pane
+fn draw_note(f: &mut Frame, area: Rect, lines: Vec<Cow<'_, str>>) {
+    let lines: Vec<Line> = lines.into_iter().map(Line::raw).collect();
+    f.render_widget(
+        Paragraph::new(lines).block(
+            Block::default()
+                .title(" Note ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Yellow)),
+        ),
+        area,
+    );
+}
+
+/// Quick program filters; the selected one is highlighted
+fn draw_chips(f: &mut Frame, area: Rect, app: &App) {
+    let active = app.active_chip();
+    let chip = |label: &str, selected: bool| {
+        let style = if selected {
+            Style::default().fg(Color::Black).bg(Color::Cyan)
+        } else {
+            Style::default().fg(Color::Cyan)
+        };
+        Span::styled(format!(" {} ", label), style)
+    };
+
+    let mut line = Line::from(vec![Span::raw(" "), chip("all", active.is_none())]);
+    for program in app.program_chips() {
+        line.push_span(Span::raw(" "));
+        line.push_span(chip(program, active == Some(program.as_str())));
+    }
+    line.push_span(Span::styled("  ←/→", Style::default().fg(Color::DarkGray)));
+    f.render_widget(Paragraph::new(line), area);
+}
+
+fn draw_status_bar(f: &mut Frame, area: Rect, app: &App) {
+    let view = view_for(app.mode);
+    let status_actions = view.status_actions();
+
+    let mut status_line = vec![
+        Span::styled(
+            format!(" {} ", view.label()),
+            Style::default().fg(Color::Black).bg(view.accent()),
+        ),
+        Span::raw(" "),
+    ];
+    if let Some(register) = app.recording_register() {
+        status_line.push(Span::styled(
+            format!(" REC @{} ", register),
+            Style::default().bg(Color::Red).fg(Color::Black),
+        ));
+        status_line.push(Span::raw(" "));
+    }
+    status_line.extend(status_actions);
+    status_line.push(Span::raw(&app.message));
+
+    let (position, matches, loaded) = app.entry_counts();
+    let indicator = format!(
+        " {}/{} · {} loaded ",
+        group_digits(position),
+        group_digits(matches),
+        group_digits(loaded)
+    );
+    let status_layout = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(indicator.chars().count() as u16),
+        ])
+        .split(area);
+
+    f.render_widget(Paragraph::new(Line::from(status_line)), status_layout[0]);
+    f.render_widget(
+        Paragraph::new(Span::styled(
+            indicator,
+            Style::default().fg(Color::DarkGray),
+        ))
+        .alignment(Alignment::Right),
+        status_layout[1],
+    );
+}
+
+/// Single line replacing the header, search bar and status bar on short terminals
+fn draw_compact_header(f: &mut Frame, area: Rect, app: &App) {
+    let view = view_for(app.mode);
+    let mut line = Line::from(vec![
+        Span::styled(
+            format!(" {} ", view.label()),
+            Style::default().fg(Color::Black).bg(view.accent()),
+        ),
+        Span::raw(" "),
+    ]);
+    if let Some(register) = app.recording_register() {
+        line.push_span(Span::styled(
+            format!(" REC @{} ", register),
+            Style::default().bg(Color::Red).fg(Color::Black),
+        ));
+        line.push_span(Span::raw(" "));
+    }
+    if let Some(program) = app.active_chip() {
+        line.push_span(Span::styled(
+            format!("[{}] ", program),
+            Style::default().fg(Color::Cyan),
+        ));
+    }
+    if app.search_mode || app.prompt.is_some() {
+        if let Some(offset) = search_cursor_offset(app) {
+            let x = area.x + line.width() as u16 + offset;
+            f.set_cursor_position((x.min(area.right().saturating_sub(1)), area.y));
+        }
+        line.extend(search_line(app).spans);
+    } else {
+        line.push_span(Span::styled(
+            "/ search  h help",
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    if !app.message.is_empty() {
+        line.push_span(Span::styled(
+            format!("  {}", app.message),
+            Style::default().fg(Color::DarkGray),
+        ));
+    }
+    f.render_widget(Paragraph::new(line), area);
+}
+
+fn draw_too_small(f: &mut Frame, area: Rect) {
+    let text = vec![
+        Line::from(Span::styled(
+            "Terminal too small",
+            Style::default().fg(Color::Yellow),
+        )),
+        Line::from(format!("need {}×{}", MIN_WIDTH, MIN_HEIGHT)),
+    ];
+    let y = area.height.saturating_sub(text.len() as u16) / 2;
+    let notice_area = Rect::new(area.x, area.y + y, area.width, area.height - y);
+    f.render_widget(
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        notice_area,
+    );
 }
 
 /// Create centered rectangle with size constraints
 fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
     let popup_width = (area.width * percent_x / 100).min(area.width - 4);
     let popup_height = (area.height * percent_y / 100).min(area.height - 4);
-    
+
     Rect::new(
         (area.width - popup_width) / 2,
         (area.height - popup_height) / 3, // Adjusted vertical centering
         popup_width,
         popup_height,
     )
-}
\ No newline at end of file
+}
+
+/// Format a count with thousands separators, e.g. 8301 -> "8,301"
+pub fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
@@ -0,0 +1,97 @@
+// views/bookmarks.rs
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::Span,
+    widgets::{Block, Borders},
+    Frame,
+};
+use super::{bookmark_marker, counted_title, pin_marker, render_entries, RowDecoration, View};
+use crate::app::{Action, App, MoveDirection};
+use crate::entry;
+
+/// Saved bookmarks, persisted next to the user's home directory
+pub struct BookmarksView;
+
+impl View for BookmarksView {
+    fn label(&self) -> &'static str {
+        "BOOKMARKS"
+    }
+
+    fn accent(&self) -> Color {
+        Color::Yellow
+    }
+
+    fn render(&self, f: &mut Frame, area: Rect, app: &App) {
+        let block = Block::default()
+            .title(counted_title("Bookmarks", app))
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Yellow));
+
+        let decorate = |cmd: &str| {
+            let bookmark = app.bookmark(cmd);
+            let trashed_at = bookmark.and_then(|bookmark| bookmark.trashed_at);
+            RowDecoration {
+                marker: if trashed_at.is_some() {
+                    Span::styled("✗ ", Style::default().fg(Color::DarkGray))
+                } else if bookmark.is_some_and(|bookmark| bookmark.pinned) {
+                    pin_marker()
+                } else {
+                    bookmark_marker()
+                },
+                title: bookmark.and_then(|bookmark| bookmark.title.clone()),
+                badge: match (trashed_at, bookmark) {
+                    (Some(at), _) => {
+                        Some(format!("deleted {}", entry::format_age(at, entry::now())))
+                    }
+                    (None, Some(bookmark)) => {
+                        let uses = (bookmark.uses > 0).then(|| format!("used {}×", bookmark.uses));
+                        let parts: Vec<String> =
+                            [bookmark.tag_label(), uses].into_iter().flatten().collect();
+                        (!parts.is_empty()).then(|| parts.join(" "))
+                    }
+                    (None, None) => None,
+                },
+            }
+        };
+        render_entries(f, area, app, block, app.current_list(), decorate, None);
+    }
+
+    fn status_actions(&self) -> Vec<Span<'static>> {
+        vec![
+            Span::styled(" B ", Style::default().bg(Color::Yellow).fg(Color::Black)),
+            Span::raw("Switch "),
+            Span::styled(" d ", Style::default().bg(Color::Red).fg(Color::Black)),
+            Span::raw("Delete "),
+            Span::styled(" t ", Style::default().bg(Color::Cyan).fg(Color::Black)),
+            Span::raw("Tags "),
+            Span::styled(" a ", Style::default().bg(Color::Cyan).fg(Color::Black)),
+            Span::raw("Note "),
+        ]
+    }
+
+    fn map_key(&self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char('b') | KeyCode::Char('d') => Some(Action::DeleteBookmark),
+            KeyCode::Char('t') => Some(Action::EditTags),
+            KeyCode::Char('a') => Some(Action::EditNote),
+            KeyCode::Char('R') => Some(Action::EditTitle),
+            KeyCode::Char('e') => Some(Action::EditCommand),
+            KeyCode::Char('g') => Some(Action::MoveToGroup),
+            KeyCode::Char('p') => Some(Action::TogglePin),
+            KeyCode::Char('u') => Some(Action::RestoreBookmark),
+            KeyCode::Char('I') => Some(Action::ImportBookmarks),
+            KeyCode::Char(' ') => Some(Action::ToggleGroup),
+            KeyCode::Char('K') => Some(Action::MoveBookmark(MoveDirection::Up)),
+            KeyCode::Char('J') => Some(Action::MoveBookmark(MoveDirection::Down)),
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::MoveBookmark(MoveDirection::Up))
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::MoveBookmark(MoveDirection::Down))
+            }
+            _ => None,
+        }
+    }
+}
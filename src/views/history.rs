@@ -0,0 +1,171 @@
+// views/history.rs
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+use super::{bookmark_marker, counted_title, render_entries, RowDecoration, View};
+use crate::app::{Action, App, MoveDirection};
+use crate::config::TimestampConfig;
+use crate::entry::{self, RunInfo};
+use crate::timefmt;
+
+/// Shell history, filtered by the active search query
+pub struct HistoryView;
+
+impl View for HistoryView {
+    fn label(&self) -> &'static str {
+        "HISTORY"
+    }
+
+    fn accent(&self) -> Color {
+        Color::Blue
+    }
+
+    fn render(&self, f: &mut Frame, area: Rect, app: &App) {
+        let mut block = Block::default()
+            .title(counted_title("Command History", app))
+            .borders(Borders::ALL);
+        if let Some(summary) = app
+            .selected_command()
+            .and_then(|cmd| app.run_info(cmd))
+            .and_then(|run| run_summary(run, &app.config.timestamps))
+        {
+            block = block.title_bottom(Line::from(summary).right_aligned());
+        }
+
+        if app.is_history_loading() {
+            let inner_area = block.inner(area);
+            app.set_size(inner_area.height.into());
+            f.render_widget(block, area);
+            f.render_widget(
+                Paragraph::new(Span::styled(
+                    "Loading history…",
+                    Style::default().fg(Color::DarkGray),
+                ))
+                .alignment(Alignment::Center),
+                inner_area,
+            );
+            return;
+        }
+
+        if app.history_is_empty() {
+            render_empty_state(f, area, app, block);
+            return;
+        }
+
+        let footer = app.has_older_history().then(|| {
+            Line::from(Span::styled(
+                "    loading older entries…",
+                Style::default().fg(Color::DarkGray),
+            ))
+        });
+
+        let decorate = |cmd: &str| RowDecoration {
+            marker: if app.is_bookmarked(cmd) {
+                bookmark_marker()
+            } else {
+                Span::raw("  ")
+            },
+            title: None,
+            badge: {
+                // Source shell when several histories are merged, the repeat
+                // count, then the time column at the right edge
+                let run = app.run_info(cmd);
+                let origin = run.and_then(|run| run.origin.clone());
+                let count = app
+                    .duplicate_count(cmd)
+                    .map(|count| format!("(×{})", count));
+                let time = run
+                    .and_then(|run| run.timestamp)
+                    .filter(|_| app.show_timestamps)
+                    .map(|timestamp| timefmt::format_timestamp(timestamp, &app.config.timestamps));
+                let parts: Vec<String> = [origin, count, time].into_iter().flatten().collect();
+                (!parts.is_empty()).then(|| parts.join(" "))
+            },
+        };
+        render_entries(f, area, app, block, app.current_list(), decorate, footer);
+    }
+
+    fn status_actions(&self) -> Vec<Span<'static>> {
+        vec![
+            Span::styled(" B ", Style::default().bg(Color::Blue).fg(Color::Black)),
+            Span::raw("Switch "),
+            Span::styled(" b ", Style::default().bg(Color::Green).fg(Color::Black)),
+            Span::raw("Bookmark "),
+        ]
+    }
+
+    fn map_key(&self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char('b') => Some(Action::ToggleBookmark),
+            KeyCode::Char('v') => Some(Action::ShowVariants),
+            KeyCode::Char('t') => Some(Action::ToggleTimestamps),
+            KeyCode::Char('D') => Some(Action::DeleteEntry),
+            KeyCode::Char(']') => Some(Action::JumpBookmark(MoveDirection::Down)),
+            KeyCode::Char('[') => Some(Action::JumpBookmark(MoveDirection::Up)),
+            _ => None,
+        }
+    }
+}
+
+/// When, where and how long the selected command last ran, and how it
+/// failed, e.g. " ran 3h ago · in ~/src · took 12s · exit 1 "
+fn run_summary(run: &RunInfo, timestamps: &TimestampConfig) -> Option<String> {
+    let mut parts = Vec::new();
+    if let Some(timestamp) = run.timestamp {
+        parts.push(format!(
+            "ran {}",
+            timefmt::format_timestamp(timestamp, timestamps)
+        ));
+    }
+    if let Some(cwd) = &run.cwd {
+        parts.push(format!("in {}", cwd));
+    }
+    if let Some(duration) = run.duration {
+        parts.push(format!("took {}", entry::format_duration(duration)));
+    }
+    if let Some(status) = run.exit_status.filter(|status| *status != 0) {
+        parts.push(format!("exit {}", status));
+    }
+    (!parts.is_empty()).then(|| format!(" {} ", parts.join(" · ")))
+}
+
+/// Explain why there is nothing to show instead of rendering an empty list
+fn render_empty_state(f: &mut Frame, area: Rect, app: &App, block: Block) {
+    let source = app.history_provider();
+    let path = source.location();
+    let reason = match app.history_error() {
+        Some(error) => format!("Could not read the history file: {}", error),
+        None => "The history file is empty.".to_string(),
+    };
+
+    let dim = Style::default().fg(Color::DarkGray);
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled("No history found", Style::default().fg(Color::Yellow))),
+        Line::from(""),
+        Line::from(reason),
+        Line::from(vec![Span::styled("Source: ", dim), Span::raw(source.label())]),
+        Line::from(vec![Span::styled("Path:   ", dim), Span::raw(path.display().to_string())]),
+        Line::from(""),
+        Line::from(Span::styled(
+            "Make sure your shell saves its history there (e.g. HISTFILE in your rc file), or press B for bookmarks.",
+            dim,
+        )),
+    ];
+
+    let inner_area = block.inner(area);
+    app.set_size(inner_area.height.into());
+
+    f.render_widget(block, area);
+    f.render_widget(
+        Paragraph::new(text)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true }),
+        inner_area,
+    );
+}
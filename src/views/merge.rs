@@ -0,0 +1,165 @@
+// views/merge.rs
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use super::{display_command, View};
+use crate::app::{Action, App};
+use crate::bookmark::Bookmark;
+use crate::merge::{ChangeKind, Side};
+
+/// Per-entry review of bookmark changes made on both sides of a conflict
+pub struct MergeView;
+
+impl View for MergeView {
+    fn label(&self) -> &'static str {
+        "MERGE"
+    }
+
+    fn accent(&self) -> Color {
+        Color::Magenta
+    }
+
+    fn render(&self, f: &mut Frame, area: Rect, app: &App) {
+        let block = Block::default()
+            .title(" Bookmark Conflicts (Space accept/reject, w save) ")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Magenta));
+
+        let changes = app
+            .merge
+            .as_ref()
+            .map(|m| m.changes.as_slice())
+            .unwrap_or_default();
+        let items = changes
+            .iter()
+            .enumerate()
+            .skip(app.skipped_items)
+            .map(|(i, change)| {
+                let (sign, color) = match change.kind {
+                    ChangeKind::Added => ("+", Color::Green),
+                    ChangeKind::Removed => ("-", Color::Red),
+                    ChangeKind::Changed { .. } => ("~", Color::Yellow),
+                    ChangeKind::Reordered => ("↕", Color::Cyan),
+                };
+                let side = match (&change.kind, change.side) {
+                    (ChangeKind::Changed { .. } | ChangeKind::Reordered, _) => "both ",
+                    (_, Side::Local) => "here ",
+                    (_, Side::Remote) => "disk ",
+                };
+                let choice = if change.accepted { "[x] " } else { "[ ] " };
+
+                let line_style = if i == app.selected {
+                    Style::default().bg(Color::Rgb(30, 30, 30)).fg(Color::Cyan)
+                } else {
+                    Style::default()
+                };
+
+                let subject = match change.kind {
+                    ChangeKind::Reordered => "bookmark order".to_string(),
+                    _ => display_command(app, &change.command).into_owned(),
+                };
+                let mut line = Line::from(vec![
+                    Span::raw(choice),
+                    Span::styled(format!("{} ", sign), Style::default().fg(color)),
+                    Span::styled(side, Style::default().fg(Color::DarkGray)),
+                    Span::raw(subject),
+                ]);
+                if let ChangeKind::Changed { ours, theirs } = &change.kind {
+                    let dim = Style::default().fg(Color::DarkGray);
+                    line.push_span(Span::styled("  here: ", dim));
+                    line.push_span(Span::raw(edit_summary(ours, theirs)));
+                    line.push_span(Span::styled("  disk: ", dim));
+                    line.push_span(Span::raw(edit_summary(theirs, ours)));
+                }
+                if change.kind == ChangeKind::Reordered {
+                    line.push_span(Span::styled(
+                        "  accepted takes the order on disk",
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                }
+                line.style(line_style)
+            })
+            .collect::<Vec<_>>();
+
+        let inner_area = block.inner(area);
+        app.set_size(inner_area.height.into());
+
+        f.render_widget(block, area);
+        f.render_widget(Paragraph::new(items), inner_area);
+    }
+
+    fn status_actions(&self) -> Vec<Span<'static>> {
+        vec![
+            Span::styled(
+                " Space ",
+                Style::default().bg(Color::Magenta).fg(Color::Black),
+            ),
+            Span::raw("Accept/Reject "),
+            Span::styled(" w ", Style::default().bg(Color::Green).fg(Color::Black)),
+            Span::raw("Save "),
+        ]
+    }
+
+    fn map_key(&self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char(' ') => Some(Action::ToggleMergeChoice),
+            KeyCode::Char('w') => Some(Action::ApplyMerge),
+            _ => None,
+        }
+    }
+}
+
+/// The fields of `bookmark` that differ from `other`, e.g.
+/// `title "deploy" #prod #k8s`
+fn edit_summary(bookmark: &Bookmark, other: &Bookmark) -> String {
+    let quoted = |field: &Option<String>| match field {
+        Some(text) => format!("\"{}\"", text),
+        None => "none".to_string(),
+    };
+    let mut parts = Vec::new();
+    if bookmark.title != other.title {
+        parts.push(format!("title {}", quoted(&bookmark.title)));
+    }
+    if bookmark.tags != other.tags {
+        parts.push(
+            bookmark
+                .tag_label()
+                .unwrap_or_else(|| "no tags".to_string()),
+        );
+    }
+    if bookmark.note != other.note {
+        parts.push(format!("note {}", quoted(&bookmark.note)));
+    }
+    if bookmark.group != other.group {
+        parts.push(format!("group {}", quoted(&bookmark.group)));
+    }
+    if bookmark.pinned != other.pinned {
+        parts.push(
+            if bookmark.pinned {
+                "pinned"
+            } else {
+                "unpinned"
+            }
+            .to_string(),
+        );
+    }
+    if bookmark.trashed_at.is_some() != other.trashed_at.is_some() {
+        parts.push(
+            if bookmark.is_trashed() {
+                "in the trash"
+            } else {
+                "not in the trash"
+            }
+            .to_string(),
+        );
+    }
+    if parts.is_empty() {
+        parts.push("other fields".to_string());
+    }
+    parts.join(" ")
+}
@@ -0,0 +1,243 @@
+// views/mod.rs
+//! Per-mode views: each mode renders its own content area and maps its own keys,
+//! while `ui::draw_ui` and `keymap` only take care of the shared chrome.
+
+mod bookmarks;
+mod history;
+mod merge;
+
+use std::borrow::Cow;
+use crossterm::event::KeyEvent;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+use crate::app::{Action, App, Mode, Section};
+use crate::ui::group_digits;
+
+pub trait View {
+    /// Mode label shown in the header and status bar
+    fn label(&self) -> &'static str;
+
+    /// Accent color used for the mode badge
+    fn accent(&self) -> Color;
+
+    /// Render the main content area
+    fn render(&self, f: &mut Frame, area: Rect, app: &App);
+
+    /// Key hints shown in the status bar
+    fn status_actions(&self) -> Vec<Span<'static>>;
+
+    /// Keys specific to this view, consulted before the shared list keys
+    fn map_key(&self, key: KeyEvent) -> Option<Action>;
+}
+
+/// What a view adds around each listed command
+struct RowDecoration<'a> {
+    /// Drawn between the row number and the command
+    marker: Span<'a>,
+    /// Listed in place of the command, which follows dimmed
+    title: Option<String>,
+    /// Right-aligned note, e.g. an occurrence count
+    badge: Option<String>,
+}
+
+/// Content block title with the number of matching and loaded entries,
+/// e.g. " Command History (142/1,000) "
+fn counted_title(name: &str, app: &App) -> String {
+    let (_, matches, loaded) = app.entry_counts();
+    format!(
+        " {} ({}/{}) ",
+        name,
+        group_digits(matches),
+        group_digits(loaded)
+    )
+}
+
+/// Marker drawn in front of bookmarked entries
+fn bookmark_marker<'a>() -> Span<'a> {
+    Span::styled("* ", Style::default().fg(Color::Yellow))
+}
+
+/// Marker drawn in front of pinned bookmarks
+fn pin_marker<'a>() -> Span<'a> {
+    Span::styled("▲ ", Style::default().fg(Color::Magenta))
+}
+
+/// Longest entry prefix rendered in lists; the rest only affects copying
+const MAX_DISPLAY_CHARS: usize = 512;
+
+/// Characters past `MAX_DISPLAY_CHARS` still masked, so a secret cut in half
+/// by the display limit is masked like a whole one
+const MASK_SLACK_CHARS: usize = 256;
+
+/// Stands in for the line breaks of multi-line commands in one-row renderings
+const LINE_BREAK_MARKER: &str = " ⏎ ";
+
+/// Look up the view backing a mode
+pub fn view_for(mode: Mode) -> &'static dyn View {
+    match mode {
+        Mode::History => &history::HistoryView,
+        Mode::Bookmarks => &bookmarks::BookmarksView,
+        Mode::Merge => &merge::MergeView,
+    }
+}
+
+/// Render `list` as numbered rows inside `block`, keeping the selection visible
+fn render_entries<'a>(
+    f: &mut Frame,
+    area: Rect,
+    app: &App,
+    block: Block<'a>,
+    list: &'a [String],
+    decorate: impl Fn(&str) -> RowDecoration<'a>,
+    footer: Option<Line<'a>>,
+) {
+    let inner_area = block.inner(area);
+    app.set_size(inner_area.height.into());
+
+    let mut badges = Vec::new();
+    let mut items = list
+        .iter()
+        .enumerate()
+        .skip(app.skipped_items)
+        .take(inner_area.height.into())
+        .map(|(i, cmd)| {
+            let line_style = if i == app.selected {
+                Style::default().bg(Color::Rgb(30, 30, 30)).fg(Color::Cyan)
+            } else {
+                Style::default()
+            };
+
+            if let Some(group) = app.group_at(i) {
+                let fold = if group.collapsed { "▸" } else { "▾" };
+                let color = if group.section == Section::Trash {
+                    Color::DarkGray
+                } else {
+                    Color::Yellow
+                };
+                return Line::from(vec![
+                    Span::raw("    "),
+                    Span::styled(
+                        format!("{} {}", fold, group.section.name()),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(
+                        format!(" ({})", group.count),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ])
+                .style(line_style);
+            }
+
+            let decoration = decorate(cmd);
+            if let Some(badge) = decoration.badge {
+                badges.push((i - app.skipped_items, badge, line_style));
+            }
+
+            let shown = display_command(app, cmd);
+            let name = match &decoration.title {
+                Some(title) => Cow::Owned(title.clone()),
+                None => Cow::Owned(shown.to_string()),
+            };
+            let mut line = Line::from(vec![
+                Span::styled(
+                    format!("{:3} ", i + 1),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                decoration.marker,
+                if app.is_highlighted(cmd) {
+                    Span::styled(
+                        name,
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    Span::raw(name)
+                },
+            ]);
+            if decoration.title.is_some() {
+                line.push_span(Span::styled(
+                    format!("  {}", shown),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+            line.style(line_style)
+        })
+        .collect::<Vec<_>>();
+    items.extend(footer);
+
+    f.render_widget(block, area);
+    f.render_widget(Paragraph::new(items), inner_area);
+
+    // Badges are drawn over the row's right edge, on top of long commands
+    for (row, badge, line_style) in badges {
+        let width = (badge.chars().count() as u16 + 1).min(inner_area.width);
+        let badge_area = Rect::new(
+            inner_area.right() - width,
+            inner_area.y + row as u16,
+            width,
+            1,
+        );
+        f.render_widget(
+            Paragraph::new(Span::styled(
+                format!(" {}", badge),
+                line_style.fg(Color::DarkGray),
+            )),
+            badge_area,
+        );
+    }
+}
+
+/// Cut very long entries (pasted blobs) for rendering, noting how much is hidden,
+/// and fold multi-line commands onto one row
+pub fn truncate_for_display(cmd: &str) -> Cow<'_, str> {
+    truncate_with_hidden(cmd, 0)
+}
+
+/// `cmd` rendered as a list row: masked like `App::shown_command`, then cut
+/// like `truncate_for_display`. Only the part that can show is masked, so long
+/// pasted blobs don't go through the mask patterns on every frame.
+pub fn display_command<'a>(app: &App, cmd: &'a str) -> Cow<'a, str> {
+    let end = cmd
+        .char_indices()
+        .nth(MAX_DISPLAY_CHARS + MASK_SLACK_CHARS)
+        .map_or(cmd.len(), |(end, _)| end);
+    match app.shown_command(&cmd[..end]) {
+        Cow::Borrowed(_) => truncate_for_display(cmd),
+        Cow::Owned(masked) => {
+            Cow::Owned(truncate_with_hidden(&masked, cmd.len() - end).into_owned())
+        }
+    }
+}
+
+/// `truncate_for_display` of a prefix of a command, whose remaining `hidden`
+/// bytes are counted as cut off
+fn truncate_with_hidden(cmd: &str, hidden: usize) -> Cow<'_, str> {
+    let shown = match cmd.char_indices().nth(MAX_DISPLAY_CHARS) {
+        Some((cut, _)) => Cow::Owned(format!(
+            "{}… (+{})",
+            &cmd[..cut],
+            human_size(cmd.len() - cut + hidden)
+        )),
+        None if hidden > 0 => Cow::Owned(format!("{}… (+{})", cmd, human_size(hidden))),
+        None => Cow::Borrowed(cmd),
+    };
+    if shown.contains('\n') {
+        Cow::Owned(shown.replace('\n', LINE_BREAK_MARKER))
+    } else {
+        shown
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{}B", bytes),
+        1024..=1_048_575 => format!("{}KB", bytes / 1024),
+        _ => format!("{}MB", bytes / 1_048_576),
+    }
+}
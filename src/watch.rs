@@ -0,0 +1,54 @@
+// watch.rs
+//! Live reload: polls the files behind the history for changes, so commands
+//! run in another terminal show up without restarting.
+//!
+//! Polling is deliberate. One `stat` per file a second costs nothing, works
+//! alike on every platform and on network or synced home directories, and
+//! still notices fish replacing its file by rename. A change triggers a full
+//! reload rather than reading the appended tail, because history files are
+//! not append-only: fish, `history -w` and zsh's `fc -W` rewrite them, and
+//! SQLite sources change in place. The reload runs on the loader thread.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use crate::bus::Update;
+
+/// Shells append at most once per command, so this is quick enough to feel live
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Size and modification time, or nothing for a missing file
+type Stamp = Option<(u64, Option<SystemTime>)>;
+
+fn stamp(path: &PathBuf) -> Stamp {
+    fs::metadata(path)
+        .ok()
+        .map(|meta| (meta.len(), meta.modified().ok()))
+}
+
+/// Report the first change to `files` as `Update::HistoryChanged`. The thread
+/// ends after that, or once `current` no longer holds `generation` because a
+/// newer load (with its own watcher) replaced the one being watched.
+pub fn spawn(files: Vec<PathBuf>, generation: u64, current: Arc<AtomicU64>, bus: Sender<Update>) {
+    if files.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        let stamps: Vec<Stamp> = files.iter().map(stamp).collect();
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            if current.load(Ordering::Relaxed) != generation {
+                return;
+            }
+            if files.iter().map(stamp).ne(stamps.iter().cloned()) {
+                let _ = bus.send(Update::HistoryChanged { generation });
+                return;
+            }
+        }
+    });
+}
@@ -0,0 +1,190 @@
+// wsl.rs
+//! History on the other side of WSL: Windows' PowerShell history when running
+//! inside a WSL distribution, and the distributions' shell histories when
+//! running on Windows.
+
+use std::io;
+use std::path::PathBuf;
+use crate::app::ShellType;
+use crate::entry::HistoryEntry;
+use crate::profile::{Phase, StartupProfile};
+use crate::sources::{HistoryProvider, ShellHistory};
+
+/// A history file outside the current environment
+pub struct ForeignHistory {
+    /// Shown as the entry's origin, e.g. "pwsh@windows" or "zsh@Ubuntu"
+    pub label: String,
+    /// Whose format the files are in
+    pub shell: ShellType,
+    /// Read together, as for PowerShell's several PSReadLine locations
+    pub paths: Vec<PathBuf>,
+}
+
+impl HistoryProvider for ForeignHistory {
+    fn label(&self) -> String {
+        self.label.clone()
+    }
+
+    fn location(&self) -> PathBuf {
+        self.paths.first().cloned().unwrap_or_default()
+    }
+
+    fn load(&self, timings: &mut Vec<Phase>) -> io::Result<Vec<HistoryEntry>> {
+        let content = StartupProfile::time(timings, "read history", || {
+            ShellType::read_files(&self.paths)
+        })?;
+        Ok(StartupProfile::time(timings, "parse history", || {
+            self.shell.parse_history(content)
+        }))
+    }
+
+    fn records_timestamps(&self) -> bool {
+        ShellHistory(self.shell.clone()).records_timestamps()
+    }
+
+    fn watched_files(&self) -> Vec<PathBuf> {
+        self.paths.clone()
+    }
+}
+
+/// Histories reachable from the other side, or none outside WSL setups
+#[cfg(target_os = "linux")]
+pub fn other_side() -> Vec<ForeignHistory> {
+    use std::path::Path;
+
+    if !inside_wsl() {
+        return Vec::new();
+    }
+    let Some(profile) = windows_user_profile().and_then(|profile| to_wsl_path(&profile)) else {
+        return Vec::new();
+    };
+
+    let app_data = Path::new(&profile).join("AppData");
+    vec![ForeignHistory {
+        label: "pwsh@windows".to_string(),
+        shell: ShellType::PowerShell,
+        paths: ShellType::windows_powershell_histories(
+            &app_data.join("Roaming"),
+            &app_data.join("Local"),
+        ),
+    }]
+}
+
+/// Histories reachable from the other side, or none outside WSL setups
+#[cfg(target_os = "windows")]
+pub fn other_side() -> Vec<ForeignHistory> {
+    let mut histories = Vec::new();
+    for distro in distributions() {
+        let root = PathBuf::from(format!(r"\\wsl.localhost\{}", distro));
+        let homes = std::fs::read_dir(root.join("home"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .chain([root.join("root")]);
+
+        for home in homes {
+            let files = [
+                (ShellType::Bash, home.join(".bash_history")),
+                (ShellType::Zsh, home.join(".zsh_history")),
+                (ShellType::Fish, home.join(".local/share/fish/fish_history")),
+            ];
+            for (shell, path) in files {
+                if path.exists() {
+                    histories.push(ForeignHistory {
+                        label: format!("{}@{}", shell.name(), distro),
+                        shell,
+                        paths: vec![path],
+                    });
+                }
+            }
+        }
+    }
+    histories
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+pub fn other_side() -> Vec<ForeignHistory> {
+    Vec::new()
+}
+
+/// WSL sets WSL_DISTRO_NAME; older releases only show up in the kernel version
+#[cfg(target_os = "linux")]
+fn inside_wsl() -> bool {
+    std::env::var_os("WSL_DISTRO_NAME").is_some()
+        || std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .is_ok_and(|release| release.to_lowercase().contains("microsoft"))
+}
+
+/// %USERPROFILE%, asked from cmd.exe through WSL interop
+#[cfg(target_os = "linux")]
+fn windows_user_profile() -> Option<String> {
+    use std::process::{Command, Stdio};
+
+    let output = Command::new("cmd.exe")
+        .args(["/d", "/c", "echo %USERPROFILE%"])
+        .stdin(Stdio::null())
+        // cmd.exe warns when started from a Linux directory
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let profile = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !profile.starts_with('%')).then_some(profile)
+}
+
+/// `C:\Users\me` as seen from WSL, e.g. `/mnt/c/Users/me`, honoring the
+/// automount root set in /etc/wsl.conf
+#[cfg(target_os = "linux")]
+fn to_wsl_path(windows: &str) -> Option<String> {
+    let (drive, rest) = windows.split_once(':')?;
+    if drive.len() != 1 || !drive.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let root = std::fs::read_to_string("/etc/wsl.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines()
+                .filter_map(|line| line.trim().strip_prefix("root"))
+                .filter_map(|rest| rest.trim_start().strip_prefix('='))
+                .map(|root| root.trim().trim_matches('"').to_string())
+                .next_back()
+        })
+        .unwrap_or_else(|| "/mnt/".to_string());
+
+    Some(format!(
+        "{}/{}{}",
+        root.trim_end_matches('/'),
+        drive.to_ascii_lowercase(),
+        rest.replace('\\', "/")
+    ))
+}
+
+/// Installed distributions from `wsl.exe --list --quiet`, which writes UTF-16
+#[cfg(target_os = "windows")]
+fn distributions() -> Vec<String> {
+    use std::process::{Command, Stdio};
+
+    let Ok(output) = Command::new("wsl.exe")
+        .args(["--list", "--quiet"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+    else {
+        return Vec::new();
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+
+    let wide: Vec<u16> = output
+        .stdout
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&wide)
+        .lines()
+        .map(|line| line.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}' || c == '\0'))
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
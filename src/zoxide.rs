@@ -0,0 +1,45 @@
+// zoxide.rs
+//! Bridge from command history to zoxide's directory database.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The last existing directory mentioned in `cmd`, e.g. the target of `cd ~/src/app`.
+/// Only absolute and `~` paths are considered, since the original working
+/// directory of a history entry is unknown.
+pub fn directory_in(cmd: &str) -> Option<PathBuf> {
+    let home = directories::BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+
+    cmd.split_whitespace()
+        .rev()
+        .map(|token| token.trim_matches(|c| c == '"' || c == '\''))
+        .filter_map(|token| match token.strip_prefix('~') {
+            Some(rest) => home
+                .as_ref()
+                .map(|home| home.join(rest.trim_start_matches('/'))),
+            None if token.starts_with('/') => Some(PathBuf::from(token)),
+            None => None,
+        })
+        .find(|path| path.is_dir())
+}
+
+/// Record `dir` in zoxide's database
+pub fn add(dir: &Path) -> io::Result<()> {
+    let status = Command::new("zoxide")
+        .arg("add")
+        .arg(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "zoxide add exited with {}",
+            status
+        )))
+    }
+}
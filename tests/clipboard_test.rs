@@ -0,0 +1,107 @@
+// clipboard_test.rs
+//! Adversarial entries must reach every clipboard backend unchanged.
+
+#[allow(dead_code)]
+#[path = "../src/clipboard.rs"]
+mod clipboard;
+
+use clipboard::{base64, powershell_script, validate, ClipboardError, MAX_POWERSHELL_SCRIPT};
+
+const ADVERSARIAL: &[&str] = &[
+    "echo 'single' \"double\"",
+    "echo '' ''' \"\"\"",
+    "printf 'a\\nb'\nsecond line\r\nthird",
+    "echo $(rm -rf ~) `whoami` ${HOME}",
+    "Write-Host $env:USERPROFILE; Remove-Item -Recurse C:\\",
+    "echo ‘curly’ “quotes” ‚low‛",
+    "echo 日本語 émoji 🚀 \u{200b}zero-width",
+    "'; Set-Clipboard pwned; '",
+    "\t  leading and trailing whitespace  \t",
+];
+
+/// Inverse of `clipboard::base64`, to check the round trip
+fn decode_base64(encoded: &str) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        b'/' => 63,
+        _ => panic!("invalid base64 character {:?}", c as char),
+    };
+
+    let mut out = Vec::new();
+    for chunk in encoded.as_bytes().chunks(4) {
+        let data: Vec<u8> = chunk
+            .iter()
+            .copied()
+            .filter(|&c| c != b'=')
+            .map(value)
+            .collect();
+        let n = data
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &v)| n | u32::from(v) << (18 - 6 * i));
+        out.extend(
+            n.to_be_bytes()[1..]
+                .iter()
+                .take(data.len().saturating_sub(1)),
+        );
+    }
+    out
+}
+
+/// The base64 payload embedded in a PowerShell script
+fn payload(script: &str) -> &str {
+    let start =
+        script.find("FromBase64String('").expect("payload start") + "FromBase64String('".len();
+    let end = script[start..].find('\'').expect("payload end") + start;
+    &script[start..end]
+}
+
+#[test]
+fn base64_matches_known_vectors() {
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+}
+
+#[test]
+fn adversarial_entries_round_trip_through_powershell() {
+    for entry in ADVERSARIAL {
+        let script = powershell_script(entry).expect("short entries fit");
+        let decoded = decode_base64(payload(&script));
+        assert_eq!(String::from_utf8(decoded).expect("utf-8"), *entry);
+    }
+}
+
+#[test]
+fn powershell_script_contains_no_entry_text() {
+    for entry in ADVERSARIAL {
+        let script = powershell_script(entry).expect("short entries fit");
+        assert!(payload(&script)
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+/=".contains(c)));
+        // Everything outside the payload is the fixed template
+        assert!(!script.contains('\n'));
+        assert!(!script.contains('$'));
+        assert_eq!(script.matches('\'').count(), 2);
+    }
+}
+
+#[test]
+fn oversized_entries_are_left_to_the_fallback() {
+    assert!(powershell_script(&"x".repeat(MAX_POWERSHELL_SCRIPT)).is_none());
+    let fits = powershell_script(&"x".repeat(MAX_POWERSHELL_SCRIPT / 2)).expect("fits");
+    assert!(fits.len() <= MAX_POWERSHELL_SCRIPT);
+}
+
+#[test]
+fn validation_accepts_adversarial_text_but_not_nul() {
+    for entry in ADVERSARIAL {
+        assert_eq!(validate(entry), Ok(()));
+    }
+    assert_eq!(validate("echo a\0b"), Err(ClipboardError::ContainsNul));
+}